@@ -25,6 +25,7 @@ class ParseArguments:
         parser.add_argument("-w", "--overwrite", action="store_true", default=False)
         parser.add_argument("-r", "--rm-old-version", action="store_true", default=False)
         parser.add_argument("--rm-source", action="store_true", default=False)
+        parser.add_argument("--split-output", action="store_true", default=False)
         parser.add_argument("-x", "--extract", action="store_true")
         parser.add_argument("--extractregex", type=str, default="")
         parser.add_argument("--titlekeys", action="store_true", default=False)
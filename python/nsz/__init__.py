@@ -1,5 +1,7 @@
 from __future__ import annotations
 
+import json
+import sys
 from argparse import ArgumentParser, Namespace
 from pathlib import Path
 from typing import List, Optional, Sequence
@@ -43,10 +45,10 @@ def _expand_files(items: Sequence[str]) -> List[Path]:
     return expanded
 
 
-def compress(filePath, outputDir, args, work=None, amountOfTastkQueued=None):
+def _compress_files(file_paths: Sequence[str], outputDir, args, machine_readable: bool = False):
     compression_level = 18 if _arg(args, "level", default=None) is None else int(_arg(args, "level", default=18))
-    processed = _native.compress(
-        [str(Path(filePath))],
+    return _native.compress(
+        list(file_paths),
         output_dir=_as_str_path(Path(outputDir) if outputDir is not None else None),
         level=compression_level,
         long_distance_mode=bool(_arg(args, "long", default=False)),
@@ -64,15 +66,26 @@ def compress(filePath, outputDir, args, work=None, amountOfTastkQueued=None):
         overwrite=bool(_arg(args, "overwrite", default=False)),
         rm_old_version=bool(_arg(args, "rm_old_version", default=False)),
         rm_source=bool(_arg(args, "rm_source", default=False)),
+        split_output=bool(_arg(args, "split_output", default=False)),
+        journal=_arg(args, "journal", default=None),
+        dry_run=bool(_arg(args, "dry_run", default=False)),
+        progress=_arg(args, "progress", default=None),
+        machine_readable=machine_readable,
     )
+
+
+def compress(filePath, outputDir, args, work=None, amountOfTastkQueued=None):
+    processed = _compress_files([str(Path(filePath))], outputDir, args)
     return Path(processed[0]) if processed else None
 
 
-def decompress(filePath, outputDir, fixPadding, statusReportInfo=None):
+def decompress(filePath, outputDir, fixPadding, statusReportInfo=None, journal=None, split_output=False):
     _native.decompress(
         [str(Path(filePath))],
         output_dir=_as_str_path(Path(outputDir) if outputDir is not None else None),
         fix_padding=bool(fixPadding),
+        split_output=bool(split_output),
+        journal=journal,
     )
 
 
@@ -93,20 +106,79 @@ def verify(
         return
 
 
-def extract(file_paths: Sequence[str], output_dir: Optional[str] = None, extract_regex: Optional[str] = None):
+def extract(
+    file_paths: Sequence[str],
+    output_dir: Optional[str] = None,
+    extract_regex: Optional[str] = None,
+    machine_readable: bool = False,
+    progress=None,
+):
     return _native.extract(
         [str(Path(path)) for path in file_paths],
         output_dir=output_dir,
         extract_regex=extract_regex,
+        progress=progress,
+        machine_readable=machine_readable,
+    )
+
+
+def create(output_file: str, sources: Sequence[str], fix_padding: bool = False, machine_readable: bool = False):
+    return _native.create(
+        output_file=output_file,
+        sources=[str(Path(path)) for path in sources],
+        fix_padding=fix_padding,
+        machine_readable=machine_readable,
+    )
+
+
+def convert(
+    file_paths: Sequence[str],
+    output_dir: Optional[str] = None,
+    split_titles: bool = False,
+    machine_readable: bool = False,
+):
+    return _native.convert(
+        [str(Path(path)) for path in file_paths],
+        output_dir=output_dir,
+        split_titles=split_titles,
+        machine_readable=machine_readable,
     )
 
 
-def create(output_file: str, sources: Sequence[str], fix_padding: bool = False):
-    return _native.create(output_file=output_file, sources=[str(Path(path)) for path in sources], fix_padding=fix_padding)
+def merge(file_paths: Sequence[str], output_file: str, machine_readable: bool = False):
+    return _native.merge(
+        [str(Path(path)) for path in file_paths],
+        str(Path(output_file)),
+        machine_readable=machine_readable,
+    )
 
 
-def titlekeys(file_paths: Sequence[str]):
-    return _native.titlekeys([str(Path(path)) for path in file_paths])
+def split(file_paths: Sequence[str], output_dir: Optional[str] = None, machine_readable: bool = False):
+    return _native.split(
+        [str(Path(path)) for path in file_paths],
+        output_dir=output_dir,
+        machine_readable=machine_readable,
+    )
+
+
+def trim(file_paths: Sequence[str], output_dir: Optional[str] = None, machine_readable: bool = False):
+    return _native.trim(
+        [str(Path(path)) for path in file_paths],
+        output_dir=output_dir,
+        machine_readable=machine_readable,
+    )
+
+
+def untrim(file_paths: Sequence[str], output_dir: Optional[str] = None, machine_readable: bool = False):
+    return _native.untrim(
+        [str(Path(path)) for path in file_paths],
+        output_dir=output_dir,
+        machine_readable=machine_readable,
+    )
+
+
+def titlekeys(file_paths: Sequence[str], machine_readable: bool = False):
+    return _native.titlekeys([str(Path(path)) for path in file_paths], machine_readable=machine_readable)
 
 
 def undupe_files(
@@ -119,6 +191,7 @@ def undupe_files(
     whitelist: Optional[str] = None,
     blacklist: Optional[str] = None,
     old_versions: bool = False,
+    machine_readable: bool = False,
 ):
     return _native.undupe(
         [str(Path(path)) for path in file_paths],
@@ -130,6 +203,7 @@ def undupe_files(
         whitelist=whitelist,
         blacklist=blacklist,
         old_versions=old_versions,
+        machine_readable=machine_readable,
     )
 
 
@@ -155,6 +229,7 @@ def _build_parser() -> ArgumentParser:
     parser.add_argument("-w", "--overwrite", action="store_true", default=False)
     parser.add_argument("-r", "--rm-old-version", action="store_true", default=False)
     parser.add_argument("--rm-source", action="store_true", default=False)
+    parser.add_argument("--split-output", action="store_true", default=False)
     parser.add_argument("-x", "--extract", action="store_true")
     parser.add_argument("--extractregex", type=str, default="")
     parser.add_argument("--titlekeys", action="store_true", default=False)
@@ -167,9 +242,117 @@ def _build_parser() -> ArgumentParser:
     parser.add_argument("--undupe-blacklist", type=str, default="")
     parser.add_argument("--undupe-old-versions", action="store_true", default=False)
     parser.add_argument("-c", "--create")
+    parser.add_argument("--continue-on-error", action="store_true", default=False)
+    parser.add_argument("--journal", type=str, default=None)
+    parser.add_argument("--dry-run", action="store_true", default=False)
+    parser.add_argument("--machine-readable", action="store_true", default=False)
     return parser
 
 
+# Exit codes shared with the native `nsz` binary, keyed by error kind.
+_EXIT_CODES = {
+    "io": 5,
+    "missing_required_key": 4,
+    "invalid_keys": 4,
+    "invalid_key_file": 4,
+    "parity_mismatch": 3,
+    "hash_mismatch": 3,
+}
+
+
+class _Events:
+    """Prints JSON-lines events from the native layer and tallies them for the summary."""
+
+    def __init__(self):
+        self.counts = {"processed": 0, "skipped": 0, "resumed": 0, "estimated": 0, "verified": 0, "failed": 0}
+        self.error = None
+        self.verifying = False
+
+    def emit(self, lines) -> bool:
+        for line in lines:
+            print(line, flush=True)
+            event = json.loads(line)
+            if event["event"] == "file":
+                self.counts[event["status"]] += 1
+            elif event["event"] == "error":
+                self.counts["failed"] += 1
+                if self.error is None:
+                    self.error = {"kind": event["kind"], "message": event["message"]}
+        return self.error is None
+
+    def finish_batch(self) -> None:
+        """Replaces the first error of a batch that continued past failures with a batch failure."""
+        if self.counts["failed"]:
+            total = sum(self.counts.values())
+            self.error = {"kind": "batch_failed", "message": f"{self.counts['failed']} of {total} files failed"}
+
+    def summary(self) -> int:
+        exit_code = 0
+        if self.error is not None:
+            exit_code = _EXIT_CODES.get(self.error["kind"], 3 if self.verifying else 1)
+        summary = {"event": "summary", **self.counts, "exit_code": exit_code, "error": self.error}
+        print(json.dumps(summary, separators=(",", ":")), flush=True)
+        return exit_code
+
+
+def _main_machine_readable(args: Namespace, files: List[Path], output_dir: Optional[Path]) -> int:
+    events = _Events()
+    if args.C:
+        for file_path in files:
+            if file_path.suffix.lower() not in {".nsp", ".xci", ".nca"}:
+                continue
+            out = output_dir if output_dir is not None else file_path.parent
+            if not events.emit(_compress_files([str(file_path)], out, args, machine_readable=True)):
+                if not args.continue_on_error:
+                    break
+    elif args.D:
+        for file_path in files:
+            if file_path.suffix.lower() not in {".nsz", ".xcz", ".ncz"}:
+                continue
+            out = output_dir if output_dir is not None else file_path.parent
+            lines = _native.decompress(
+                [str(file_path)],
+                output_dir=str(out),
+                fix_padding=bool(args.fix_padding),
+                split_output=bool(args.split_output),
+                journal=args.journal,
+                machine_readable=True,
+            )
+            if not events.emit(lines):
+                if not args.continue_on_error:
+                    break
+    elif args.extract:
+        events.emit(extract([str(path) for path in files], _as_str_path(output_dir), args.extractregex or None, True))
+    elif args.create:
+        events.emit(create(args.create, [str(path) for path in files], fix_padding=args.fix_padding, machine_readable=True))
+    elif args.titlekeys:
+        events.emit(titlekeys([str(path) for path in files], machine_readable=True))
+    elif args.undupe or args.undupe_dryrun:
+        events.emit(
+            undupe_files(
+                [str(path) for path in files],
+                output_dir=_as_str_path(output_dir),
+                dry_run=args.undupe_dryrun,
+                rename=args.undupe_rename,
+                hardlink=args.undupe_hardlink,
+                priority_list=args.undupe_prioritylist or None,
+                whitelist=args.undupe_whitelist or None,
+                blacklist=args.undupe_blacklist or None,
+                old_versions=args.undupe_old_versions,
+                machine_readable=True,
+            )
+        )
+    elif args.verify:
+        events.verifying = True
+        for file_path in files:
+            if not events.emit(_native.verify([str(file_path)], fix_padding=bool(args.fix_padding), machine_readable=True)):
+                if not args.continue_on_error:
+                    break
+    if args.continue_on_error:
+        events.finish_batch()
+    return events.summary()
+
+
 def main(argv: Optional[Sequence[str]] = None) -> int:
     parser = _build_parser()
     args = parser.parse_args(argv)
@@ -178,19 +361,42 @@ def main(argv: Optional[Sequence[str]] = None) -> int:
     if output_dir is not None and not output_dir.exists():
         raise RuntimeError(f'Output directory "{output_dir}" does not exist')
 
+    if args.machine_readable:
+        return _main_machine_readable(args, files, output_dir)
+
     if args.C:
+        failed = 0
         for file_path in files:
             if file_path.suffix.lower() not in {".nsp", ".xci", ".nca"}:
                 continue
-            compress(file_path, output_dir if output_dir is not None else file_path.parent, args)
-        return 0
+            try:
+                compress(file_path, output_dir if output_dir is not None else file_path.parent, args)
+            except RuntimeError as exc:
+                if not args.continue_on_error:
+                    raise
+                failed += 1
+                print(f"nsz: error: {file_path}: {exc}", file=sys.stderr)
+        return 1 if failed else 0
 
     if args.D:
+        failed = 0
         for file_path in files:
             if file_path.suffix.lower() not in {".nsz", ".xcz", ".ncz"}:
                 continue
-            decompress(file_path, output_dir if output_dir is not None else file_path.parent, args.fix_padding)
-        return 0
+            try:
+                decompress(
+                    file_path,
+                    output_dir if output_dir is not None else file_path.parent,
+                    args.fix_padding,
+                    journal=args.journal,
+                    split_output=args.split_output,
+                )
+            except RuntimeError as exc:
+                if not args.continue_on_error:
+                    raise
+                failed += 1
+                print(f"nsz: error: {file_path}: {exc}", file=sys.stderr)
+        return 1 if failed else 0
 
     if args.extract:
         extract([str(path) for path in files], _as_str_path(output_dir), args.extractregex or None)
@@ -219,9 +425,16 @@ def main(argv: Optional[Sequence[str]] = None) -> int:
         return 0
 
     if args.verify:
+        failed = 0
         for file_path in files:
-            verify(file_path, args.fix_padding, True, True)
-        return 0
+            try:
+                verify(file_path, args.fix_padding, True, True)
+            except VerificationException as exc:
+                if not args.continue_on_error:
+                    raise
+                failed += 1
+                print(f"nsz: error: {file_path}: {exc}", file=sys.stderr)
+        return 3 if failed else 0
 
     parser.print_help()
     return 0
@@ -234,6 +447,11 @@ __all__ = [
     "compress",
     "decompress",
     "verify",
+    "convert",
+    "merge",
+    "split",
+    "trim",
+    "untrim",
     "extract",
     "create",
     "titlekeys",
@@ -0,0 +1,862 @@
+//! Native `nsz` command line, accepting the upstream Python `nsz` flags.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use nsz_rs::fs_ops::split_files::logical_path;
+use nsz_rs::{
+    CompressRequest, CreateRequest, DecompressRequest, ExtractRequest, FileReport, FileStatus,
+    NszError, OperationReport, TitleKeysRequest, UndupeRequest, VerifyReport, VerifyRequest,
+    VerifyVerdict,
+};
+
+/// An operation failed for a reason not covered by a more specific code.
+const EXIT_FAILURE: u8 = 1;
+/// The command line could not be parsed or names a missing output directory.
+const EXIT_USAGE: u8 = 2;
+/// A file failed verification.
+const EXIT_VERIFY_FAILED: u8 = 3;
+/// Keys are missing, malformed or inconsistent.
+const EXIT_KEYS: u8 = 4;
+/// Reading or writing a file failed.
+const EXIT_IO: u8 = 5;
+
+const USAGE: &str =
+    "usage: nsz [-h] [-C] [-D] [-l LEVEL] [-L] [-B] [-S] [-s BS] [-V] [-Q] [-K] [-F]
+           [-p] [-P] [-t THREADS] [-m MULTI] [-o OUTPUT] [-w] [-r] [--rm-source]
+           [--split-output] [--keys KEYS] [--title-keys TITLE_KEYS]
+           [-x] [--extractregex EXTRACTREGEX] [--titlekeys] [--undupe]
+           [--undupe-dryrun] [--undupe-rename] [--undupe-hardlink]
+           [--undupe-prioritylist UNDUPE_PRIORITYLIST]
+           [--undupe-whitelist UNDUPE_WHITELIST]
+           [--undupe-blacklist UNDUPE_BLACKLIST] [--undupe-old-versions]
+           [-c CREATE] [--continue-on-error] [--journal JOURNAL]
+           [--dry-run] [--machine-readable]
+           [file ...]";
+
+const HELP: &str = "
+positional arguments:
+  file                  files or directories to process
+
+options:
+  -h, --help            show this help message and exit
+  -C                    compress .nsp/.xci/.nca files
+  -D                    decompress .nsz/.xcz/.ncz files
+  -l, --level LEVEL     zstd compression level (default 18)
+  -L, --long            enable zstd long-distance matching
+  -B, --block           use block compression
+  -S, --solid           use solid compression
+  -s, --bs BS           block size exponent for block compression (default 20)
+  -V, --verify          verify files, or verify after compressing with -C
+  -Q, --quick-verify    quick verification after compressing
+  -K, --keep            keep all XCI partitions when compressing
+  -F, --fix-padding     apply upstream padding fixes
+  -p, --parseCnmt       parse CNMTs when compressing
+  -P, --alwaysParseCnmt
+                        always parse CNMTs when compressing
+  -t, --threads THREADS
+                        compression threads (default -1)
+  -m, --multi MULTI     parallel files for compatibility paths (default 4)
+  -o, --output OUTPUT   existing output directory (default: next to each input)
+  -w, --overwrite       overwrite existing outputs
+  -r, --rm-old-version  remove older versions of compressed titles
+  --rm-source           remove source files after compressing
+  --split-output        with -C/-D, write FAT32-sized parts (.xc0, .xc1, ... for
+                        .xci and a folder of 00, 01, ... otherwise)
+  --keys KEYS           prod.keys file to use instead of the default locations
+  --title-keys TITLE_KEYS
+                        title.keys file to use instead of the default locations
+  -x, --extract         extract container contents
+  --extractregex EXTRACTREGEX
+                        only extract entries matching this regex
+  --titlekeys           collect title keys from tickets
+  --undupe              remove duplicate files
+  --undupe-dryrun       report duplicates without removing them
+  --undupe-rename       rename kept files while deduplicating
+  --undupe-hardlink     hardlink duplicates instead of removing them
+  --undupe-prioritylist UNDUPE_PRIORITYLIST
+                        regex of files to keep first
+  --undupe-whitelist UNDUPE_WHITELIST
+                        regex of files to always keep
+  --undupe-blacklist UNDUPE_BLACKLIST
+                        regex of files to always remove
+  --undupe-old-versions remove older versions while deduplicating
+  -c, --create CREATE   create an .nsp from the given files
+  --continue-on-error   keep going after a file fails and exit with the
+                        status of a failed batch once all files are done
+  --journal JOURNAL     record finished inputs of -C/-D in this file and skip
+                        them when the batch is run again
+  --dry-run             with -C, estimate output sizes, ratios and times from
+                        a sample of each NCA without writing anything
+  --machine-readable    print one JSON object per line: a file event per
+                        input and a closing summary event (needs the serde
+                        feature)
+
+exit status:
+  0 success, 1 operation failed, 2 usage error, 3 verification failed,
+  4 missing or invalid keys, 5 I/O error";
+
+const LONG_OPTIONS: &[&str] = &[
+    "help",
+    "level",
+    "long",
+    "block",
+    "solid",
+    "bs",
+    "verify",
+    "quick-verify",
+    "keep",
+    "fix-padding",
+    "parseCnmt",
+    "alwaysParseCnmt",
+    "threads",
+    "multi",
+    "output",
+    "overwrite",
+    "rm-old-version",
+    "rm-source",
+    "split-output",
+    "keys",
+    "title-keys",
+    "extract",
+    "extractregex",
+    "titlekeys",
+    "undupe",
+    "undupe-dryrun",
+    "undupe-rename",
+    "undupe-hardlink",
+    "undupe-prioritylist",
+    "undupe-whitelist",
+    "undupe-blacklist",
+    "undupe-old-versions",
+    "create",
+    "continue-on-error",
+    "journal",
+    "dry-run",
+    "machine-readable",
+];
+
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &["nsp", "xci", "nca"];
+const DECOMPRESSIBLE_EXTENSIONS: &[&str] = &["nsz", "xcz", "ncz"];
+const VERIFIABLE_EXTENSIONS: &[&str] = &["nsp", "nsz", "xci", "xcz", "nca", "ncz"];
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{USAGE}");
+            eprintln!("nsz: error: {message}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if args.help {
+        println!("{USAGE}\n{HELP}");
+        return ExitCode::SUCCESS;
+    }
+
+    let mut events = Events {
+        machine_readable: args.machine_readable,
+        ..Default::default()
+    };
+    let result = run(&args, &mut events);
+    events.summary(result.as_ref().err());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("nsz: error: {err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Parsed command line, with the upstream defaults.
+#[derive(Debug)]
+struct Args {
+    files: Vec<PathBuf>,
+    help: bool,
+    compress: bool,
+    decompress: bool,
+    level: i32,
+    long: bool,
+    block: bool,
+    solid: bool,
+    bs: u8,
+    verify: bool,
+    quick_verify: bool,
+    keep: bool,
+    fix_padding: bool,
+    parse_cnmt: bool,
+    always_parse_cnmt: bool,
+    threads: i32,
+    multi: i32,
+    output: Option<PathBuf>,
+    overwrite: bool,
+    rm_old_version: bool,
+    rm_source: bool,
+    split_output: bool,
+    keys: Option<PathBuf>,
+    title_keys: Option<PathBuf>,
+    extract: bool,
+    extract_regex: String,
+    titlekeys: bool,
+    undupe: bool,
+    undupe_dryrun: bool,
+    undupe_rename: bool,
+    undupe_hardlink: bool,
+    undupe_priority_list: String,
+    undupe_whitelist: String,
+    undupe_blacklist: String,
+    undupe_old_versions: bool,
+    create: Option<PathBuf>,
+    continue_on_error: bool,
+    journal: Option<PathBuf>,
+    dry_run: bool,
+    machine_readable: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            help: false,
+            compress: false,
+            decompress: false,
+            level: 18,
+            long: false,
+            block: false,
+            solid: false,
+            bs: 20,
+            verify: false,
+            quick_verify: false,
+            keep: false,
+            fix_padding: false,
+            parse_cnmt: false,
+            always_parse_cnmt: false,
+            threads: -1,
+            multi: 4,
+            output: None,
+            overwrite: false,
+            rm_old_version: false,
+            rm_source: false,
+            split_output: false,
+            keys: None,
+            title_keys: None,
+            extract: false,
+            extract_regex: String::new(),
+            titlekeys: false,
+            undupe: false,
+            undupe_dryrun: false,
+            undupe_rename: false,
+            undupe_hardlink: false,
+            undupe_priority_list: String::new(),
+            undupe_whitelist: String::new(),
+            undupe_blacklist: String::new(),
+            undupe_old_versions: false,
+            create: None,
+            continue_on_error: false,
+            journal: None,
+            dry_run: false,
+            machine_readable: false,
+        }
+    }
+}
+
+impl Args {
+    /// Parses arguments the way upstream's argparse parser does: short flags may be
+    /// grouped (`-CV`), values may be attached (`-l19`, `--level=19`) and `--` ends options.
+    fn parse(argv: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = Self::default();
+        let mut argv = argv.into_iter();
+        while let Some(arg) = argv.next() {
+            if arg == "--" {
+                args.files.extend(argv.by_ref().map(PathBuf::from));
+                break;
+            }
+
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                if !LONG_OPTIONS.contains(&name) {
+                    return Err(format!("unrecognized arguments: {arg}"));
+                }
+                if takes_value(name) {
+                    let value = match inline {
+                        Some(value) => value,
+                        None => next_value(&mut argv, name)?,
+                    };
+                    args.set_value(name, &value)?;
+                } else if inline.is_some() {
+                    return Err(format!("argument --{name}: ignored explicit argument"));
+                } else {
+                    args.set_flag(name);
+                }
+                continue;
+            }
+
+            if let Some(cluster) = arg.strip_prefix('-').filter(|cluster| !cluster.is_empty()) {
+                for (pos, flag) in cluster.char_indices() {
+                    let name = short_option(flag)
+                        .ok_or_else(|| format!("unrecognized arguments: {arg}"))?;
+                    if takes_value(name) {
+                        let attached = &cluster[pos + flag.len_utf8()..];
+                        let value = if attached.is_empty() {
+                            next_value(&mut argv, name)?
+                        } else {
+                            attached.to_string()
+                        };
+                        args.set_value(name, &value)?;
+                        break;
+                    }
+                    args.set_flag(name);
+                }
+                continue;
+            }
+
+            args.files.push(PathBuf::from(arg));
+        }
+        Ok(args)
+    }
+
+    fn set_flag(&mut self, name: &str) {
+        let flag = match name {
+            "help" => &mut self.help,
+            "C" => &mut self.compress,
+            "D" => &mut self.decompress,
+            "long" => &mut self.long,
+            "block" => &mut self.block,
+            "solid" => &mut self.solid,
+            "verify" => &mut self.verify,
+            "quick-verify" => &mut self.quick_verify,
+            "keep" => &mut self.keep,
+            "fix-padding" => &mut self.fix_padding,
+            "parseCnmt" => &mut self.parse_cnmt,
+            "alwaysParseCnmt" => &mut self.always_parse_cnmt,
+            "overwrite" => &mut self.overwrite,
+            "rm-old-version" => &mut self.rm_old_version,
+            "rm-source" => &mut self.rm_source,
+            "split-output" => &mut self.split_output,
+            "extract" => &mut self.extract,
+            "titlekeys" => &mut self.titlekeys,
+            "undupe" => &mut self.undupe,
+            "undupe-dryrun" => &mut self.undupe_dryrun,
+            "undupe-rename" => &mut self.undupe_rename,
+            "undupe-hardlink" => &mut self.undupe_hardlink,
+            "undupe-old-versions" => &mut self.undupe_old_versions,
+            "continue-on-error" => &mut self.continue_on_error,
+            "dry-run" => &mut self.dry_run,
+            "machine-readable" => &mut self.machine_readable,
+            _ => return,
+        };
+        *flag = true;
+    }
+
+    fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "level" => self.level = parse_int(name, value)?,
+            "bs" => self.bs = parse_int(name, value)?,
+            "threads" => self.threads = parse_int(name, value)?,
+            "multi" => self.multi = parse_int(name, value)?,
+            "output" => self.output = Some(PathBuf::from(value)),
+            "keys" => self.keys = Some(PathBuf::from(value)),
+            "title-keys" => self.title_keys = Some(PathBuf::from(value)),
+            "extractregex" => self.extract_regex = value.to_string(),
+            "undupe-prioritylist" => self.undupe_priority_list = value.to_string(),
+            "undupe-whitelist" => self.undupe_whitelist = value.to_string(),
+            "undupe-blacklist" => self.undupe_blacklist = value.to_string(),
+            "create" => self.create = Some(PathBuf::from(value)),
+            "journal" => self.journal = Some(PathBuf::from(value)),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn short_option(flag: char) -> Option<&'static str> {
+    Some(match flag {
+        'h' => "help",
+        'C' => "C",
+        'D' => "D",
+        'l' => "level",
+        'L' => "long",
+        'B' => "block",
+        'S' => "solid",
+        's' => "bs",
+        'V' => "verify",
+        'Q' => "quick-verify",
+        'K' => "keep",
+        'F' => "fix-padding",
+        'p' => "parseCnmt",
+        'P' => "alwaysParseCnmt",
+        't' => "threads",
+        'm' => "multi",
+        'o' => "output",
+        'w' => "overwrite",
+        'r' => "rm-old-version",
+        'x' => "extract",
+        'c' => "create",
+        _ => return None,
+    })
+}
+
+fn takes_value(name: &str) -> bool {
+    matches!(
+        name,
+        "level"
+            | "bs"
+            | "threads"
+            | "multi"
+            | "output"
+            | "keys"
+            | "title-keys"
+            | "extractregex"
+            | "undupe-prioritylist"
+            | "undupe-whitelist"
+            | "undupe-blacklist"
+            | "create"
+            | "journal"
+    )
+}
+
+fn next_value(argv: &mut impl Iterator<Item = String>, name: &str) -> Result<String, String> {
+    argv.next()
+        .ok_or_else(|| format!("argument --{name}: expected one argument"))
+}
+
+fn parse_int<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("argument --{name}: invalid int value: '{value}'"))
+}
+
+/// Failure of a command, carrying what its exit code is derived from.
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Operation(NszError),
+    Verification(NszError),
+}
+
+impl CliError {
+    #[cfg(feature = "serde")]
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Usage(_) => "usage",
+            Self::Operation(err) | Self::Verification(err) => err.kind(),
+        }
+    }
+
+    fn exit_code(&self) -> u8 {
+        let (Self::Operation(err) | Self::Verification(err)) = self else {
+            return EXIT_USAGE;
+        };
+        match err.root() {
+            NszError::Io(_) => EXIT_IO,
+            NszError::MissingRequiredKey { .. }
+            | NszError::InvalidKeys { .. }
+            | NszError::InvalidKeyFile { .. } => EXIT_KEYS,
+            NszError::ParityMismatch { .. } | NszError::HashMismatch { .. } => EXIT_VERIFY_FAILED,
+            _ if matches!(self, Self::Verification(_)) => EXIT_VERIFY_FAILED,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(message) => f.write_str(message),
+            Self::Operation(err) | Self::Verification(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Dispatches in the same order as upstream: the first requested action runs.
+fn run(args: &Args, events: &mut Events) -> Result<(), CliError> {
+    if args.machine_readable && !cfg!(feature = "serde") {
+        return Err(CliError::Usage(
+            "--machine-readable needs nsz built with the serde feature".to_string(),
+        ));
+    }
+    let files = expand_files(&args.files);
+    if let Some(output) = &args.output {
+        if !output.is_dir() {
+            return Err(CliError::Usage(format!(
+                "Output directory \"{}\" does not exist",
+                output.display()
+            )));
+        }
+    }
+
+    if args.compress {
+        for file in with_extensions(&files, COMPRESSIBLE_EXTENSIONS) {
+            let started = Instant::now();
+            let result = nsz_rs::compress(&CompressRequest {
+                files: vec![file.clone()],
+                output_dir: Some(output_dir_for(args, file)),
+                level: args.level,
+                long_distance_mode: args.long,
+                block: args.block,
+                solid: args.solid,
+                block_size_exponent: args.bs,
+                verify: args.verify,
+                quick_verify: args.quick_verify,
+                keep: args.keep,
+                fix_padding: args.fix_padding,
+                parse_cnmt: args.parse_cnmt,
+                always_parse_cnmt: args.always_parse_cnmt,
+                multi: args.multi,
+                threads: args.threads,
+                overwrite: args.overwrite,
+                rm_old_version: args.rm_old_version,
+                rm_source: args.rm_source,
+                split_output: args.split_output,
+                keys_file: args.keys.clone(),
+                title_keys_file: args.title_keys.clone(),
+                journal: args.journal.clone(),
+                dry_run: args.dry_run,
+                ..Default::default()
+            });
+            match events.check(file, started, result) {
+                Ok(report) => events.operation(&report),
+                Err(err) if args.continue_on_error => events.continued(file, &err),
+                Err(err) => return Err(CliError::Operation(err)),
+            }
+        }
+        return events.batch_result().map_err(CliError::Operation);
+    }
+
+    if args.decompress {
+        for file in with_extensions(&files, DECOMPRESSIBLE_EXTENSIONS) {
+            let started = Instant::now();
+            let result = nsz_rs::decompress(&DecompressRequest {
+                files: vec![file.clone()],
+                output_dir: Some(output_dir_for(args, file)),
+                fix_padding: args.fix_padding,
+                split_output: args.split_output,
+                keys_file: args.keys.clone(),
+                journal: args.journal.clone(),
+                ..Default::default()
+            });
+            match events.check(file, started, result) {
+                Ok(report) => events.operation(&report),
+                Err(err) if args.continue_on_error => events.continued(file, &err),
+                Err(err) => return Err(CliError::Operation(err)),
+            }
+        }
+        return events.batch_result().map_err(CliError::Operation);
+    }
+
+    if args.extract {
+        let report = nsz_rs::extract(&ExtractRequest {
+            files,
+            output_dir: args.output.clone(),
+            extract_regex: non_empty(&args.extract_regex),
+            keys_file: args.keys.clone(),
+            ..Default::default()
+        })
+        .map_err(CliError::Operation)?;
+        events.operation(&report);
+        return Ok(());
+    }
+
+    if let Some(output_file) = &args.create {
+        let report = nsz_rs::create(&CreateRequest {
+            output_file: Some(output_file.clone()),
+            sources: files,
+            fix_padding: args.fix_padding,
+            ..Default::default()
+        })
+        .map_err(CliError::Operation)?;
+        events.operation(&report);
+        return Ok(());
+    }
+
+    if args.titlekeys {
+        let report = nsz_rs::titlekeys(&TitleKeysRequest {
+            files,
+            keys_file: args.keys.clone(),
+            ..Default::default()
+        })
+        .map_err(CliError::Operation)?;
+        events.operation(&report);
+        return Ok(());
+    }
+
+    if args.undupe || args.undupe_dryrun {
+        let report = nsz_rs::undupe(&UndupeRequest {
+            files,
+            output_dir: args.output.clone(),
+            dry_run: args.undupe_dryrun,
+            rename: args.undupe_rename,
+            hardlink: args.undupe_hardlink,
+            priority_list: non_empty(&args.undupe_priority_list),
+            whitelist: non_empty(&args.undupe_whitelist),
+            blacklist: non_empty(&args.undupe_blacklist),
+            old_versions: args.undupe_old_versions,
+            ..Default::default()
+        })
+        .map_err(CliError::Operation)?;
+        events.operation(&report);
+        return Ok(());
+    }
+
+    if args.verify {
+        for file in with_extensions(&files, VERIFIABLE_EXTENSIONS) {
+            let started = Instant::now();
+            let result = nsz_rs::verify(&VerifyRequest {
+                files: vec![file.clone()],
+                fix_padding: args.fix_padding,
+                keys_file: args.keys.clone(),
+                ..Default::default()
+            });
+            match events.check(file, started, result) {
+                Ok(report) => events.verification(&report),
+                Err(err) if args.continue_on_error => events.continued(file, &err),
+                Err(err) => return Err(CliError::Verification(err)),
+            }
+        }
+        return events.batch_result().map_err(CliError::Verification);
+    }
+
+    if !args.machine_readable {
+        println!("{USAGE}\n{HELP}");
+    }
+    Ok(())
+}
+
+/// Prints results as plain paths, or as JSON-lines events with `--machine-readable`.
+#[derive(Debug, Default)]
+struct Events {
+    machine_readable: bool,
+    processed: usize,
+    skipped: usize,
+    resumed: usize,
+    estimated: usize,
+    verified: usize,
+    failed: usize,
+    failures: Vec<FileReport>,
+}
+
+impl Events {
+    fn operation(&mut self, report: &OperationReport) {
+        self.count(&report.files);
+        if self.machine_readable {
+            emit_files(&report.files);
+            return;
+        }
+        for path in &report.removed_temp_files {
+            println!("[CLEANED] {}", path.display());
+        }
+        for path in &report.processed_files {
+            println!("{}", path.display());
+        }
+        for path in &report.skipped_files {
+            println!("[SKIPPED] {}", path.display());
+        }
+        for path in &report.resumed_files {
+            println!("[RESUMED] {}", path.display());
+        }
+        for file in &report.files {
+            if let (FileStatus::Estimated, Some(output), Some(size), Some(duration)) = (
+                file.status,
+                &file.output,
+                file.output_size,
+                file.estimated_duration,
+            ) {
+                println!(
+                    "[ESTIMATED] {} ({size} bytes, ratio {:.3}, ~{:.1}s)",
+                    output.display(),
+                    file.ratio.unwrap_or_default(),
+                    duration.as_secs_f64()
+                );
+            }
+        }
+    }
+
+    fn verification(&mut self, report: &VerifyReport) {
+        self.count(&report.files);
+        if self.machine_readable {
+            emit_files(&report.files);
+            return;
+        }
+        for file in &report.files {
+            let label = match file.verdict() {
+                Some(VerifyVerdict::Unverifiable) => "UNVERIFIABLE",
+                _ => "VERIFIED",
+            };
+            println!("[{label}] {}", file.input.display());
+        }
+    }
+
+    /// Passes `result` through, reporting `file` as failed when it is an error.
+    fn check<T>(
+        &mut self,
+        file: &Path,
+        started: Instant,
+        result: Result<T, NszError>,
+    ) -> Result<T, NszError> {
+        if let Err(err) = &result {
+            let failure = FileReport::failed(file, started.elapsed(), err);
+            if self.machine_readable {
+                emit_files(std::slice::from_ref(&failure));
+            }
+            self.failed += 1;
+            self.failures.push(failure);
+        }
+        result
+    }
+
+    /// Reports a failure that `--continue-on-error` moves past.
+    fn continued(&self, file: &Path, err: &NszError) {
+        if !self.machine_readable {
+            eprintln!("nsz: error: {}: {err}", file.display());
+        }
+    }
+
+    /// Fails a per-file batch that moved past failures.
+    fn batch_result(&mut self) -> Result<(), NszError> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        Err(NszError::BatchFailed {
+            failed: self.failures.len(),
+            total: self.processed
+                + self.skipped
+                + self.resumed
+                + self.estimated
+                + self.verified
+                + self.failed,
+            files: std::mem::take(&mut self.failures),
+        })
+    }
+
+    fn count(&mut self, files: &[FileReport]) {
+        for file in files {
+            match file.status {
+                FileStatus::Processed => self.processed += 1,
+                FileStatus::Skipped => self.skipped += 1,
+                FileStatus::Resumed => self.resumed += 1,
+                FileStatus::Estimated => self.estimated += 1,
+                FileStatus::Verified => self.verified += 1,
+                FileStatus::Failed => self.failed += 1,
+            }
+        }
+    }
+
+    /// Closes a machine-readable run with its totals and exit status.
+    fn summary(&self, error: Option<&CliError>) {
+        if !self.machine_readable {
+            return;
+        }
+        #[cfg(feature = "serde")]
+        println!(
+            "{}",
+            serde_json::json!({
+                "event": "summary",
+                "processed": self.processed,
+                "skipped": self.skipped,
+                "resumed": self.resumed,
+                "estimated": self.estimated,
+                "verified": self.verified,
+                "failed": self.failed,
+                "exit_code": error.map_or(0, CliError::exit_code),
+                "error": error.map(|err| serde_json::json!({
+                    "kind": err.kind(),
+                    "message": err.to_string(),
+                })),
+            })
+        );
+        #[cfg(not(feature = "serde"))]
+        let _ = error;
+    }
+}
+
+#[cfg(feature = "serde")]
+fn emit_files(files: &[FileReport]) {
+    for file in files {
+        println!("{}", file.to_json_line());
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn emit_files(_files: &[FileReport]) {}
+
+/// Expands directories into the files below them in sorted order, keeping split folders
+/// and `.xc0` parts as single inputs and dropping missing paths.
+fn expand_files(items: &[PathBuf]) -> Vec<PathBuf> {
+    let mut expanded = Vec::new();
+    for item in items {
+        if item.is_dir() && !is_split_folder(item) {
+            collect_dir(item, &mut expanded);
+        } else if item.exists() {
+            expanded.push(item.clone());
+        }
+    }
+    expanded
+}
+
+fn collect_dir(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() && !is_split_folder(&path) {
+            collect_dir(&path, out);
+        } else if !is_trailing_xci_part(&path) {
+            out.push(path);
+        }
+    }
+}
+
+fn is_split_folder(path: &Path) -> bool {
+    path.join("00").is_file() && has_extension(path, VERIFIABLE_EXTENSIONS)
+}
+
+/// Returns whether `path` is an `.xc1`, `.xc2`, ... part read along with its `.xc0`.
+fn is_trailing_xci_part(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| {
+            ext.to_ascii_lowercase()
+                .strip_prefix("xc")?
+                .parse::<u32>()
+                .ok()
+        })
+        .is_some_and(|index| index > 0)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    logical_path(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn with_extensions<'a>(
+    files: &'a [PathBuf],
+    extensions: &'a [&str],
+) -> impl Iterator<Item = &'a PathBuf> {
+    files
+        .iter()
+        .filter(move |file| has_extension(file, extensions))
+}
+
+/// Outputs go to `-o` when given and next to the input otherwise, as upstream does.
+fn output_dir_for(args: &Args, file: &Path) -> PathBuf {
+    if let Some(output) = &args.output {
+        return output.clone();
+    }
+    file.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
@@ -0,0 +1,67 @@
+//! Cooperative cancellation of long-running operations.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::NszError;
+
+type Poll = dyn Fn() -> bool + Send + Sync;
+
+/// Shared flag that stops an operation at its next checkpoint with [`NszError::Cancelled`].
+///
+/// Clones share the flag, so a GUI or scheduler keeps one clone and passes another in the
+/// request. Operations check it between files, container entries, 16 MiB chunks of NCA/NCZ
+/// payload and NCZ blocks.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    flag: Arc<AtomicBool>,
+    poll: Option<Arc<Poll>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that also cancels once `poll` returns `true`; `poll` runs at every checkpoint
+    /// on the thread running the operation.
+    pub fn from_fn(poll: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            flag: Arc::default(),
+            poll: Some(Arc::new(poll)),
+        }
+    }
+
+    /// Requests cancellation; safe to call from any thread.
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        if self.flag.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.poll.as_ref().is_some_and(|poll| poll()) {
+            self.cancel();
+            return true;
+        }
+        false
+    }
+
+    /// Fails with [`NszError::Cancelled`] once cancellation was requested.
+    pub(crate) fn check(&self) -> Result<(), NszError> {
+        if self.is_cancelled() {
+            return Err(NszError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.flag.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
@@ -1,5 +1,8 @@
 use std::path::PathBuf;
 
+use crate::cancel::CancellationToken;
+use crate::progress::Progress;
+
 /// High-level compression request options.
 #[derive(Debug, Clone)]
 pub struct CompressRequest {
@@ -39,8 +42,32 @@ pub struct CompressRequest {
     pub rm_old_version: bool,
     /// Removes source files after successful compression.
     pub rm_source: bool,
+    /// Writes outputs as FAT32-sized numbered parts (a split folder, or `.xc0`/`.xc1` for XCI).
+    pub split_output: bool,
+    /// Refuses to compress NCAs without loaded keys instead of falling back to unplanned NCZ.
+    pub require_keys: bool,
+    /// Records failing inputs in the report and keeps going, failing with
+    /// [`NszError::BatchFailed`](crate::NszError::BatchFailed) once the batch is done.
+    pub continue_on_error: bool,
+    /// Optional job journal file; inputs it records as done, with unchanged inputs and
+    /// intact outputs, are skipped and reported as resumed.
+    pub journal: Option<PathBuf>,
+    /// Estimates each output instead of writing it: containers are parsed and planned as
+    /// usual, but only a sample of every NCA is compressed. Inputs left to the Python
+    /// fallback are skipped.
+    pub dry_run: bool,
+    /// Number of 16 MiB payload chunks per NCA compressed by `dry_run`; at least one is.
+    pub estimate_sample_chunks: usize,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
+    /// Optional `title.keys` database used when a container has no ticket for a rights ID.
+    pub title_keys_file: Option<PathBuf>,
     /// Optional Python baseline repository root for compatibility fallback.
     pub python_repo_root: Option<PathBuf>,
+    /// Observer notified as containers, entries and outputs are processed.
+    pub progress: Progress,
+    /// Token that stops the operation with [`NszError::Cancelled`](crate::NszError::Cancelled).
+    pub cancel: CancellationToken,
 }
 
 impl Default for CompressRequest {
@@ -64,7 +91,17 @@ impl Default for CompressRequest {
             overwrite: false,
             rm_old_version: false,
             rm_source: false,
+            split_output: false,
+            require_keys: false,
+            continue_on_error: false,
+            journal: None,
+            dry_run: false,
+            estimate_sample_chunks: 4,
+            keys_file: None,
+            title_keys_file: None,
             python_repo_root: None,
+            progress: Progress::default(),
+            cancel: CancellationToken::default(),
         }
     }
 }
@@ -78,8 +115,22 @@ pub struct DecompressRequest {
     pub output_dir: Option<PathBuf>,
     /// Applies padding fixes in compatibility paths.
     pub fix_padding: bool,
+    /// Writes outputs as FAT32-sized numbered parts (a split folder, or `.xc0`/`.xc1` for XCI).
+    pub split_output: bool,
+    /// Records failing inputs in the report and keeps going, failing with
+    /// [`NszError::BatchFailed`](crate::NszError::BatchFailed) once the batch is done.
+    pub continue_on_error: bool,
+    /// Optional job journal file; inputs it records as done, with unchanged inputs and
+    /// intact outputs, are skipped and reported as resumed.
+    pub journal: Option<PathBuf>,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
     /// Optional Python baseline repository root for compatibility fallback.
     pub python_repo_root: Option<PathBuf>,
+    /// Observer notified as containers, entries and outputs are processed.
+    pub progress: Progress,
+    /// Token that stops the operation with [`NszError::Cancelled`](crate::NszError::Cancelled).
+    pub cancel: CancellationToken,
 }
 
 /// High-level verification request options.
@@ -89,8 +140,67 @@ pub struct VerifyRequest {
     pub files: Vec<PathBuf>,
     /// Applies padding fixes in compatibility paths.
     pub fix_padding: bool,
+    /// Records failing inputs in the report and keeps going, failing with
+    /// [`NszError::BatchFailed`](crate::NszError::BatchFailed) once the batch is done.
+    pub continue_on_error: bool,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
     /// Optional Python baseline repository root for compatibility fallback.
     pub python_repo_root: Option<PathBuf>,
+    /// Observer notified as containers, entries and outputs are processed.
+    pub progress: Progress,
+    /// Token that stops the operation with [`NszError::Cancelled`](crate::NszError::Cancelled).
+    pub cancel: CancellationToken,
+}
+
+/// High-level XCI/XCZ to NSP/NSZ conversion request options.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertRequest {
+    /// Input `.xci`/`.xcz` files to convert.
+    pub files: Vec<PathBuf>,
+    /// Destination directory for converted outputs.
+    pub output_dir: Option<PathBuf>,
+    /// Writes one NSP/NSZ per title listed by the cart's CNMTs; requires keys.
+    pub split_titles: bool,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
+    /// Optional `title.keys` database used to generate tickets the cart does not carry.
+    pub title_keys_file: Option<PathBuf>,
+}
+
+/// High-level multi-title merge request options.
+#[derive(Debug, Clone, Default)]
+pub struct MergeRequest {
+    /// Input `.nsp`/`.nsz` files to merge.
+    pub files: Vec<PathBuf>,
+    /// Output file; `.nsp`/`.nsz` writes a PFS0 and `.xci`/`.xcz` a gamecard-style secure partition.
+    pub output_file: Option<PathBuf>,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
+    /// Optional `title.keys` database used to generate tickets the inputs do not carry.
+    pub title_keys_file: Option<PathBuf>,
+}
+
+/// High-level per-title split request options.
+#[derive(Debug, Clone, Default)]
+pub struct SplitRequest {
+    /// Input `.nsp`/`.nsz`/`.xci`/`.xcz` files to split.
+    pub files: Vec<PathBuf>,
+    /// Destination directory for per-title outputs.
+    pub output_dir: Option<PathBuf>,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
+    /// Optional `title.keys` database used to generate tickets the input does not carry.
+    pub title_keys_file: Option<PathBuf>,
+}
+
+/// High-level XCI trim/untrim request options.
+#[derive(Debug, Clone, Default)]
+pub struct TrimRequest {
+    /// Input `.xci` files to trim or untrim.
+    pub files: Vec<PathBuf>,
+    /// Destination directory for rewritten images.
+    pub output_dir: Option<PathBuf>,
 }
 
 /// High-level extraction request options.
@@ -102,8 +212,14 @@ pub struct ExtractRequest {
     pub output_dir: Option<PathBuf>,
     /// Optional regex used by compatibility extraction flow.
     pub extract_regex: Option<String>,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
     /// Optional Python baseline repository root for compatibility fallback.
     pub python_repo_root: Option<PathBuf>,
+    /// Observer notified as containers, entries and outputs are processed.
+    pub progress: Progress,
+    /// Token that stops the operation with [`NszError::Cancelled`](crate::NszError::Cancelled).
+    pub cancel: CancellationToken,
 }
 
 /// High-level create-container request options.
@@ -124,6 +240,8 @@ pub struct CreateRequest {
 pub struct TitleKeysRequest {
     /// Input files to inspect for titlekey extraction.
     pub files: Vec<PathBuf>,
+    /// Optional `prod.keys`/`keys.txt` file; the default locations are searched when unset.
+    pub keys_file: Option<PathBuf>,
     /// Optional Python baseline repository root for compatibility fallback.
     pub python_repo_root: Option<PathBuf>,
 }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use crate::container::nca::{read_section_pfs0, NcaKeySet, TicketRecord};
+use crate::container::pfs0::Pfs0Header;
+use crate::error::NszError;
+
+const CNMT_HEADER_SIZE: usize = 0x20;
+const CNMT_CONTENT_RECORD_SIZE: usize = 0x38;
+
+/// One content record of a content meta.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CnmtContent {
+    /// SHA-256 of the content NCA.
+    pub hash: [u8; 32],
+    /// Content ID; the NCA is stored as `<content id hex>.nca`.
+    pub content_id: [u8; 16],
+    /// Content size in bytes.
+    pub size: u64,
+    /// Content type (0 meta, 1 program, 2 data, 3 control, 4 HTML document, 5 legal, 6 delta).
+    pub content_type: u8,
+    /// ID offset for multi-program titles.
+    pub id_offset: u8,
+}
+
+/// Parsed packaged content meta (`.cnmt`) of one title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cnmt {
+    /// Title ID described by this meta.
+    pub title_id: u64,
+    /// Title version.
+    pub version: u32,
+    /// Content meta type (0x80 application, 0x81 patch, 0x82 add-on content, ...).
+    pub meta_type: u8,
+    /// Content records, in meta order.
+    pub contents: Vec<CnmtContent>,
+}
+
+impl Cnmt {
+    /// Parses raw `.cnmt` bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, NszError> {
+        if data.len() < CNMT_HEADER_SIZE {
+            return Err(NszError::ContainerFormat {
+                message: "CNMT header truncated".to_string(),
+            });
+        }
+
+        let title_id = u64::from_le_bytes(data[0x00..0x08].try_into().unwrap());
+        let version = u32::from_le_bytes(data[0x08..0x0C].try_into().unwrap());
+        let meta_type = data[0x0C];
+        let extended_header_size = u16::from_le_bytes(data[0x0E..0x10].try_into().unwrap());
+        let content_count = u16::from_le_bytes(data[0x10..0x12].try_into().unwrap()) as usize;
+
+        let records_start = CNMT_HEADER_SIZE + extended_header_size as usize;
+        let records_end = records_start + content_count * CNMT_CONTENT_RECORD_SIZE;
+        if data.len() < records_end {
+            return Err(NszError::ContainerFormat {
+                message: "CNMT content records truncated".to_string(),
+            });
+        }
+
+        let contents = data[records_start..records_end]
+            .chunks_exact(CNMT_CONTENT_RECORD_SIZE)
+            .map(|record| {
+                let mut size = [0u8; 8];
+                size[..6].copy_from_slice(&record[0x30..0x36]);
+                CnmtContent {
+                    hash: record[0x00..0x20].try_into().unwrap(),
+                    content_id: record[0x20..0x30].try_into().unwrap(),
+                    size: u64::from_le_bytes(size),
+                    content_type: record[0x36],
+                    id_offset: record[0x37],
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            title_id,
+            version,
+            meta_type,
+            contents,
+        })
+    }
+
+    /// Reads the `.cnmt` stored in a meta NCA (`*.cnmt.nca`).
+    pub fn from_meta_nca(
+        data: &[u8],
+        keys: &NcaKeySet,
+        tickets: &HashMap<[u8; 16], TicketRecord>,
+    ) -> Result<Self, NszError> {
+        let pfs0 = read_section_pfs0(data, keys, tickets)?;
+        let header = Pfs0Header::from_bytes(&pfs0)?;
+        let data_start = 16 + header.entries.len() * 24 + header.string_table.len();
+        for entry in &header.entries {
+            let name = header
+                .string_table
+                .get(entry.string_table_offset as usize..)
+                .unwrap_or_default()
+                .split(|byte| *byte == 0)
+                .next()
+                .unwrap_or_default();
+            if !name.ends_with(b".cnmt") {
+                continue;
+            }
+            let start = data_start.saturating_add(entry.offset as usize);
+            let end = start.saturating_add(entry.size as usize);
+            if end > pfs0.len() {
+                return Err(NszError::ContainerFormat {
+                    message: "CNMT entry outside meta NCA PFS0".to_string(),
+                });
+            }
+            return Self::from_bytes(&pfs0[start..end]);
+        }
+        Err(NszError::ContainerFormat {
+            message: "meta NCA does not contain a .cnmt file".to_string(),
+        })
+    }
+}
@@ -1,3 +1,4 @@
+pub mod cnmt;
 pub mod hfs0;
 pub mod nca;
 pub mod nsp;
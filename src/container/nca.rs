@@ -1,15 +1,28 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use aes::cipher::generic_array::GenericArray;
-use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
-use aes::Aes128;
-use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
 
+use crate::crypto::aes::{ctr_apply, ecb_decrypt, ecb_decrypt_block, xts_decrypt};
+use crate::crypto::keys::{decode_hex_array, hex_string};
+pub use crate::crypto::keyset::{KeyOrigin, NcaKeySet};
+use crate::crypto::Redacted;
 use crate::error::NszError;
 
 const NCA_MEDIA_SIZE: u64 = 0x200;
 const NCA_HEADER_SIZE: usize = 0xC00;
 const NCA_SECTOR_SIZE: usize = 0x200;
+const NCA_FS_HEADERS_OFFSET: usize = 0x400;
+const NCZ_CRYPTO_TYPE_NONE: u64 = 1;
+const FS_TYPE_PFS0: u8 = 1;
+const HASH_TYPE_SHA256: u8 = 2;
+const TICKET_SIZE: usize = 0x2C0;
+const TICKET_BODY_OFFSET: usize = 0x140;
+const TICKET_ISSUER: &[u8] = b"Root-CA00000003-XS00000020";
+const BUCKET_TREE_NODE_SIZE: u64 = 0x4000;
+const BUCKET_TREE_NODE_HEADER_SIZE: usize = 0x10;
+const SPARSE_ENTRY_SIZE: usize = 0x14;
 const UNCOMPRESSABLE_HEADER_SIZE: u64 = 0x4000;
 const BKTR_HEADER_SIZE: u64 = 0x4000;
 
@@ -30,7 +43,7 @@ impl NcaCompressionMeta {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct NcaEncryptionSection {
     /// Section offset in decompressed NCA space.
     pub offset: u64,
@@ -54,7 +67,7 @@ pub struct NcaCompressionPlan {
     pub sections: Vec<NcaEncryptionSection>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct TicketRecord {
     /// Rights ID extracted from the ticket.
     pub rights_id: [u8; 16],
@@ -64,140 +77,71 @@ pub struct TicketRecord {
     pub master_key_revision: u8,
 }
 
-#[derive(Debug, Clone)]
-pub struct NcaKeySet {
-    /// NCA header key used for header decryption.
-    pub header_key: [u8; 32],
-    aes_kek_generation_source: [u8; 16],
-    aes_key_generation_source: [u8; 16],
-    titlekek_source: [u8; 16],
-    key_area_key_application_source: [u8; 16],
-    master_keys: HashMap<u8, [u8; 16]>,
-    key_area_keys_application: HashMap<u8, [u8; 16]>,
-}
-
-impl NcaKeySet {
-    /// Parses key material from a `keys.txt`-style content string.
-    pub fn from_keys_str(content: &str) -> Result<Self, NszError> {
-        let mut raw = HashMap::new();
-        for line in content.lines() {
-            let trimmed = line.trim();
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                continue;
-            }
-            let Some((name, value)) = trimmed.split_once('=') else {
-                continue;
-            };
-            raw.insert(name.trim().to_string(), value.trim().to_string());
-        }
-
-        let header_key = parse_named_key::<32>(&raw, "header_key")?;
-        let aes_kek_gen_source = parse_named_key::<16>(&raw, "aes_kek_generation_source")?;
-        let aes_key_gen_source = parse_named_key::<16>(&raw, "aes_key_generation_source")?;
-        let titlekek_source = parse_named_key::<16>(&raw, "titlekek_source")?;
-        let key_area_key_application_source =
-            parse_named_key::<16>(&raw, "key_area_key_application_source")?;
-
-        let mut master_keys = HashMap::new();
-        let mut key_area_keys_application = HashMap::new();
-        for (name, value) in &raw {
-            if let Some(suffix) = name.strip_prefix("master_key_") {
-                if let Ok(index) = u8::from_str_radix(suffix, 16) {
-                    let Some(key) = parse_hex_key::<16>(value) else {
-                        return Err(NszError::ContainerFormat {
-                            message: format!("invalid hex value for {name}"),
-                        });
-                    };
-                    master_keys.insert(index, key);
-                }
-            }
-            if let Some(suffix) = name.strip_prefix("key_area_key_application_") {
-                if let Ok(index) = u8::from_str_radix(suffix, 16) {
-                    let Some(key) = parse_hex_key::<16>(value) else {
-                        return Err(NszError::ContainerFormat {
-                            message: format!("invalid hex value for {name}"),
-                        });
-                    };
-                    key_area_keys_application.insert(index, key);
-                }
-            }
-        }
-
-        if master_keys.is_empty() {
-            return Err(NszError::ContainerFormat {
-                message: "keys file does not contain any master_key_XX entries".to_string(),
-            });
-        }
-
-        Ok(Self {
-            header_key,
-            aes_kek_generation_source: aes_kek_gen_source,
-            aes_key_generation_source: aes_key_gen_source,
-            titlekek_source,
-            key_area_key_application_source,
-            master_keys,
-            key_area_keys_application,
-        })
-    }
-
-    fn master_key_index_from_header(header: &ParsedNcaHeader) -> u8 {
-        let max_type = header.crypto_type.max(header.crypto_type2);
-        max_type.saturating_sub(1)
+impl fmt::Debug for NcaEncryptionSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NcaEncryptionSection")
+            .field("offset", &self.offset)
+            .field("size", &self.size)
+            .field("crypto_type", &self.crypto_type)
+            .field("crypto_key", &Redacted)
+            .field("crypto_counter", &self.crypto_counter)
+            .finish()
     }
+}
 
-    fn master_key(&self, master_index: u8) -> Result<[u8; 16], NszError> {
-        self.master_keys
-            .get(&master_index)
-            .copied()
-            .ok_or_else(|| NszError::ContainerFormat {
-                message: format!("missing master_key_{master_index:02x}"),
-            })
+impl Drop for NcaEncryptionSection {
+    fn drop(&mut self) {
+        self.crypto_key.zeroize();
     }
+}
 
-    fn title_kek(&self, master_index: u8) -> Result<[u8; 16], NszError> {
-        let master_key = self.master_key(master_index)?;
-        aes_ecb_decrypt_block(&master_key, &self.titlekek_source)
+impl fmt::Debug for TicketRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TicketRecord")
+            .field("rights_id", &hex_string(&self.rights_id))
+            .field("encrypted_title_key", &Redacted)
+            .field("master_key_revision", &self.master_key_revision)
+            .finish()
     }
+}
 
-    fn key_area_key_application(&self, master_index: u8) -> Result<[u8; 16], NszError> {
-        if let Some(key) = self.key_area_keys_application.get(&master_index) {
-            return Ok(*key);
-        }
-        let master_key = self.master_key(master_index)?;
-        generate_kek(
-            &self.key_area_key_application_source,
-            &master_key,
-            &self.aes_kek_generation_source,
-            &self.aes_key_generation_source,
-        )
+impl Drop for TicketRecord {
+    fn drop(&mut self) {
+        self.encrypted_title_key.zeroize();
     }
+}
 
-    fn resolve_title_key(
-        &self,
-        header: &ParsedNcaHeader,
-        tickets: &HashMap<[u8; 16], TicketRecord>,
-    ) -> Result<[u8; 16], NszError> {
-        let master_index = Self::master_key_index_from_header(header);
-        if header.rights_id != [0u8; 16] {
-            let ticket =
-                tickets
-                    .get(&header.rights_id)
-                    .ok_or_else(|| NszError::ContainerFormat {
-                        message: format!(
-                            "missing ticket for rights id {}",
-                            hex_string(&header.rights_id)
-                        ),
-                    })?;
-            let title_kek = self.title_kek(master_index)?;
-            return aes_ecb_decrypt_block(&title_kek, &ticket.encrypted_title_key);
-        }
+fn master_key_index_from_header(header: &ParsedNcaHeader) -> u8 {
+    let max_type = header.crypto_type.max(header.crypto_type2);
+    max_type.saturating_sub(1)
+}
 
-        let key_area_key = self.key_area_key_application(master_index)?;
-        let key_block = aes_ecb_decrypt(&key_area_key, &header.encrypted_key_block)?;
-        let mut title_key = [0u8; 16];
-        title_key.copy_from_slice(&key_block[0x20..0x30]);
-        Ok(title_key)
+fn resolve_title_key(
+    keys: &NcaKeySet,
+    header: &ParsedNcaHeader,
+    tickets: &HashMap<[u8; 16], TicketRecord>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+) -> Result<[u8; 16], NszError> {
+    let master_index = master_key_index_from_header(header);
+    if header.rights_id != [0u8; 16] {
+        let ticket = tickets
+            .get(&header.rights_id)
+            .or_else(|| title_keys.get(&header.rights_id))
+            .ok_or_else(|| NszError::ContainerFormat {
+                message: format!(
+                    "missing ticket or title.keys entry for rights id {}",
+                    hex_string(&header.rights_id)
+                ),
+            })?;
+        let title_kek = keys.title_kek(master_index)?;
+        return ecb_decrypt_block(&title_kek, &ticket.encrypted_title_key);
     }
+
+    let key_area_key = keys.key_area_key(header.key_area_key_index, master_index)?;
+    let key_block = Zeroizing::new(ecb_decrypt(&key_area_key, &header.encrypted_key_block)?);
+    let mut title_key = [0u8; 16];
+    title_key.copy_from_slice(&key_block[0x20..0x30]);
+    Ok(title_key)
 }
 
 /// Parses a ticket blob and extracts the fields needed for title-key decryption.
@@ -246,6 +190,57 @@ pub fn parse_ticket_record(data: &[u8]) -> Result<TicketRecord, NszError> {
     })
 }
 
+/// Parses a `title.keys` database into ticket records keyed by rights ID.
+///
+/// Each non-comment line holds `rights_id = encrypted_title_key`. The `|`-separated
+/// `titlekeys.txt` layout written by upstream `nsz --titlekeys` is accepted as well;
+/// any fields after the title key are ignored.
+pub fn parse_title_keys_str(content: &str) -> Result<HashMap<[u8; 16], TicketRecord>, NszError> {
+    let mut out = HashMap::new();
+    for (line_index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut fields = trimmed.split(['=', '|']).map(str::trim);
+        let (Some(rights_id), Some(title_key)) = (fields.next(), fields.next()) else {
+            return Err(NszError::ContainerFormat {
+                message: format!(
+                    "title keys line {} is not `rights_id = key`",
+                    line_index + 1
+                ),
+            });
+        };
+        let (Some(rights_id), Some(encrypted_title_key)) = (
+            decode_hex_array::<16>(rights_id),
+            decode_hex_array::<16>(title_key),
+        ) else {
+            return Err(NszError::ContainerFormat {
+                message: format!("invalid hex value on title keys line {}", line_index + 1),
+            });
+        };
+        out.insert(
+            rights_id,
+            TicketRecord {
+                rights_id,
+                encrypted_title_key,
+                master_key_revision: rights_id[15],
+            },
+        );
+    }
+    Ok(out)
+}
+
+/// Adds `title.keys` records for rights IDs that have no ticket in the container.
+pub fn merge_title_keys(
+    tickets: &mut HashMap<[u8; 16], TicketRecord>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+) {
+    for (rights_id, record) in title_keys {
+        tickets.entry(*rights_id).or_insert_with(|| record.clone());
+    }
+}
+
 /// Reads NCA metadata used to decide whether the file should be compressed.
 pub fn analyze_for_compression(
     data: &[u8],
@@ -255,14 +250,77 @@ pub fn analyze_for_compression(
     Ok(header.meta())
 }
 
+/// Returns the rights ID of a titlekey-crypto NCA, or `None` for standard crypto.
+///
+/// Only the header is read, so NCZ data works as well.
+pub fn read_rights_id(data: &[u8], header_key: &[u8; 32]) -> Result<Option<[u8; 16]>, NszError> {
+    let header = parse_nca_header(data, header_key)?;
+    Ok((header.rights_id != [0u8; 16]).then_some(header.rights_id))
+}
+
+/// Decrypts the PFS0 stored in the first section of an NCA, such as the `.cnmt`
+/// container of a meta NCA.
+pub fn read_section_pfs0(
+    data: &[u8],
+    keys: &NcaKeySet,
+    tickets: &HashMap<[u8; 16], TicketRecord>,
+) -> Result<Vec<u8>, NszError> {
+    let header = parse_nca_header(data, &keys.header_key)?;
+    let section = header
+        .sections
+        .first()
+        .ok_or_else(|| NszError::ContainerFormat {
+            message: "NCA has no sections".to_string(),
+        })?;
+    let (offset, size) = section
+        .pfs0_region
+        .ok_or_else(|| NszError::ContainerFormat {
+            message: "first NCA section is not a PFS0".to_string(),
+        })?;
+    let title_key = Zeroizing::new(resolve_title_key(keys, &header, tickets, &HashMap::new())?);
+    let size = usize::try_from(size).map_err(|_| NszError::ContainerFormat {
+        message: "PFS0 region exceeds platform limits".to_string(),
+    })?;
+    read_section_range(data, section, offset, size, &title_key)
+}
+
+/// Builds a common ticket for `record`.
+///
+/// The signature is left zeroed, as in tickets generated by other dumping tools,
+/// so consoles only accept it with signature patches.
+pub fn build_common_ticket(record: &TicketRecord) -> Vec<u8> {
+    let mut out = vec![0u8; TICKET_SIZE];
+    out[0..4].copy_from_slice(&0x0001_0004u32.to_le_bytes());
+    let body = &mut out[TICKET_BODY_OFFSET..];
+    body[..TICKET_ISSUER.len()].copy_from_slice(TICKET_ISSUER);
+    body[0x40..0x50].copy_from_slice(&record.encrypted_title_key);
+    body[0x140] = 2;
+    body[0x145] = record.master_key_revision;
+    body[0x160..0x170].copy_from_slice(&record.rights_id);
+    body[0x178..0x17C].copy_from_slice(&(TICKET_SIZE as u32).to_le_bytes());
+    out
+}
+
 /// Builds per-section crypto metadata required for byte-parity NCZ compression.
 pub fn build_compression_plan(
     data: &[u8],
     keys: &NcaKeySet,
     tickets: &HashMap<[u8; 16], TicketRecord>,
+) -> Result<NcaCompressionPlan, NszError> {
+    build_compression_plan_with_title_keys(data, keys, tickets, &HashMap::new())
+}
+
+/// Like [`build_compression_plan`], but looks up rights IDs without a ticket in
+/// `title_keys` instead of copying the database into `tickets`.
+pub fn build_compression_plan_with_title_keys(
+    data: &[u8],
+    keys: &NcaKeySet,
+    tickets: &HashMap<[u8; 16], TicketRecord>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
 ) -> Result<NcaCompressionPlan, NszError> {
     let header = parse_nca_header(data, &keys.header_key)?;
-    let title_key = keys.resolve_title_key(&header, tickets)?;
+    let title_key = Zeroizing::new(resolve_title_key(keys, &header, tickets, title_keys)?);
+    check_title_key(data, &header, &title_key)?;
 
     let mut sections = Vec::new();
     let offset_first_section = header
@@ -270,10 +328,22 @@ pub fn build_compression_plan(
         .first()
         .map_or(UNCOMPRESSABLE_HEADER_SIZE, |section| section.offset);
     for section in &header.sections {
-        let normalized_crypto_type = match section.crypto_type {
-            4 => 3u64,
-            value => u64::from(value),
-        };
+        let normalized_crypto_type = ncz_crypto_type(section.crypto_type)?;
+        if let Some(sparse) = &section.sparse {
+            if section.bktr_subsection_size > 0 {
+                return Err(NszError::UnsupportedFeature {
+                    feature: "NCA section with both sparse and BKTR layers".to_string(),
+                });
+            }
+            sections.extend(sparse_sections(
+                data,
+                section,
+                sparse,
+                normalized_crypto_type,
+                &title_key,
+            )?);
+            continue;
+        }
         if section.bktr_subsection_size > 0 {
             let entries = parse_bktr_subsection_entries(data, section, &title_key)?;
             if entries.is_empty() {
@@ -281,7 +351,7 @@ pub fn build_compression_plan(
                     offset: section.real_offset(),
                     size: section.size,
                     crypto_type: normalized_crypto_type,
-                    crypto_key: title_key,
+                    crypto_key: *title_key,
                     crypto_counter: section.crypto_counter,
                 });
             } else {
@@ -290,7 +360,7 @@ pub fn build_compression_plan(
                         offset: section.real_offset().saturating_add(entry.virtual_offset),
                         size: entry.size,
                         crypto_type: normalized_crypto_type,
-                        crypto_key: title_key,
+                        crypto_key: *title_key,
                         crypto_counter: set_bktr_counter(section.crypto_counter, entry.ctr),
                     });
                 }
@@ -301,7 +371,7 @@ pub fn build_compression_plan(
                         offset: next_offset,
                         size: section_end.saturating_sub(next_offset),
                         crypto_type: normalized_crypto_type,
-                        crypto_key: title_key,
+                        crypto_key: *title_key,
                         crypto_counter: section.crypto_counter,
                     });
                 }
@@ -313,7 +383,7 @@ pub fn build_compression_plan(
             offset: section.real_offset(),
             size: section.size,
             crypto_type: normalized_crypto_type,
-            crypto_key: title_key,
+            crypto_key: *title_key,
             crypto_counter: section.crypto_counter,
         });
     }
@@ -341,6 +411,63 @@ struct ParsedSection {
     section_start: u64,
     bktr_subsection_offset: u64,
     bktr_subsection_size: u64,
+    sparse: Option<SparseInfo>,
+    pfs0_region: Option<(u64, u64)>,
+    hash_table: Option<HashTableInfo>,
+}
+
+/// Hash table of a SHA-256 hashed section and the master hash over it, from an FS header.
+#[derive(Debug, Clone, Copy)]
+struct HashTableInfo {
+    master_hash: [u8; 32],
+    offset: u64,
+    size: u64,
+}
+
+/// Location of a bucket tree table inside a section, from an FS header.
+#[derive(Debug, Clone, Copy)]
+struct BucketTableInfo {
+    offset: u64,
+    size: u64,
+    entry_count: u32,
+}
+
+impl BucketTableInfo {
+    /// Parses the 0x20-byte `offset, size, bucket header` layout; an empty table is `None`.
+    fn parse(raw: &[u8]) -> Result<Option<Self>, NszError> {
+        let offset = u64::from_le_bytes(raw[0x0..0x8].try_into().unwrap());
+        let size = u64::from_le_bytes(raw[0x8..0x10].try_into().unwrap());
+        if size == 0 {
+            return Ok(None);
+        }
+        if &raw[0x10..0x14] != b"BKTR" {
+            return Err(NszError::ContainerFormat {
+                message: "bucket tree header magic mismatch".to_string(),
+            });
+        }
+        let entry_count = u32::from_le_bytes(raw[0x18..0x1C].try_into().unwrap());
+        Ok(Some(Self {
+            offset,
+            size,
+            entry_count,
+        }))
+    }
+}
+
+/// Sparse layer of a section: which virtual ranges are backed by physical data.
+#[derive(Debug, Clone, Copy)]
+struct SparseInfo {
+    table: BucketTableInfo,
+    physical_offset: u64,
+    generation: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SparseEntry {
+    virtual_offset: u64,
+    physical_offset: u64,
+    size: u64,
+    backed: bool,
 }
 
 impl ParsedSection {
@@ -349,12 +476,19 @@ impl ParsedSection {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NcaFormat {
+    Nca2,
+    Nca3,
+}
+
 #[derive(Debug, Clone)]
 struct ParsedNcaHeader {
     content_type: u8,
     size: u64,
     crypto_type: u8,
     crypto_type2: u8,
+    key_area_key_index: u8,
     rights_id: [u8; 16],
     encrypted_key_block: [u8; 64],
     sections: Vec<ParsedSection>,
@@ -392,16 +526,48 @@ fn parse_nca_header(data: &[u8], header_key: &[u8; 32]) -> Result<ParsedNcaHeade
     }
 
     let mut header = data[..NCA_HEADER_SIZE].to_vec();
-    decrypt_nca_header_xts(&mut header, header_key)?;
+    xts_decrypt(
+        &mut header[..NCA_FS_HEADERS_OFFSET],
+        header_key,
+        NCA_SECTOR_SIZE,
+        0,
+    )?;
 
-    if &header[0x200..0x204] != b"NCA2" && &header[0x200..0x204] != b"NCA3" {
-        return Err(NszError::ContainerFormat {
-            message: "NCA header magic mismatch after XTS decryption".to_string(),
-        });
+    let format = match &header[0x200..0x204] {
+        b"NCA3" => NcaFormat::Nca3,
+        b"NCA2" => NcaFormat::Nca2,
+        // NCA0 key areas are RSA-OAEP encrypted with a key this crate does not have, so
+        // its sections can neither be planned nor verified.
+        b"NCA0" => {
+            return Err(NszError::UnsupportedFeature {
+                feature: "NCA0 content (RSA-encrypted key area)".to_string(),
+            })
+        }
+        _ => {
+            return Err(NszError::ContainerFormat {
+                message: "NCA header magic mismatch after XTS decryption".to_string(),
+            })
+        }
+    };
+    match format {
+        // NCA3 encrypts the whole header as one run of sequential sectors.
+        NcaFormat::Nca3 => xts_decrypt(
+            &mut header[NCA_FS_HEADERS_OFFSET..],
+            header_key,
+            NCA_SECTOR_SIZE,
+            2,
+        )?,
+        // NCA2 encrypts every FS header on its own as sector 0.
+        NcaFormat::Nca2 => {
+            for fs_header in header[NCA_FS_HEADERS_OFFSET..].chunks_exact_mut(NCA_SECTOR_SIZE) {
+                xts_decrypt(fs_header, header_key, NCA_SECTOR_SIZE, 0)?;
+            }
+        }
     }
 
     let content_type = header[0x205];
     let crypto_type = header[0x206];
+    let key_area_key_index = header[0x207];
     let size = u64::from_le_bytes(header[0x208..0x210].try_into().unwrap());
     let crypto_type2 = header[0x220];
 
@@ -428,7 +594,7 @@ fn parse_nca_header(data: &[u8], header_key: &[u8; 32]) -> Result<ParsedNcaHeade
             continue;
         }
 
-        let section_header_start = 0x400usize + section_index * 0x200;
+        let section_header_start = NCA_FS_HEADERS_OFFSET + section_index * NCA_SECTOR_SIZE;
         let section_header = &header[section_header_start..section_header_start + 0x200];
         let crypto_type_section = section_header[0x4];
 
@@ -441,14 +607,54 @@ fn parse_nca_header(data: &[u8], header_key: &[u8; 32]) -> Result<ParsedNcaHeade
         let bktr_subsection_size =
             u64::from_le_bytes(section_header[0x128..0x130].try_into().unwrap());
 
+        let sparse_generation =
+            u16::from_le_bytes(section_header[0x170..0x172].try_into().unwrap());
+        let sparse = match BucketTableInfo::parse(&section_header[0x148..0x168])? {
+            Some(table) if sparse_generation != 0 => Some(SparseInfo {
+                table,
+                physical_offset: u64::from_le_bytes(
+                    section_header[0x168..0x170].try_into().unwrap(),
+                ),
+                generation: sparse_generation,
+            }),
+            _ => None,
+        };
+        // Compression sits above the AES-CTR layer, so it never changes the counters;
+        // the table only has to lie inside the section.
+        let size = end.saturating_sub(offset);
+        if let Some(table) = BucketTableInfo::parse(&section_header[0x178..0x198])? {
+            if table.offset.saturating_add(table.size) > size {
+                return Err(NszError::ContainerFormat {
+                    message: "compression table outside section bounds".to_string(),
+                });
+            }
+        }
+
+        let hash_table = (section_header[0x3] == HASH_TYPE_SHA256).then(|| HashTableInfo {
+            master_hash: section_header[0x8..0x28].try_into().unwrap(),
+            offset: u64::from_le_bytes(section_header[0x30..0x38].try_into().unwrap()),
+            size: u64::from_le_bytes(section_header[0x38..0x40].try_into().unwrap()),
+        });
+        let pfs0_region = (section_header[0x2] == FS_TYPE_PFS0
+            && section_header[0x3] == HASH_TYPE_SHA256)
+            .then(|| {
+                (
+                    u64::from_le_bytes(section_header[0x40..0x48].try_into().unwrap()),
+                    u64::from_le_bytes(section_header[0x48..0x50].try_into().unwrap()),
+                )
+            });
+
         sections.push(ParsedSection {
             offset,
-            size: end.saturating_sub(offset),
+            size,
             crypto_type: crypto_type_section,
             crypto_counter,
             section_start: 0,
             bktr_subsection_offset,
             bktr_subsection_size,
+            sparse,
+            pfs0_region,
+            hash_table,
         });
     }
 
@@ -458,12 +664,59 @@ fn parse_nca_header(data: &[u8], header_key: &[u8; 32]) -> Result<ParsedNcaHeade
         size,
         crypto_type,
         crypto_type2,
+        key_area_key_index,
         rights_id,
         encrypted_key_block,
         sections,
     })
 }
 
+/// Checks `title_key` against the master hash of the first plain AES-CTR section with a
+/// SHA-256 hash table, so a wrong key area key, titlekek or master key fails with
+/// [`NszError::InvalidKeys`] instead of yielding crypto metadata that cannot restore the NCA.
+///
+/// NCAs without such a section (RomFS-only, sparse or BKTR layers) are not checked.
+fn check_title_key(
+    data: &[u8],
+    header: &ParsedNcaHeader,
+    title_key: &[u8; 16],
+) -> Result<(), NszError> {
+    let Some((section, table)) = header.sections.iter().find_map(|section| {
+        let table = section.hash_table?;
+        (section.crypto_type == 3
+            && section.sparse.is_none()
+            && section.bktr_subsection_size == 0
+            && table.size > 0)
+            .then_some((section, table))
+    }) else {
+        return Ok(());
+    };
+    let size = usize::try_from(table.size).map_err(|_| NszError::ContainerFormat {
+        message: "NCA hash table exceeds platform limits".to_string(),
+    })?;
+    let hashes = read_section_range(data, section, table.offset, size, title_key)?;
+    if Sha256::digest(&hashes).as_slice() == table.master_hash {
+        return Ok(());
+    }
+    let master_index = master_key_index_from_header(header);
+    let key = if header.rights_id == [0u8; 16] {
+        let kind = match header.key_area_key_index {
+            0 => "application",
+            1 => "ocean",
+            _ => "system",
+        };
+        format!("key_area_key_{kind}_{master_index:02x}")
+    } else {
+        format!(
+            "titlekek_{master_index:02x} or the title key for rights id {}",
+            hex_string(&header.rights_id)
+        )
+    };
+    Err(NszError::InvalidKeys {
+        details: format!("{key} does not decrypt the NCA section hash table"),
+    })
+}
+
 fn parse_bktr_subsection_entries(
     data: &[u8],
     section: &ParsedSection,
@@ -542,6 +795,163 @@ fn parse_bktr_subsection_entries(
     Ok(out)
 }
 
+/// Maps an FS header encryption type to the crypto type written to NCZ sections.
+fn ncz_crypto_type(fs_crypto_type: u8) -> Result<u64, NszError> {
+    match fs_crypto_type {
+        1..=3 => Ok(u64::from(fs_crypto_type)),
+        // CTR-EX and the skip-layer-hash variants are plain AES-CTR at the byte level.
+        4..=6 => Ok(3),
+        other => Err(NszError::UnsupportedFeature {
+            feature: format!("NCA section crypto type {other}"),
+        }),
+    }
+}
+
+/// Splits a sparse section into NCZ sections for its physical layout.
+///
+/// Physical data runs are encrypted with the counter of their virtual offset, so
+/// each run gets a counter shifted to line up with its physical position. The
+/// sparse table uses the sparse generation in its IV; remaining bytes pass through.
+fn sparse_sections(
+    data: &[u8],
+    section: &ParsedSection,
+    sparse: &SparseInfo,
+    crypto_type: u64,
+    title_key: &[u8; 16],
+) -> Result<Vec<NcaEncryptionSection>, NszError> {
+    let mut table_counter = section.crypto_counter;
+    table_counter[4..8].copy_from_slice(&(u32::from(sparse.generation) << 16).to_be_bytes());
+    let table_start = sparse.physical_offset.saturating_add(sparse.table.offset);
+    let table_end = table_start.saturating_add(sparse.table.size);
+    if table_end > data.len() as u64 {
+        return Err(NszError::ContainerFormat {
+            message: "sparse table exceeds NCA file size".to_string(),
+        });
+    }
+    let mut table = data[table_start as usize..table_end as usize].to_vec();
+    ctr_apply(
+        &mut table,
+        title_key,
+        &table_counter,
+        u128::from(table_start),
+    );
+
+    let mut pieces = vec![NcaEncryptionSection {
+        offset: table_start,
+        size: sparse.table.size,
+        crypto_type,
+        crypto_key: *title_key,
+        crypto_counter: table_counter,
+    }];
+    for entry in parse_sparse_entries(&table, sparse.table.entry_count)? {
+        if !entry.backed || entry.size == 0 {
+            continue;
+        }
+        let physical = sparse.physical_offset.saturating_add(entry.physical_offset);
+        let logical = section.offset.saturating_add(entry.virtual_offset);
+        if physical % 16 != logical % 16 {
+            return Err(NszError::ContainerFormat {
+                message: "sparse entry is not aligned to the AES block size".to_string(),
+            });
+        }
+        let shift = u128::from(logical / 16).wrapping_sub(u128::from(physical / 16));
+        let counter = u128::from_be_bytes(section.crypto_counter).wrapping_add(shift);
+        pieces.push(NcaEncryptionSection {
+            offset: physical,
+            size: entry.size,
+            crypto_type,
+            crypto_key: *title_key,
+            crypto_counter: counter.to_be_bytes(),
+        });
+    }
+    cover_section(section.offset, section.size, pieces)
+}
+
+/// Reads the sparse bucket tree: one L1 node followed by its entry sets.
+fn parse_sparse_entries(table: &[u8], entry_count: u32) -> Result<Vec<SparseEntry>, NszError> {
+    let node_size = BUCKET_TREE_NODE_SIZE as usize;
+    let per_set = (node_size - BUCKET_TREE_NODE_HEADER_SIZE) / SPARSE_ENTRY_SIZE;
+    let entry_count = entry_count as usize;
+    let set_count = entry_count.div_ceil(per_set);
+    if table.len() < node_size.saturating_mul(set_count + 1) {
+        return Err(NszError::ContainerFormat {
+            message: "sparse table truncated".to_string(),
+        });
+    }
+
+    let mut out: Vec<SparseEntry> = Vec::with_capacity(entry_count);
+    for set_index in 0..set_count {
+        let set = &table[node_size * (set_index + 1)..node_size * (set_index + 2)];
+        let count = u32::from_le_bytes(set[4..8].try_into().unwrap()) as usize;
+        let end_offset = u64::from_le_bytes(set[8..16].try_into().unwrap());
+        if count > per_set {
+            return Err(NszError::ContainerFormat {
+                message: "sparse entry set overflows its node".to_string(),
+            });
+        }
+        for entry_index in 0..count {
+            let base = BUCKET_TREE_NODE_HEADER_SIZE + entry_index * SPARSE_ENTRY_SIZE;
+            let virtual_offset = u64::from_le_bytes(set[base..base + 8].try_into().unwrap());
+            if let Some(previous) = out.last_mut() {
+                previous.size = virtual_offset.saturating_sub(previous.virtual_offset);
+            }
+            out.push(SparseEntry {
+                virtual_offset,
+                physical_offset: u64::from_le_bytes(set[base + 8..base + 16].try_into().unwrap()),
+                size: 0,
+                backed: u32::from_le_bytes(set[base + 16..base + 20].try_into().unwrap()) == 0,
+            });
+        }
+        if let Some(last) = out.last_mut() {
+            last.size = end_offset.saturating_sub(last.virtual_offset);
+        }
+    }
+    if out.len() != entry_count {
+        return Err(NszError::ContainerFormat {
+            message: "sparse entry count mismatch".to_string(),
+        });
+    }
+    Ok(out)
+}
+
+/// Orders `pieces` and fills the gaps in `[offset, offset + size)` with pass-through sections.
+fn cover_section(
+    offset: u64,
+    size: u64,
+    mut pieces: Vec<NcaEncryptionSection>,
+) -> Result<Vec<NcaEncryptionSection>, NszError> {
+    pieces.sort_by_key(|piece| piece.offset);
+    let end = offset.saturating_add(size);
+    let mut cursor = offset;
+    let mut out = Vec::with_capacity(pieces.len() * 2 + 1);
+    for piece in pieces {
+        if piece.offset < cursor || piece.offset.saturating_add(piece.size) > end {
+            return Err(NszError::ContainerFormat {
+                message: "sparse layout overlaps or leaves its section".to_string(),
+            });
+        }
+        if piece.offset > cursor {
+            out.push(passthrough_section(cursor, piece.offset - cursor));
+        }
+        cursor = piece.offset.saturating_add(piece.size);
+        out.push(piece);
+    }
+    if cursor < end {
+        out.push(passthrough_section(cursor, end - cursor));
+    }
+    Ok(out)
+}
+
+const fn passthrough_section(offset: u64, size: u64) -> NcaEncryptionSection {
+    NcaEncryptionSection {
+        offset,
+        size,
+        crypto_type: NCZ_CRYPTO_TYPE_NONE,
+        crypto_key: [0u8; 16],
+        crypto_counter: [0u8; 16],
+    }
+}
+
 fn read_section_range(
     data: &[u8],
     section: &ParsedSection,
@@ -565,8 +975,8 @@ fn read_section_range(
     }
 
     let mut bytes = data[absolute_offset as usize..end_absolute as usize].to_vec();
-    if matches!(section.crypto_type, 3 | 4) {
-        apply_aes_ctr(
+    if matches!(section.crypto_type, 3..=6) {
+        ctr_apply(
             &mut bytes,
             title_key,
             &section.crypto_counter,
@@ -576,74 +986,6 @@ fn read_section_range(
     Ok(bytes)
 }
 
-fn parse_named_key<const N: usize>(
-    values: &HashMap<String, String>,
-    name: &str,
-) -> Result<[u8; N], NszError> {
-    let value = values.get(name).ok_or_else(|| NszError::ContainerFormat {
-        message: format!("keys file missing {name}"),
-    })?;
-    parse_hex_key::<N>(value).ok_or_else(|| NszError::ContainerFormat {
-        message: format!("invalid hex value for {name}"),
-    })
-}
-
-fn parse_hex_key<const N: usize>(value: &str) -> Option<[u8; N]> {
-    if value.len() != N * 2 {
-        return None;
-    }
-    let mut out = [0u8; N];
-    for (index, slot) in out.iter_mut().enumerate() {
-        let hi = hex_nibble(value.as_bytes()[index * 2])?;
-        let lo = hex_nibble(value.as_bytes()[index * 2 + 1])?;
-        *slot = (hi << 4) | lo;
-    }
-    Some(out)
-}
-
-fn generate_kek(
-    src: &[u8; 16],
-    master_key: &[u8; 16],
-    intermediate_kek_seed: &[u8; 16],
-    final_key_seed: &[u8; 16],
-) -> Result<[u8; 16], NszError> {
-    let kek = aes_ecb_decrypt_block(master_key, intermediate_kek_seed)?;
-    let src_kek = aes_ecb_decrypt_block(&kek, src)?;
-    aes_ecb_decrypt_block(&src_kek, final_key_seed)
-}
-
-fn aes_ecb_decrypt_block(key: &[u8; 16], block: &[u8; 16]) -> Result<[u8; 16], NszError> {
-    let cipher = Aes128::new_from_slice(key).map_err(|_| NszError::ContainerFormat {
-        message: "invalid AES-128 key".to_string(),
-    })?;
-    let mut out = *block;
-    cipher.decrypt_block(GenericArray::from_mut_slice(&mut out));
-    Ok(out)
-}
-
-fn aes_ecb_decrypt(key: &[u8; 16], data: &[u8]) -> Result<Vec<u8>, NszError> {
-    if !data.len().is_multiple_of(16) {
-        return Err(NszError::ContainerFormat {
-            message: "AES-ECB data is not 16-byte aligned".to_string(),
-        });
-    }
-    let cipher = Aes128::new_from_slice(key).map_err(|_| NszError::ContainerFormat {
-        message: "invalid AES-128 key".to_string(),
-    })?;
-    let mut out = data.to_vec();
-    for block in out.chunks_exact_mut(16) {
-        cipher.decrypt_block(GenericArray::from_mut_slice(block));
-    }
-    Ok(out)
-}
-
-fn apply_aes_ctr(buf: &mut [u8], key: &[u8; 16], counter: &[u8; 16], offset: u128) {
-    type AesCtr = ctr::Ctr128BE<Aes128>;
-    let mut cipher = AesCtr::new(key.into(), counter.into());
-    cipher.seek(offset);
-    cipher.apply_keystream(buf);
-}
-
 fn set_bktr_counter(base_counter: [u8; 16], ctr_value: u32) -> [u8; 16] {
     let mut counter = base_counter;
     for index in 0..8 {
@@ -656,77 +998,3 @@ fn set_bktr_counter(base_counter: [u8; 16], ctr_value: u32) -> [u8; 16] {
     }
     counter
 }
-
-fn hex_string(data: &[u8]) -> String {
-    let mut out = String::with_capacity(data.len() * 2);
-    for byte in data {
-        out.push(hex_digit(byte >> 4));
-        out.push(hex_digit(byte & 0x0F));
-    }
-    out
-}
-
-fn hex_digit(value: u8) -> char {
-    match value {
-        0..=9 => (b'0' + value) as char,
-        _ => (b'a' + (value - 10)) as char,
-    }
-}
-
-fn hex_nibble(byte: u8) -> Option<u8> {
-    match byte {
-        b'0'..=b'9' => Some(byte - b'0'),
-        b'a'..=b'f' => Some(byte - b'a' + 10),
-        b'A'..=b'F' => Some(byte - b'A' + 10),
-        _ => None,
-    }
-}
-
-fn decrypt_nca_header_xts(data: &mut [u8], header_key: &[u8; 32]) -> Result<(), NszError> {
-    if !data.len().is_multiple_of(NCA_SECTOR_SIZE) {
-        return Err(NszError::ContainerFormat {
-            message: "NCA header size is not aligned to XTS sector size".to_string(),
-        });
-    }
-
-    let cipher_data =
-        Aes128::new_from_slice(&header_key[..16]).map_err(|_| NszError::ContainerFormat {
-            message: "invalid NCA XTS data key length".to_string(),
-        })?;
-    let cipher_tweak =
-        Aes128::new_from_slice(&header_key[16..]).map_err(|_| NszError::ContainerFormat {
-            message: "invalid NCA XTS tweak key length".to_string(),
-        })?;
-
-    for (sector_index, sector) in data.chunks_exact_mut(NCA_SECTOR_SIZE).enumerate() {
-        let mut tweak = [0u8; 16];
-        tweak[8..16].copy_from_slice(&(sector_index as u64).to_be_bytes());
-        let mut tweak_block = tweak;
-        cipher_tweak.encrypt_block(GenericArray::from_mut_slice(&mut tweak_block));
-
-        for block in sector.chunks_exact_mut(16) {
-            for (index, value) in block.iter_mut().enumerate() {
-                *value ^= tweak_block[index];
-            }
-            cipher_data.decrypt_block(GenericArray::from_mut_slice(block));
-            for (index, value) in block.iter_mut().enumerate() {
-                *value ^= tweak_block[index];
-            }
-            xts_mul_alpha_le(&mut tweak_block);
-        }
-    }
-
-    Ok(())
-}
-
-fn xts_mul_alpha_le(tweak: &mut [u8; 16]) {
-    let mut carry = 0u8;
-    for byte in tweak.iter_mut() {
-        let new_carry = *byte >> 7;
-        *byte = (*byte << 1) | carry;
-        carry = new_carry;
-    }
-    if carry != 0 {
-        tweak[0] ^= 0x87;
-    }
-}
@@ -257,3 +257,22 @@ pub fn encode_pfs0<B: AsRef<[u8]>>(
     }
     Ok(out)
 }
+
+/// Encodes entries into a new PFS0 container laid out like upstream `nsz` output.
+///
+/// Names are NUL-separated and the string table is padded with 1 to 16 NUL bytes so
+/// the header ends on a 0x10 boundary.
+pub fn encode_new_pfs0<B: AsRef<[u8]>>(entries: &[(String, B)]) -> Result<Vec<u8>, NszError> {
+    let names_size = entries
+        .iter()
+        .map(|(name, _)| name.len())
+        .sum::<usize>()
+        .saturating_add(entries.len().saturating_sub(1));
+    let unpadded = 16 + entries.len() * 24 + names_size;
+    let header_size = unpadded + (0x10 - unpadded % 0x10);
+    encode_pfs0(
+        entries,
+        header_size as u64,
+        (header_size - 16 - entries.len() * 24) as u32,
+    )
+}
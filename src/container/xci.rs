@@ -1,6 +1,159 @@
-use crate::container::hfs0::Hfs0Archive;
+use std::io::{Read, Seek, SeekFrom};
+
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+use crate::container::hfs0::{encode_hfs0, Hfs0Archive};
 use crate::error::NszError;
 
+/// Gamecard media unit size used by header addresses.
+pub const XCI_MEDIA_UNIT_SIZE: u64 = 0x200;
+
+/// Byte used to pad gamecard images out to the full card size.
+pub const XCI_PADDING_BYTE: u8 = 0xFF;
+
+/// Public exponent of the gamecard header signing key.
+const XCI_HEADER_SIGN_EXPONENT: u32 = 0x10001;
+
+/// Outcome of checking the card header's RSA-2048 signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum XciSignatureStatus {
+    /// The signature matches the header and the signing modulus.
+    Valid,
+    /// The signature does not match; the header was edited or is not from a retail card.
+    Invalid,
+    /// No `xci_header_sign_modulus` was loaded, so the signature was not checked.
+    Unchecked,
+}
+
+/// ROM size bytes, from the smallest card to the largest.
+const XCI_ROM_SIZES: [u8; 6] = [0xFA, 0xF8, 0xF0, 0xE0, 0xE1, 0xE2];
+
+/// Usable bytes of the card the ROM size byte stands for.
+///
+/// Cards reserve 0x24 bytes of every 0x200 for ECC, so a 1 GiB card holds `0x3B80_0000` bytes.
+fn rom_size_capacity(rom_size: u8) -> Option<u64> {
+    let gib = match rom_size {
+        0xFA => 1,
+        0xF8 => 2,
+        0xF0 => 4,
+        0xE0 => 8,
+        0xE1 => 16,
+        0xE2 => 32,
+        _ => return None,
+    };
+    let capacity = gib << 30;
+    Some(capacity - capacity / XCI_MEDIA_UNIT_SIZE * 0x24)
+}
+
+/// Size of the gamecard header, signature included.
+pub const XCI_CARD_HEADER_SIZE: usize = 0x200;
+
+/// Bytes of an image's tail read at a time while checking it is padding.
+const PADDING_SCAN_CHUNK_SIZE: usize = 0x10_0000;
+
+/// Bytes that hold the card header whether or not a key area precedes it.
+pub const XCI_HEADER_PREFIX_SIZE: usize = 0x1000 + XCI_CARD_HEADER_SIZE;
+
+/// Gamecard header fields, as stored at the start of an XCI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XciCardHeader {
+    /// RSA-2048 PKCS#1 signature over the header bytes from `0x100`.
+    pub signature: [u8; 0x100],
+    /// Secure area start, in media units.
+    pub secure_area_start: u32,
+    /// Backup area start, in media units (`0xFFFF_FFFF` on retail cards).
+    pub backup_area_start: u32,
+    /// Title key decryption index (high nibble) and KEK index (low nibble).
+    pub key_index: u8,
+    /// ROM size byte selecting the card capacity.
+    pub rom_size: u8,
+    /// Header version.
+    pub version: u8,
+    /// Card flags (auto-boot, history erase, repair tool, region bits).
+    pub flags: u8,
+    /// Package ID used to select the card's initial data.
+    pub package_id: u64,
+    /// Last media unit holding card data.
+    pub valid_data_end: u32,
+    /// IV for the encrypted gamecard info, stored byte-reversed.
+    pub iv: [u8; 0x10],
+    /// Root HFS0 offset relative to the header.
+    pub hfs0_offset: u64,
+    /// Root HFS0 header size.
+    pub hfs0_header_size: u64,
+    /// SHA-256 of the root HFS0 header.
+    pub hfs0_header_hash: [u8; 0x20],
+    /// SHA-256 of the initial data.
+    pub initial_data_hash: [u8; 0x20],
+    /// Secure mode selector.
+    pub sel_sec: u32,
+    /// T1 key selector.
+    pub sel_t1_key: u32,
+    /// Key selector.
+    pub sel_key: u32,
+    /// Normal area end, in media units.
+    pub normal_area_end: u32,
+    /// AES-128-CBC encrypted gamecard info block.
+    pub encrypted_gamecard_info: [u8; 0x70],
+}
+
+impl XciCardHeader {
+    /// Parses a gamecard header from its first [`XCI_CARD_HEADER_SIZE`] bytes.
+    pub fn from_bytes(header: &[u8]) -> Result<Self, NszError> {
+        if header.len() < XCI_CARD_HEADER_SIZE {
+            return Err(NszError::ContainerFormat {
+                message: "XCI header truncated".to_string(),
+            });
+        }
+        if &header[0x100..0x104] != b"HEAD" {
+            return Err(NszError::ContainerFormat {
+                message: "XCI header magic mismatch".to_string(),
+            });
+        }
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+        Ok(Self {
+            signature: header[..0x100].try_into().unwrap(),
+            secure_area_start: u32_at(0x104),
+            backup_area_start: u32_at(0x108),
+            key_index: header[0x10C],
+            rom_size: header[0x10D],
+            version: header[0x10E],
+            flags: header[0x10F],
+            package_id: u64_at(0x110),
+            valid_data_end: u32_at(0x118),
+            iv: header[0x120..0x130].try_into().unwrap(),
+            hfs0_offset: u64_at(0x130),
+            hfs0_header_size: u64_at(0x138),
+            hfs0_header_hash: header[0x140..0x160].try_into().unwrap(),
+            initial_data_hash: header[0x160..0x180].try_into().unwrap(),
+            sel_sec: u32_at(0x180),
+            sel_t1_key: u32_at(0x184),
+            sel_key: u32_at(0x188),
+            normal_area_end: u32_at(0x18C),
+            encrypted_gamecard_info: header[0x190..0x200].try_into().unwrap(),
+        })
+    }
+
+    /// Byte offset of the secure area relative to the header.
+    pub fn secure_area_offset(&self) -> u64 {
+        u64::from(self.secure_area_start) * XCI_MEDIA_UNIT_SIZE
+    }
+
+    /// Byte offset where the normal area ends, relative to the header.
+    pub fn normal_area_end_offset(&self) -> u64 {
+        u64::from(self.normal_area_end) * XCI_MEDIA_UNIT_SIZE
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct XciArchive {
     /// Header base offset used for this XCI layout.
@@ -9,33 +162,31 @@ pub struct XciArchive {
     pub hfs0_offset: u64,
     /// Root HFS0 header size field from the XCI header.
     pub hfs0_header_size: u64,
+    /// Parsed gamecard header.
+    pub card_header: XciCardHeader,
 }
 
 impl XciArchive {
     /// Parses the XCI root header and HFS0 location metadata.
     pub fn from_bytes(data: &[u8]) -> Result<Self, NszError> {
-        let header_offset = if data.len() >= 0x104 && &data[0x100..0x104] == b"HEAD" {
+        Self::from_prefix(data, data.len() as u64)
+    }
+
+    /// Like [`XciArchive::from_bytes`], from the first bytes of a `file_len`-byte image.
+    ///
+    /// The prefix must reach the end of the card header, so [`XCI_HEADER_PREFIX_SIZE`]
+    /// bytes always suffice.
+    pub fn from_prefix(prefix: &[u8], file_len: u64) -> Result<Self, NszError> {
+        let header_offset = if prefix.len() >= 0x104 && &prefix[0x100..0x104] == b"HEAD" {
             0u64
         } else {
             0x1000u64
         };
         let base = header_offset as usize;
 
-        if data.len() < base + 0x140 {
-            return Err(NszError::ContainerFormat {
-                message: "XCI header truncated".to_string(),
-            });
-        }
-
-        if &data[base + 0x100..base + 0x104] != b"HEAD" {
-            return Err(NszError::ContainerFormat {
-                message: "XCI header magic mismatch".to_string(),
-            });
-        }
-
-        let hfs0_offset = u64::from_le_bytes(data[base + 0x130..base + 0x138].try_into().unwrap());
-        let hfs0_header_size =
-            u64::from_le_bytes(data[base + 0x138..base + 0x140].try_into().unwrap());
+        let card_header = XciCardHeader::from_bytes(prefix.get(base..).unwrap_or_default())?;
+        let hfs0_offset = card_header.hfs0_offset;
+        let hfs0_header_size = card_header.hfs0_header_size;
         let hfs0_abs_offset =
             header_offset
                 .checked_add(hfs0_offset)
@@ -43,7 +194,7 @@ impl XciArchive {
                     message: "XCI HFS0 offset overflow".to_string(),
                 })?;
 
-        if hfs0_abs_offset as usize >= data.len() {
+        if hfs0_abs_offset >= file_len {
             return Err(NszError::ContainerFormat {
                 message: "XCI HFS0 offset outside file".to_string(),
             });
@@ -53,6 +204,7 @@ impl XciArchive {
             header_offset,
             hfs0_offset,
             hfs0_header_size,
+            card_header,
         })
     }
 
@@ -86,18 +238,197 @@ impl XciArchive {
                 message: "XCI root HFS0 absolute offset overflow".to_string(),
             })
     }
+
+    /// Computes the SHA-256 of the root HFS0 header region declared by the card header.
+    pub fn compute_root_hfs0_header_hash(&self, data: &[u8]) -> Result<[u8; 0x20], NszError> {
+        let root = self.root_hfs0_bytes(data)?;
+        let header = usize::try_from(self.hfs0_header_size)
+            .ok()
+            .and_then(|size| root.get(..size))
+            .ok_or_else(|| NszError::ContainerFormat {
+                message: "XCI root HFS0 header size exceeds file".to_string(),
+            })?;
+        Ok(Sha256::digest(header).into())
+    }
+
+    /// Checks the card header's RSA-2048 PKCS#1 v1.5 signature over its bytes from `0x100`.
+    pub fn signature_status(
+        &self,
+        data: &[u8],
+        modulus: Option<&[u8; 0x100]>,
+    ) -> XciSignatureStatus {
+        let Some(modulus) = modulus else {
+            return XciSignatureStatus::Unchecked;
+        };
+        let base = self.header_offset as usize;
+        let Ok(key) = RsaPublicKey::new(
+            BigUint::from_bytes_be(modulus),
+            BigUint::from(XCI_HEADER_SIGN_EXPONENT),
+        ) else {
+            return XciSignatureStatus::Invalid;
+        };
+        let digest = Sha256::digest(&data[base + 0x100..base + XCI_CARD_HEADER_SIZE]);
+        match key.verify(
+            Pkcs1v15Sign::new::<Sha256>(),
+            &digest,
+            &self.card_header.signature,
+        ) {
+            Ok(()) => XciSignatureStatus::Valid,
+            Err(_) => XciSignatureStatus::Invalid,
+        }
+    }
+
+    /// File size of the image trimmed to the card header's valid data end address.
+    pub fn used_size(&self) -> u64 {
+        self.header_offset + (u64::from(self.card_header.valid_data_end) + 1) * XCI_MEDIA_UNIT_SIZE
+    }
+
+    /// File size of the untrimmed image, from the ROM size byte.
+    pub fn card_size(&self) -> Result<u64, NszError> {
+        let rom_size = self.card_header.rom_size;
+        let capacity = rom_size_capacity(rom_size).ok_or_else(|| NszError::UnsupportedFeature {
+            feature: format!("XCI ROM size byte {rom_size:#04x}"),
+        })?;
+        Ok(self.header_offset + capacity)
+    }
+}
+
+/// Rewrites the card header's root HFS0 header hash to match the image's current root HFS0.
+///
+/// Any change to the header invalidates its signature; see [`XciArchive::signature_status`].
+pub fn refresh_root_hfs0_header_hash(image: &mut [u8]) -> Result<(), NszError> {
+    let archive = XciArchive::from_bytes(image)?;
+    let hash = archive.compute_root_hfs0_header_hash(image)?;
+    let base = archive.header_offset as usize;
+    image[base + 0x140..base + 0x160].copy_from_slice(&hash);
+    Ok(())
+}
+
+/// Root HFS0 offset used for images built by [`encode_xci`], after the cert region.
+const BUILT_XCI_HFS0_OFFSET: usize = 0xF000;
+
+/// Builds an unsigned gamecard image whose root HFS0 holds empty `update` and `normal`
+/// partitions and the given `secure` partition.
+///
+/// The header picks the smallest card that fits and ends the valid data at the image end.
+pub fn encode_xci(secure_partition: &[u8]) -> Result<Vec<u8>, NszError> {
+    let unit = XCI_MEDIA_UNIT_SIZE as usize;
+    let no_entries: [(String, &[u8]); 0] = [];
+    let empty_partition = encode_hfs0(&no_entries, XCI_MEDIA_UNIT_SIZE, 0)?;
+    let partitions = [
+        ("update".to_string(), empty_partition.as_slice()),
+        ("normal".to_string(), empty_partition.as_slice()),
+        ("secure".to_string(), secure_partition),
+    ];
+    let root = encode_hfs0(&partitions, XCI_MEDIA_UNIT_SIZE, 0)?;
+
+    let mut out = vec![0u8; BUILT_XCI_HFS0_OFFSET];
+    out.extend_from_slice(&root);
+    out.resize(out.len().next_multiple_of(unit), 0);
+
+    let len = out.len() as u64;
+    let rom_size = XCI_ROM_SIZES
+        .into_iter()
+        .find(|rom_size| rom_size_capacity(*rom_size).is_some_and(|capacity| len <= capacity))
+        .ok_or_else(|| NszError::UnsupportedFeature {
+            feature: format!("XCI images larger than a 32 GiB card ({len:#x} bytes)"),
+        })?;
+    let secure_offset = BUILT_XCI_HFS0_OFFSET + root.len() - secure_partition.len();
+    let secure_area_start = u32::try_from(secure_offset / unit).unwrap_or(u32::MAX);
+    let valid_data_end =
+        u32::try_from(out.len() / unit - 1).map_err(|_| NszError::ContainerFormat {
+            message: "XCI valid data end does not fit the card header".to_string(),
+        })?;
+
+    let header = &mut out[..XCI_CARD_HEADER_SIZE];
+    header[0x100..0x104].copy_from_slice(b"HEAD");
+    header[0x104..0x108].copy_from_slice(&secure_area_start.to_le_bytes());
+    header[0x108..0x10C].copy_from_slice(&u32::MAX.to_le_bytes());
+    header[0x10D] = rom_size;
+    header[0x118..0x11C].copy_from_slice(&valid_data_end.to_le_bytes());
+    header[0x130..0x138].copy_from_slice(&(BUILT_XCI_HFS0_OFFSET as u64).to_le_bytes());
+    header[0x138..0x140].copy_from_slice(&XCI_MEDIA_UNIT_SIZE.to_le_bytes());
+    header[0x18C..0x190].copy_from_slice(&secure_area_start.to_le_bytes());
+    refresh_root_hfs0_header_hash(&mut out)?;
+    Ok(out)
+}
+
+/// Returns the image cut to its used data size, failing if anything but padding would be removed.
+pub fn trim_xci(data: &[u8]) -> Result<&[u8], NszError> {
+    let archive = XciArchive::from_bytes(data)?;
+    let used = archive.used_size();
+    let used_len = usize::try_from(used).unwrap_or(usize::MAX);
+    if used_len > data.len() {
+        return Err(NszError::ContainerFormat {
+            message: format!(
+                "XCI is {:#x} bytes but its header declares {used:#x} bytes of data",
+                data.len()
+            ),
+        });
+    }
+    ensure_padding(&data[used_len..], used, used)?;
+    Ok(&data[..used_len])
+}
+
+/// Returns how many `0xFF` bytes restore a trimmed image to its full card size.
+///
+/// Already-untrimmed images need none; any tail past the used data must be padding. Only
+/// the card header and that tail are read, a chunk at a time.
+pub fn untrim_padding_len(image: &mut (impl Read + Seek)) -> Result<u64, NszError> {
+    let len = image.seek(SeekFrom::End(0))?;
+    let mut prefix = vec![0u8; XCI_HEADER_PREFIX_SIZE.min(len as usize)];
+    image.seek(SeekFrom::Start(0))?;
+    image.read_exact(&mut prefix)?;
+    let archive = XciArchive::from_prefix(&prefix, len)?;
+    let used = archive.used_size();
+    let card = archive.card_size()?;
+    if used > card || len < used || len > card {
+        return Err(NszError::ContainerFormat {
+            message: format!(
+                "XCI is {len:#x} bytes, outside its used size {used:#x} and card size {card:#x}"
+            ),
+        });
+    }
+
+    image.seek(SeekFrom::Start(used))?;
+    let mut chunk = vec![0u8; PADDING_SCAN_CHUNK_SIZE];
+    let mut offset = used;
+    while offset < len {
+        let read = image.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        ensure_padding(&chunk[..read], offset, used)?;
+        offset += read as u64;
+    }
+    Ok(card - len)
+}
+
+/// Checks that `tail`, found at `offset` past the used data end `used`, is all padding.
+fn ensure_padding(tail: &[u8], offset: u64, used: u64) -> Result<(), NszError> {
+    let Some(position) = tail.iter().position(|byte| *byte != XCI_PADDING_BYTE) else {
+        return Ok(());
+    };
+    Err(NszError::ContainerFormat {
+        message: format!(
+            "XCI has non-padding data at {:#x}, past its used data size {used:#x}",
+            offset + position as u64
+        ),
+    })
 }
 
-/// Rebuilds an XCI-like output by preserving the original prefix and replacing root HFS0 bytes.
+/// Rebuilds an XCI-like output by keeping every byte before the root HFS0 and replacing the tree.
+///
+/// The kept prefix holds the key area (when dumped), card header, initial data and the
+/// certificate region at `0x7000`.
 pub fn encode_xci_like(
     input: &[u8],
     archive: &XciArchive,
     root_hfs0: &[u8],
 ) -> Result<Vec<u8>, NszError> {
     let root_offset = archive.root_hfs0_absolute_offset()? as usize;
-    let prefix_len = input.len().min(0x200);
-    let mut out = input[..prefix_len].to_vec();
-    out.resize(root_offset, 0);
+    let mut out = Vec::with_capacity(root_offset + root_hfs0.len());
+    out.extend_from_slice(&input[..root_offset]);
     out.extend_from_slice(root_hfs0);
     Ok(out)
 }
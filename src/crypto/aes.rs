@@ -0,0 +1,110 @@
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use zeroize::Zeroizing;
+
+use crate::error::NszError;
+
+/// Decrypts one AES-128-ECB block.
+pub fn ecb_decrypt_block(key: &[u8; 16], block: &[u8; 16]) -> Result<[u8; 16], NszError> {
+    let cipher = Aes128::new_from_slice(key).map_err(|_| NszError::ContainerFormat {
+        message: "invalid AES-128 key".to_string(),
+    })?;
+    let mut out = *block;
+    cipher.decrypt_block(GenericArray::from_mut_slice(&mut out));
+    Ok(out)
+}
+
+/// Decrypts 16-byte aligned data with AES-128-ECB.
+pub fn ecb_decrypt(key: &[u8; 16], data: &[u8]) -> Result<Vec<u8>, NszError> {
+    if !data.len().is_multiple_of(16) {
+        return Err(NszError::ContainerFormat {
+            message: "AES-ECB data is not 16-byte aligned".to_string(),
+        });
+    }
+    let cipher = Aes128::new_from_slice(key).map_err(|_| NszError::ContainerFormat {
+        message: "invalid AES-128 key".to_string(),
+    })?;
+    let mut out = data.to_vec();
+    for block in out.chunks_exact_mut(16) {
+        cipher.decrypt_block(GenericArray::from_mut_slice(block));
+    }
+    Ok(out)
+}
+
+/// Derives a key the way Horizon's `GenerateAesKek` + `GenerateAesKey` do.
+pub fn generate_kek(
+    src: &[u8; 16],
+    master_key: &[u8; 16],
+    intermediate_kek_seed: &[u8; 16],
+    final_key_seed: &[u8; 16],
+) -> Result<[u8; 16], NszError> {
+    let kek = Zeroizing::new(ecb_decrypt_block(master_key, intermediate_kek_seed)?);
+    let src_kek = Zeroizing::new(ecb_decrypt_block(&kek, src)?);
+    ecb_decrypt_block(&src_kek, final_key_seed)
+}
+
+/// Applies AES-128-CTR keystream starting `offset` bytes into the stream.
+pub fn ctr_apply(buf: &mut [u8], key: &[u8; 16], counter: &[u8; 16], offset: u128) {
+    type AesCtr = ctr::Ctr128BE<Aes128>;
+    let mut cipher = AesCtr::new(key.into(), counter.into());
+    cipher.seek(offset);
+    cipher.apply_keystream(buf);
+}
+
+/// Decrypts `data` in place with Nintendo's big-endian-tweak AES-128-XTS.
+///
+/// `sector_size` must divide `data.len()`; the first sector uses tweak `first_sector`.
+pub fn xts_decrypt(
+    data: &mut [u8],
+    key: &[u8; 32],
+    sector_size: usize,
+    first_sector: u64,
+) -> Result<(), NszError> {
+    if sector_size == 0 || !data.len().is_multiple_of(sector_size) {
+        return Err(NszError::ContainerFormat {
+            message: "XTS data size is not aligned to the sector size".to_string(),
+        });
+    }
+
+    let cipher_data =
+        Aes128::new_from_slice(&key[..16]).map_err(|_| NszError::ContainerFormat {
+            message: "invalid XTS data key length".to_string(),
+        })?;
+    let cipher_tweak =
+        Aes128::new_from_slice(&key[16..]).map_err(|_| NszError::ContainerFormat {
+            message: "invalid XTS tweak key length".to_string(),
+        })?;
+
+    for (sector_index, sector) in data.chunks_exact_mut(sector_size).enumerate() {
+        let mut tweak = [0u8; 16];
+        tweak[8..16].copy_from_slice(&(first_sector + sector_index as u64).to_be_bytes());
+        cipher_tweak.encrypt_block(GenericArray::from_mut_slice(&mut tweak));
+
+        for block in sector.chunks_exact_mut(16) {
+            for (value, mask) in block.iter_mut().zip(tweak.iter()) {
+                *value ^= mask;
+            }
+            cipher_data.decrypt_block(GenericArray::from_mut_slice(block));
+            for (value, mask) in block.iter_mut().zip(tweak.iter()) {
+                *value ^= mask;
+            }
+            xts_mul_alpha_le(&mut tweak);
+        }
+    }
+
+    Ok(())
+}
+
+fn xts_mul_alpha_le(tweak: &mut [u8; 16]) {
+    let mut carry = 0u8;
+    for byte in tweak.iter_mut() {
+        let new_carry = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = new_carry;
+    }
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
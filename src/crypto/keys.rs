@@ -1,43 +1,277 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
 
+use zeroize::Zeroize;
+
+use crate::crypto::keyset::NcaKeySet;
+use crate::crypto::Redacted;
 use crate::error::NszError;
 
-const REQUIRED_KEYS: &[&str] = &[
+/// Keys stored as 32-byte XTS key pairs.
+const XTS_KEYS: &[&str] = &[
+    "header_key",
+    "header_key_source",
+    "sd_card_save_key_source",
+    "sd_card_nca_key_source",
+    "sd_card_custom_storage_key_source",
+    "sd_card_save_key",
+    "sd_card_nca_key",
+    "sd_card_custom_storage_key",
+];
+
+/// Single AES-128 keys consumed by the NCA key set.
+const AES_KEYS: &[&str] = &[
     "aes_kek_generation_source",
     "aes_key_generation_source",
     "titlekek_source",
     "key_area_key_application_source",
     "key_area_key_ocean_source",
     "key_area_key_system_source",
+    "master_key_source",
+    "header_kek_source",
+    "mariko_kek",
+];
+
+/// RSA-2048 public moduli used to check signatures.
+const RSA_MODULI: &[&str] = &["xci_header_sign_modulus"];
+
+/// Prefixes of `<prefix>XX` AES-128 key families.
+const INDEXED_AES_KEYS: &[&str] = &[
+    "master_key_",
+    "master_kek_",
+    "master_kek_source_",
+    "mariko_master_kek_source_",
+    "tsec_root_key_",
+    "titlekek_",
+    "key_area_key_application_",
+    "key_area_key_ocean_",
+    "key_area_key_system_",
 ];
 
-#[derive(Debug, Clone)]
-pub struct LoadedKeys {
-    pub values: HashMap<String, String>,
+/// One decoded entry of a keys file.
+#[derive(PartialEq, Eq)]
+pub struct KeyEntry {
+    /// Decoded key bytes.
+    pub value: Vec<u8>,
+    /// 1-based line the entry was read from.
+    pub line: usize,
 }
 
-/// Parses key-value text and validates the minimum required key set.
-pub fn load_from_str(content: &str) -> Result<LoadedKeys, NszError> {
-    let mut values = HashMap::new();
+/// Parsed `prod.keys`/hactool `keys.txt` content.
+///
+/// Names are case-insensitive and stored lowercase. Entries are `name = value`
+/// (hactool also accepts `name, value`); lines starting with `#` or `;` are comments.
+#[derive(Default)]
+pub struct KeyFile {
+    entries: BTreeMap<String, KeyEntry>,
+    duplicates: Vec<(String, usize)>,
+}
 
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with('#') {
-            continue;
+impl KeyFile {
+    /// Parses and validates keys file content.
+    ///
+    /// Malformed lines, non-hex values, wrong lengths for known keys and entries
+    /// that redefine a key with a different value are rejected with their line number.
+    /// Repeated identical entries are accepted and listed by [`KeyFile::duplicates`].
+    pub fn parse(content: &str) -> Result<Self, NszError> {
+        let mut out = Self::default();
+        for (line, name, value) in scan(content)? {
+            let value = decode_hex(&value).ok_or_else(|| NszError::InvalidKeyFile {
+                line,
+                message: format!("`{name}` is not a hex string"),
+            })?;
+            if let Some(expected) = expected_len(&name) {
+                if value.len() != expected {
+                    return Err(NszError::InvalidKeyFile {
+                        line,
+                        message: format!("`{name}` must be {expected} bytes, got {}", value.len()),
+                    });
+                }
+            }
+            if let Some(existing) = out.entries.get(&name) {
+                if existing.value != value {
+                    return Err(NszError::InvalidKeyFile {
+                        line,
+                        message: format!(
+                            "`{name}` conflicts with the value on line {}",
+                            existing.line
+                        ),
+                    });
+                }
+                out.duplicates.push((name, line));
+                continue;
+            }
+            out.entries.insert(name, KeyEntry { value, line });
         }
+        Ok(out)
+    }
 
-        if let Some((key, value)) = trimmed.split_once('=') {
-            values.insert(key.trim().to_string(), value.trim().to_string());
-        }
+    /// Returns the entry for `name`, looked up case-insensitively.
+    pub fn entry(&self, name: &str) -> Option<&KeyEntry> {
+        self.entries.get(&name.to_ascii_lowercase())
+    }
+
+    /// Returns the key bytes for `name` when present with exactly `N` bytes.
+    pub fn get<const N: usize>(&self, name: &str) -> Option<[u8; N]> {
+        self.entry(name)?.value.as_slice().try_into().ok()
+    }
+
+    /// Returns every `<prefix>XX` AES-128 key keyed by its hex index.
+    pub fn indexed(&self, prefix: &str) -> BTreeMap<u8, [u8; 16]> {
+        self.entries
+            .iter()
+            .filter_map(|(name, entry)| {
+                let index = key_index(name, prefix)?;
+                Some((index, entry.value.as_slice().try_into().ok()?))
+            })
+            .collect()
+    }
+
+    /// Iterates over entry names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Lists repeated identical entries as `(name, line)` pairs.
+    pub fn duplicates(&self) -> &[(String, usize)] {
+        &self.duplicates
+    }
+
+    /// Returns the number of distinct keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no keys were parsed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Debug for KeyEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyEntry")
+            .field("value", &Redacted)
+            .field("line", &self.line)
+            .finish()
+    }
+}
+
+impl Drop for KeyEntry {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl fmt::Debug for KeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyFile")
+            .field("entries", &self.entries)
+            .field("duplicates", &self.duplicates)
+            .finish()
     }
+}
+
+/// Parses key-value text and checks that it yields a usable NCA key set.
+///
+/// Source keys are optional: [`NcaKeySet::from_key_file`] derives what it can from those
+/// present and only fails when no header key can be had.
+pub fn load_from_str(content: &str) -> Result<KeyFile, NszError> {
+    let file = KeyFile::parse(content)?;
+    NcaKeySet::from_key_file(&file)?;
+    Ok(file)
+}
 
-    for key in REQUIRED_KEYS {
-        if !values.contains_key(*key) {
-            return Err(NszError::MissingRequiredKey {
-                key: (*key).to_string(),
+/// Splits content into `(line, lowercase name, value)` triples.
+fn scan(content: &str) -> Result<Vec<(usize, String, String)>, NszError> {
+    let mut out = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        let Some((name, value)) = trimmed.split_once(['=', ',']) else {
+            return Err(NszError::InvalidKeyFile {
+                line: index + 1,
+                message: "expected `name = value`".to_string(),
+            });
+        };
+        let name = name.trim().to_ascii_lowercase();
+        if name.is_empty() {
+            return Err(NszError::InvalidKeyFile {
+                line: index + 1,
+                message: "missing key name".to_string(),
             });
         }
+        out.push((index + 1, name, value.trim().to_string()));
+    }
+    Ok(out)
+}
+
+fn expected_len(name: &str) -> Option<usize> {
+    if RSA_MODULI.contains(&name) {
+        return Some(0x100);
+    }
+    if XTS_KEYS.contains(&name) {
+        return Some(32);
+    }
+    if AES_KEYS.contains(&name)
+        || INDEXED_AES_KEYS
+            .iter()
+            .any(|prefix| key_index(name, prefix).is_some())
+    {
+        return Some(16);
+    }
+    None
+}
+
+fn key_index(name: &str, prefix: &str) -> Option<u8> {
+    let suffix = name.strip_prefix(prefix)?;
+    if suffix.len() != 2 {
+        return None;
+    }
+    u8::from_str_radix(suffix, 16).ok()
+}
+
+/// Decodes an even-length hex string.
+pub(crate) fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.is_empty() || !value.len().is_multiple_of(2) {
+        return None;
     }
+    value
+        .as_bytes()
+        .chunks_exact(2)
+        .map(|pair| Some((hex_nibble(pair[0])? << 4) | hex_nibble(pair[1])?))
+        .collect()
+}
+
+/// Decodes a hex string of exactly `N` bytes.
+pub(crate) fn decode_hex_array<const N: usize>(value: &str) -> Option<[u8; N]> {
+    decode_hex(value)?.try_into().ok()
+}
 
-    Ok(LoadedKeys { values })
+/// Encodes bytes as lowercase hex.
+pub(crate) fn hex_string(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 2);
+    for byte in data {
+        out.push(hex_digit(byte >> 4));
+        out.push(hex_digit(byte & 0x0F));
+    }
+    out
+}
+
+fn hex_digit(value: u8) -> char {
+    match value {
+        0..=9 => (b'0' + value) as char,
+        _ => (b'a' + (value - 10)) as char,
+    }
+}
+
+fn hex_nibble(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
 }
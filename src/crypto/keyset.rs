@@ -0,0 +1,366 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::aes::{ecb_decrypt, ecb_decrypt_block, generate_kek};
+use crate::crypto::keys::KeyFile;
+use crate::crypto::Redacted;
+use crate::error::NszError;
+
+/// Where a key held by [`NcaKeySet`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOrigin {
+    /// The key was present verbatim in the keys file.
+    Supplied,
+    /// The key was derived from source keys in the keys file.
+    Derived,
+}
+
+const KEY_AREA_KEY_KINDS: [&str; 3] = ["application", "ocean", "system"];
+
+pub struct NcaKeySet {
+    /// NCA header key used for header decryption.
+    pub header_key: [u8; 32],
+    master_keys: KeyMap,
+    title_keks: KeyMap,
+    key_area_keys: [KeyMap; 3],
+    missing_sources: HashMap<&'static str, Vec<&'static str>>,
+    origins: BTreeMap<String, KeyOrigin>,
+    inconsistent: Vec<String>,
+}
+
+impl NcaKeySet {
+    /// Parses key material from a `keys.txt`-style content string.
+    ///
+    /// Keys that are absent but derivable from source keys (header key, master keys,
+    /// title KEKs and key area keys) are derived; see [`NcaKeySet::key_origins`].
+    pub fn from_keys_str(content: &str) -> Result<Self, NszError> {
+        Self::from_key_file(&KeyFile::parse(content)?)
+    }
+
+    /// Builds the key set from an already parsed keys file.
+    pub fn from_key_file(file: &KeyFile) -> Result<Self, NszError> {
+        let mut origins = BTreeMap::new();
+        let aes_kek_generation_source = secret(file.get::<16>("aes_kek_generation_source"));
+        let aes_key_generation_source = secret(file.get::<16>("aes_key_generation_source"));
+        let master_key_source = secret(file.get::<16>("master_key_source"));
+        let mariko_kek = secret(file.get::<16>("mariko_kek"));
+        let tsec_root_keys = KeyMap(file.indexed("tsec_root_key_"));
+        let master_kek_sources = KeyMap(file.indexed("master_kek_source_"));
+        let mariko_master_kek_sources = KeyMap(file.indexed("mariko_master_kek_source_"));
+
+        let mut master_keks = KeyMap(file.indexed("master_kek_"));
+        record_supplied(&mut origins, "master_kek", &master_keks);
+        let kek_sources = master_kek_sources
+            .iter()
+            .filter_map(|(index, source)| {
+                let root = tsec_root_keys.get(&index.checked_sub(6)?)?;
+                Some((*index, root, source))
+            })
+            .chain(
+                mariko_master_kek_sources
+                    .iter()
+                    .filter_map(|(index, source)| Some((*index, mariko_kek.as_deref()?, source))),
+            );
+        let mut inconsistent = Vec::new();
+        for (index, root, source) in kek_sources {
+            let derived = Zeroizing::new(ecb_decrypt_block(root, source)?);
+            let name = format!("master_kek_{index:02x}");
+            offer_derived(
+                &mut master_keks,
+                index,
+                &derived,
+                name,
+                &mut origins,
+                &mut inconsistent,
+            );
+        }
+
+        let mut master_keys = KeyMap(file.indexed("master_key_"));
+        record_supplied(&mut origins, "master_key", &master_keys);
+        if let Some(source) = master_key_source.as_deref() {
+            for (index, kek) in master_keks.iter() {
+                let derived = Zeroizing::new(ecb_decrypt_block(kek, source)?);
+                let name = format!("master_key_{index:02x}");
+                offer_derived(
+                    &mut master_keys,
+                    *index,
+                    &derived,
+                    name,
+                    &mut origins,
+                    &mut inconsistent,
+                );
+            }
+        }
+
+        let header_kek_source = secret(file.get::<16>("header_kek_source"));
+        let header_key_source = secret(file.get::<32>("header_key_source"));
+        let header_derivation = (
+            header_kek_source.as_deref(),
+            header_key_source.as_deref(),
+            master_keys.get(&0),
+            aes_kek_generation_source.as_deref(),
+            aes_key_generation_source.as_deref(),
+        );
+        let header_key = if let Some(key) = secret(file.get::<32>("header_key")) {
+            origins.insert("header_key".to_string(), KeyOrigin::Supplied);
+            if let (
+                Some(kek_source),
+                Some(key_source),
+                Some(master_key),
+                Some(kek_seed),
+                Some(key_seed),
+            ) = header_derivation
+            {
+                let kek = Zeroizing::new(generate_kek(kek_source, master_key, kek_seed, key_seed)?);
+                if *Zeroizing::new(ecb_decrypt(&kek, key_source)?) != *key {
+                    inconsistent.push("header_key".to_string());
+                }
+            }
+            key
+        } else {
+            let (
+                Some(kek_source),
+                Some(key_source),
+                Some(master_key),
+                Some(kek_seed),
+                Some(key_seed),
+            ) = header_derivation
+            else {
+                return Err(NszError::MissingRequiredKey {
+                    key:
+                        "header_key (or header_kek_source and header_key_source with master_key_00)"
+                            .to_string(),
+                });
+            };
+            let kek = Zeroizing::new(generate_kek(kek_source, master_key, kek_seed, key_seed)?);
+            let mut key = Zeroizing::new([0u8; 32]);
+            key.copy_from_slice(&Zeroizing::new(ecb_decrypt(&kek, key_source)?));
+            origins.insert("header_key".to_string(), KeyOrigin::Derived);
+            key
+        };
+
+        let mut missing_sources = HashMap::new();
+        let titlekek_source = secret(file.get::<16>("titlekek_source"));
+        let mut title_keks = KeyMap(file.indexed("titlekek_"));
+        record_supplied(&mut origins, "titlekek", &title_keks);
+        if let Some(source) = titlekek_source.as_deref() {
+            for (index, master_key) in master_keys.iter() {
+                let derived = Zeroizing::new(ecb_decrypt_block(master_key, source)?);
+                let name = format!("titlekek_{index:02x}");
+                offer_derived(
+                    &mut title_keks,
+                    *index,
+                    &derived,
+                    name,
+                    &mut origins,
+                    &mut inconsistent,
+                );
+            }
+        } else {
+            missing_sources.insert("titlekek", vec!["titlekek_source"]);
+        }
+
+        let mut key_area_keys: [KeyMap; 3] = Default::default();
+        for (kind, keys) in KEY_AREA_KEY_KINDS.iter().zip(key_area_keys.iter_mut()) {
+            let prefix = format!("key_area_key_{kind}");
+            *keys = KeyMap(file.indexed(&format!("{prefix}_")));
+            record_supplied(&mut origins, &prefix, keys);
+
+            let source_name = match *kind {
+                "application" => "key_area_key_application_source",
+                "ocean" => "key_area_key_ocean_source",
+                _ => "key_area_key_system_source",
+            };
+            let source = secret(file.get::<16>(source_name));
+            let (Some(source), Some(kek_seed), Some(key_seed)) = (
+                source.as_deref(),
+                aes_kek_generation_source.as_deref(),
+                aes_key_generation_source.as_deref(),
+            ) else {
+                let missing = [
+                    (source_name, source.is_none()),
+                    (
+                        "aes_kek_generation_source",
+                        aes_kek_generation_source.is_none(),
+                    ),
+                    (
+                        "aes_key_generation_source",
+                        aes_key_generation_source.is_none(),
+                    ),
+                ]
+                .into_iter()
+                .filter_map(|(name, absent)| absent.then_some(name))
+                .collect();
+                missing_sources.insert(source_name, missing);
+                continue;
+            };
+            for (index, master_key) in master_keys.iter() {
+                let derived = Zeroizing::new(generate_kek(source, master_key, kek_seed, key_seed)?);
+                let name = format!("{prefix}_{index:02x}");
+                offer_derived(
+                    keys,
+                    *index,
+                    &derived,
+                    name,
+                    &mut origins,
+                    &mut inconsistent,
+                );
+            }
+        }
+
+        Ok(Self {
+            header_key: *header_key,
+            master_keys,
+            title_keks,
+            key_area_keys,
+            missing_sources,
+            origins,
+            inconsistent,
+        })
+    }
+
+    /// Reports, for every key this set holds, whether it was supplied or derived.
+    pub fn key_origins(&self) -> &BTreeMap<String, KeyOrigin> {
+        &self.origins
+    }
+
+    /// Lists supplied keys that differ from the value derived from their sources.
+    pub fn inconsistent_keys(&self) -> &[String] {
+        &self.inconsistent
+    }
+
+    /// Returns every key held by this set with its name, for fingerprinting.
+    pub(crate) fn named_keys(&self) -> Vec<(String, &[u8])> {
+        let mut out = vec![("header_key".to_string(), self.header_key.as_slice())];
+        let families = [
+            ("master_key".to_string(), &self.master_keys),
+            ("titlekek".to_string(), &self.title_keks),
+        ]
+        .into_iter()
+        .chain(
+            KEY_AREA_KEY_KINDS
+                .iter()
+                .zip(&self.key_area_keys)
+                .map(|(kind, keys)| (format!("key_area_key_{kind}"), keys)),
+        );
+        for (prefix, keys) in families {
+            out.extend(
+                keys.iter()
+                    .map(|(index, key)| (format!("{prefix}_{index:02x}"), key.as_slice())),
+            );
+        }
+        out
+    }
+
+    /// Names the key to ask for when a derived key for `master_index` is unavailable.
+    fn missing_key(&self, master_index: u8, derived: &'static str) -> NszError {
+        let key = if !self.master_keys.contains_key(&master_index) {
+            format!("master_key_{master_index:02x}")
+        } else if let Some(sources) = self.missing_sources.get(derived) {
+            sources.join(", ")
+        } else {
+            derived.to_string()
+        };
+        NszError::MissingRequiredKey { key }
+    }
+
+    pub(crate) fn title_kek(&self, master_index: u8) -> Result<[u8; 16], NszError> {
+        self.title_keks
+            .get(&master_index)
+            .copied()
+            .ok_or_else(|| self.missing_key(master_index, "titlekek"))
+    }
+
+    pub(crate) fn key_area_key(&self, kind: u8, master_index: u8) -> Result<[u8; 16], NszError> {
+        let Some(keys) = self.key_area_keys.get(usize::from(kind)) else {
+            return Err(NszError::ContainerFormat {
+                message: format!("unknown key area key index {kind}"),
+            });
+        };
+        let source_name = match kind {
+            0 => "key_area_key_application_source",
+            1 => "key_area_key_ocean_source",
+            _ => "key_area_key_system_source",
+        };
+        keys.get(&master_index)
+            .copied()
+            .ok_or_else(|| self.missing_key(master_index, source_name))
+    }
+}
+
+impl fmt::Debug for NcaKeySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NcaKeySet")
+            .field("header_key", &Redacted)
+            .field("keys", &self.origins)
+            .field("inconsistent", &self.inconsistent)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for NcaKeySet {
+    fn drop(&mut self) {
+        self.header_key.zeroize();
+    }
+}
+
+/// Indexed keys that are wiped on drop, so neither the key set nor the intermediate keys
+/// of [`NcaKeySet::from_key_file`] outlive their use, even when derivation fails part-way.
+#[derive(Default)]
+struct KeyMap(BTreeMap<u8, [u8; 16]>);
+
+impl Deref for KeyMap {
+    type Target = BTreeMap<u8, [u8; 16]>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for KeyMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for KeyMap {
+    fn drop(&mut self) {
+        self.0.values_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+fn secret<const N: usize>(key: Option<[u8; N]>) -> Option<Zeroizing<[u8; N]>> {
+    key.map(Zeroizing::new)
+}
+
+/// Records a derived key unless it was supplied; supplied keys that disagree are noted.
+fn offer_derived(
+    keys: &mut BTreeMap<u8, [u8; 16]>,
+    index: u8,
+    derived: &[u8; 16],
+    name: String,
+    origins: &mut BTreeMap<String, KeyOrigin>,
+    inconsistent: &mut Vec<String>,
+) {
+    match keys.get(&index) {
+        Some(supplied) if supplied != derived => inconsistent.push(name),
+        Some(_) => {}
+        None => {
+            keys.insert(index, *derived);
+            origins.insert(name, KeyOrigin::Derived);
+        }
+    }
+}
+
+fn record_supplied(
+    origins: &mut BTreeMap<String, KeyOrigin>,
+    prefix: &str,
+    keys: &BTreeMap<u8, [u8; 16]>,
+) {
+    for index in keys.keys() {
+        origins.insert(format!("{prefix}_{index:02x}"), KeyOrigin::Supplied);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::keys::KeyFile;
+use crate::crypto::keyset::{KeyOrigin, NcaKeySet};
+use crate::crypto::validate::{validate_keys, KeyFingerprints, KeyValidationReport};
+use crate::error::NszError;
+
+/// Console key material loaded from a `prod.keys`/`keys.txt` file.
+///
+/// Key buffers are zeroed on drop and `Debug` output lists key names only.
+#[derive(Debug)]
+pub struct KeyStore {
+    source: Option<PathBuf>,
+    key_file: KeyFile,
+    nca_keys: NcaKeySet,
+}
+
+impl KeyStore {
+    /// Loads key material from an explicit keys file.
+    pub fn from_file(path: &Path) -> Result<Self, NszError> {
+        let content = fs::read_to_string(path)?;
+        Self::build(Some(path.to_path_buf()), &content)
+    }
+
+    /// Parses key material from `keys.txt`-style content.
+    pub fn from_keys_str(content: &str) -> Result<Self, NszError> {
+        Self::build(None, content)
+    }
+
+    /// Loads the first keys file found in [`KeyStore::default_search_paths`].
+    ///
+    /// Missing files are skipped; a keys file that exists but cannot be parsed is an error.
+    pub fn load_default() -> Result<Option<Self>, NszError> {
+        for candidate in Self::default_search_paths() {
+            match fs::read_to_string(&candidate) {
+                Ok(content) => return Self::build(Some(candidate), &content).map(Some),
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    fn build(source: Option<PathBuf>, content: &str) -> Result<Self, NszError> {
+        let parsed = KeyFile::parse(content)
+            .and_then(|key_file| Ok((NcaKeySet::from_key_file(&key_file)?, key_file)));
+        let (nca_keys, key_file) = match (parsed, source.as_deref()) {
+            (Ok(parsed), _) => parsed,
+            (Err(err), Some(path)) => return Err(annotate(err, path)),
+            (Err(err), None) => return Err(err),
+        };
+        Ok(Self {
+            source,
+            key_file,
+            nca_keys,
+        })
+    }
+
+    /// Loads `explicit` when given, otherwise searches the default locations.
+    pub fn resolve(explicit: Option<&Path>) -> Result<Option<Self>, NszError> {
+        explicit.map_or_else(Self::load_default, |path| Self::from_file(path).map(Some))
+    }
+
+    /// Returns the locations searched by [`KeyStore::load_default`], in order.
+    ///
+    /// `NSZ_KEYS_FILE` comes first, then `prod.keys`/`keys.txt` in the working
+    /// directory and in `~/.switch`.
+    pub fn default_search_paths() -> Vec<PathBuf> {
+        let mut out = Vec::new();
+        if let Ok(path) = std::env::var("NSZ_KEYS_FILE") {
+            out.push(PathBuf::from(path));
+        }
+        out.push(PathBuf::from("prod.keys"));
+        out.push(PathBuf::from("keys.txt"));
+        if let Ok(home) = std::env::var("HOME") {
+            let switch_dir = PathBuf::from(home).join(".switch");
+            out.push(switch_dir.join("prod.keys"));
+            out.push(switch_dir.join("keys.txt"));
+        }
+        out
+    }
+
+    /// Returns the file the keys were loaded from, if any.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Reports which keys were supplied by the keys file and which were derived.
+    pub fn key_origins(&self) -> &BTreeMap<String, KeyOrigin> {
+        self.nca_keys.key_origins()
+    }
+
+    /// Checks for supplied keys that contradict their sources, and compares keys against
+    /// `fingerprints` where it has an entry.
+    pub fn validate(&self, fingerprints: &KeyFingerprints) -> KeyValidationReport {
+        validate_keys(&self.key_file, &self.nca_keys, fingerprints)
+    }
+
+    /// Returns every entry of the parsed keys file.
+    pub fn key_file(&self) -> &KeyFile {
+        &self.key_file
+    }
+
+    /// Returns the key set used for NCA header and section decryption.
+    pub fn nca_keys(&self) -> &NcaKeySet {
+        &self.nca_keys
+    }
+}
+
+fn annotate(err: NszError, path: &Path) -> NszError {
+    match err {
+        NszError::ContainerFormat { message } => NszError::ContainerFormat {
+            message: format!("{}: {message}", path.display()),
+        },
+        NszError::InvalidKeyFile { line, message } => NszError::InvalidKeyFile {
+            line,
+            message: format!("{message} ({})", path.display()),
+        },
+        other => other,
+    }
+}
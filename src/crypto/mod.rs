@@ -1 +1,8 @@
+pub mod aes;
 pub mod keys;
+pub mod keyset;
+pub mod keystore;
+mod redact;
+pub mod validate;
+
+pub(crate) use redact::Redacted;
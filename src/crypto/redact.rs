@@ -0,0 +1,10 @@
+use std::fmt;
+
+/// Placeholder printed by `Debug` impls in place of key material.
+pub struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
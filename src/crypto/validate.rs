@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::crypto::aes::xts_decrypt;
+use crate::crypto::keys::{decode_hex_array, KeyFile};
+use crate::crypto::keyset::NcaKeySet;
+use crate::error::NszError;
+
+/// SHA-256 fingerprints of known-good keys, by key name.
+///
+/// Only hashes are stored, never key material.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFingerprints {
+    hashes: BTreeMap<String, [u8; 32]>,
+}
+
+impl KeyFingerprints {
+    /// Returns the fingerprints compiled into the crate, which is an empty table.
+    ///
+    /// Keys are instead checked against the data they decrypt: [`check_header_key`] for
+    /// `header_key`, and the section hash check done while planning each NCA for the
+    /// content keys. Supply a table with [`KeyFingerprints::from_file`] or
+    /// `NSZ_KEY_FINGERPRINTS` to compare keys against known hashes as well.
+    pub fn builtin() -> Self {
+        Self::default()
+    }
+
+    /// Parses `name = sha256hex` lines; `#` and `;` start comments.
+    pub fn parse(content: &str) -> Result<Self, NszError> {
+        let mut out = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+                continue;
+            }
+            let Some((name, value)) = trimmed.split_once('=') else {
+                return Err(NszError::InvalidKeyFile {
+                    line: index + 1,
+                    message: "expected `name = sha256`".to_string(),
+                });
+            };
+            let name = name.trim().to_ascii_lowercase();
+            let hash =
+                decode_hex_array::<32>(value.trim()).ok_or_else(|| NszError::InvalidKeyFile {
+                    line: index + 1,
+                    message: format!("`{name}` is not a SHA-256 hex digest"),
+                })?;
+            out.hashes.insert(name, hash);
+        }
+        Ok(out)
+    }
+
+    /// Reads a fingerprint table from `path`.
+    pub fn from_file(path: &Path) -> Result<Self, NszError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Loads the table named by `NSZ_KEY_FINGERPRINTS`, or the built-in one.
+    pub fn load_default() -> Result<Self, NszError> {
+        std::env::var_os("NSZ_KEY_FINGERPRINTS").map_or_else(
+            || Ok(Self::builtin()),
+            |path| Self::from_file(Path::new(&path)),
+        )
+    }
+
+    /// Adds or replaces the fingerprint for `name`.
+    pub fn insert(&mut self, name: &str, sha256: [u8; 32]) {
+        self.hashes.insert(name.to_ascii_lowercase(), sha256);
+    }
+
+    /// Returns the number of fingerprints.
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Returns whether the table has no fingerprints.
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    fn check(&self, name: &str, value: &[u8]) -> KeyStatus {
+        match self.hashes.get(name) {
+            Some(expected) if Sha256::digest(value).as_slice() == expected => KeyStatus::Verified,
+            Some(_) => KeyStatus::Mismatch,
+            None => KeyStatus::Unchecked,
+        }
+    }
+}
+
+/// Outcome of validating one key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStatus {
+    /// The key hash matches its known fingerprint.
+    Verified,
+    /// The key hash differs from its known fingerprint.
+    Mismatch,
+    /// The supplied key differs from the value derived from its sources.
+    Inconsistent,
+    /// No fingerprint is known and nothing contradicts the key.
+    Unchecked,
+}
+
+/// Per-key results of [`validate_keys`].
+#[derive(Debug, Clone, Default)]
+pub struct KeyValidationReport {
+    /// Status of every supplied or derived key, by name.
+    pub keys: BTreeMap<String, KeyStatus>,
+}
+
+impl KeyValidationReport {
+    /// Returns whether no key is known to be wrong.
+    pub fn is_valid(&self) -> bool {
+        self.problems().next().is_none()
+    }
+
+    /// Iterates over keys that are known to be wrong.
+    pub fn problems(&self) -> impl Iterator<Item = (&str, KeyStatus)> {
+        self.keys
+            .iter()
+            .filter(|(_, status)| matches!(status, KeyStatus::Mismatch | KeyStatus::Inconsistent))
+            .map(|(name, status)| (name.as_str(), *status))
+    }
+
+    /// Converts a report with problems into [`NszError::InvalidKeys`].
+    pub fn into_result(self) -> Result<Self, NszError> {
+        if self.is_valid() {
+            return Ok(self);
+        }
+        let details = self
+            .problems()
+            .map(|(name, status)| match status {
+                KeyStatus::Mismatch => format!("{name} does not match its known fingerprint"),
+                _ => format!("{name} differs from the value derived from its sources"),
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        Err(NszError::InvalidKeys { details })
+    }
+}
+
+/// Checks supplied keys against the values derived from their sources, and every key
+/// against `fingerprints` when the table has an entry for it.
+pub fn validate_keys(
+    key_file: &KeyFile,
+    nca_keys: &NcaKeySet,
+    fingerprints: &KeyFingerprints,
+) -> KeyValidationReport {
+    let mut report = KeyValidationReport::default();
+    let supplied = key_file.names().filter_map(|name| {
+        key_file
+            .entry(name)
+            .map(|entry| (name.to_string(), entry.value.as_slice()))
+    });
+    for (name, value) in supplied.chain(nca_keys.named_keys()) {
+        let status = if nca_keys.inconsistent_keys().contains(&name) {
+            KeyStatus::Inconsistent
+        } else {
+            fingerprints.check(&name, value)
+        };
+        report.keys.entry(name).or_insert(status);
+    }
+    report
+}
+
+/// Checks that `header_key` decrypts the header of `nca` to an `NCA3`, `NCA2` or `NCA0`
+/// magic, failing with [`NszError::InvalidKeys`] otherwise.
+pub fn check_header_key(header_key: &[u8; 32], nca: &[u8]) -> Result<(), NszError> {
+    let mut header = nca
+        .get(..0x400)
+        .ok_or_else(|| NszError::ContainerFormat {
+            message: "NCA data too short for a header key check".to_string(),
+        })?
+        .to_vec();
+    xts_decrypt(&mut header, header_key, 0x200, 0)?;
+    if matches!(&header[0x200..0x204], b"NCA3" | b"NCA2" | b"NCA0") {
+        return Ok(());
+    }
+    Err(NszError::InvalidKeys {
+        details: "header_key does not decrypt the NCA header to an NCA magic".to_string(),
+    })
+}
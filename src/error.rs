@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::report::FileReport;
+
 #[derive(Debug, Error)]
 pub enum NszError {
     #[error("io error: {0}")]
@@ -8,6 +10,10 @@ pub enum NszError {
     BaselineVersionParse { path: String },
     #[error("missing required key: {key}")]
     MissingRequiredKey { key: String },
+    #[error("invalid keys: {details}")]
+    InvalidKeys { details: String },
+    #[error("invalid keys file at line {line}: {message}")]
+    InvalidKeyFile { line: usize, message: String },
     #[error("container format error: {message}")]
     ContainerFormat { message: String },
     #[error("unsupported feature: {feature}")]
@@ -27,6 +33,89 @@ pub enum NszError {
         actual_sha256: String,
         first_diff_offset: u64,
     },
+    #[error(
+        "hash mismatch: expected sha256 {expected_sha256}..., content hashes to {actual_sha256}"
+    )]
+    HashMismatch {
+        expected_sha256: String,
+        actual_sha256: String,
+    },
     #[error("not implemented: {0}")]
     NotImplemented(&'static str),
+    #[error("operation cancelled")]
+    Cancelled,
+    #[error("not enough free space for {path}: needs {needed} bytes, {available} available")]
+    InsufficientSpace {
+        path: String,
+        needed: u64,
+        available: u64,
+    },
+    #[error("{entry}: {source}")]
+    InEntry {
+        entry: String,
+        #[source]
+        source: Box<Self>,
+    },
+    #[error("{failed} of {total} files failed")]
+    BatchFailed {
+        failed: usize,
+        total: usize,
+        files: Vec<FileReport>,
+    },
+}
+
+impl NszError {
+    /// Stable snake-case name of the error variant, for machine-readable reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            Self::BaselineVersionParse { .. } => "baseline_version_parse",
+            Self::MissingRequiredKey { .. } => "missing_required_key",
+            Self::InvalidKeys { .. } => "invalid_keys",
+            Self::InvalidKeyFile { .. } => "invalid_key_file",
+            Self::ContainerFormat { .. } => "container_format",
+            Self::UnsupportedFeature { .. } => "unsupported_feature",
+            Self::ExternalCommand { .. } => "external_command",
+            Self::ParityMismatch { .. } => "parity_mismatch",
+            Self::HashMismatch { .. } => "hash_mismatch",
+            Self::NotImplemented(_) => "not_implemented",
+            Self::Cancelled => "cancelled",
+            Self::InsufficientSpace { .. } => "insufficient_space",
+            Self::InEntry { source, .. } => source.kind(),
+            Self::BatchFailed { .. } => "batch_failed",
+        }
+    }
+
+    /// Name of the container entry the error happened in, if any.
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            Self::InEntry { entry, .. } => Some(entry),
+            _ => None,
+        }
+    }
+
+    /// The underlying error with any container entry context stripped.
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::InEntry { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// Whether the error is a cancellation, which stops a batch even with `continue_on_error`.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Cancelled)
+    }
+
+    /// Attaches container entry context; cancellations stay bare since they are not about
+    /// the entry.
+    pub(crate) fn in_entry(self, entry: &str) -> Self {
+        if self.is_cancelled() {
+            return self;
+        }
+        Self::InEntry {
+            entry: entry.to_string(),
+            source: Box::new(self),
+        }
+    }
 }
@@ -0,0 +1,197 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::NszError;
+
+/// Suffix of the hidden sibling an output is staged in until it is complete.
+const TEMP_SUFFIX: &str = ".nsz-rs-tmp";
+
+/// Returns the hidden sibling `path` is written to before being renamed into place.
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}{TEMP_SUFFIX}"))
+}
+
+/// Returns whether `path` names a staging file or folder of an unfinished write.
+pub fn is_temp_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') && name.ends_with(TEMP_SUFFIX))
+}
+
+/// Writes `data` to `path` through a synced sibling temp file, so a crash or full disk
+/// never leaves a truncated file under the final name.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), NszError> {
+    write_atomic_with(path, data.len() as u64, |writer| writer.write_all(data))
+}
+
+/// Like [`write_atomic`], streaming the `size` bytes of the output through `write`.
+pub fn write_atomic_with(
+    path: &Path,
+    size: u64,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> Result<(), NszError> {
+    ensure_free_space(path, size)?;
+    let temp = temp_path_for(path);
+    let result = write_synced(&temp, write).and_then(|()| fs::rename(&temp, path));
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(err.into());
+    }
+    sync_parent(path);
+    Ok(())
+}
+
+/// Writes `parts` as the numbered files `00`, `01`, ... of a split folder at `path`.
+///
+/// The parts are staged in a hidden sibling folder that replaces `path` once all of them
+/// are synced.
+pub fn write_folder_atomic<'a>(
+    path: &Path,
+    parts: impl Iterator<Item = &'a [u8]>,
+    size: u64,
+) -> Result<(), NszError> {
+    ensure_free_space(path, size)?;
+    let temp = temp_path_for(path);
+    remove_temp(&temp)?;
+    let result = (|| {
+        fs::create_dir_all(&temp)?;
+        for (index, part) in parts.enumerate() {
+            write_synced(&temp.join(format!("{index:02}")), |writer| {
+                writer.write_all(part)
+            })?;
+        }
+        sync_parent(&temp.join("00"));
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_dir_all(&temp);
+        return Err(err.into());
+    }
+    sync_parent(path);
+    Ok(())
+}
+
+/// Writes each `(path, data)` pair through its own synced temp file, renaming them into
+/// place only once every part is on disk.
+pub fn write_parts_atomic<'a>(
+    parts: impl Iterator<Item = (PathBuf, &'a [u8])>,
+    size: u64,
+) -> Result<(), NszError> {
+    let mut staged = Vec::new();
+    let result = (|| {
+        for (path, data) in parts {
+            if staged.is_empty() {
+                ensure_free_space(&path, size)?;
+            }
+            let temp = temp_path_for(&path);
+            staged.push((temp.clone(), path));
+            write_synced(&temp, |writer| writer.write_all(data))?;
+        }
+        for (temp, path) in &staged {
+            fs::rename(temp, path)?;
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        for (temp, _) in &staged {
+            let _ = fs::remove_file(temp);
+        }
+        return Err(err);
+    }
+    if let Some((_, path)) = staged.first() {
+        sync_parent(path);
+    }
+    Ok(())
+}
+
+/// Removes the staging files and folders interrupted runs left in `dir`, returning what
+/// was removed.
+///
+/// Runs call this on their output directories before writing, so two runs must not share
+/// an output directory at the same time.
+pub fn clean_stale_temps(dir: &Path) -> Result<Vec<PathBuf>, NszError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut removed = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if is_temp_path(&path) {
+            remove_temp(&path)?;
+            removed.push(path);
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
+
+/// Fails with [`NszError::InsufficientSpace`] when the volume `path` is written on has
+/// less room than `size` bytes, counting the space an existing file at `path` frees.
+///
+/// Volumes whose free space cannot be queried are assumed to have room.
+pub fn ensure_free_space(path: &Path, size: u64) -> Result<(), NszError> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Ok(available) = fs4::available_space(dir) else {
+        return Ok(());
+    };
+    let replaced = fs::metadata(path).map_or(0, |metadata| metadata.len());
+    let needed = size.saturating_sub(replaced);
+    if needed > available {
+        return Err(NszError::InsufficientSpace {
+            path: path.display().to_string(),
+            needed,
+            available,
+        });
+    }
+    Ok(())
+}
+
+fn write_synced(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .sync_all()
+}
+
+fn remove_temp(path: &Path) -> Result<(), NszError> {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Syncs the directory holding `path` so a rename survives a crash; only Unix can open
+/// directories for this, and failures are ignored since the data itself is already synced.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
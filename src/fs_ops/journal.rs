@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::NszError;
+
+use super::split_files::input_parts;
+
+/// Append-only record of the inputs a batch finished, so re-running an interrupted batch
+/// skips them.
+///
+/// Each line holds the operation, the input's logical size and modification time, the
+/// output's size and modification time, the SHA-256 of the output written, and the input
+/// and output paths, separated by tabs. An input counts as done only while its size and
+/// modification time are unchanged and its output is intact: an output whose size and
+/// modification time still match is trusted as is, and any other output must still hash to
+/// the recorded value. Anything else, including a line torn by a crash, is redone. Lines
+/// written before outputs were stamped are checked by hash alone. Paths that are not UTF-8
+/// or contain tabs or newlines are not recorded.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    operation: &'static str,
+    done: HashMap<PathBuf, Record>,
+    /// Whether the file ends in a line torn by a crash, which the next record must not extend.
+    torn: bool,
+}
+
+/// Logical size and modification time (nanoseconds since the Unix epoch) of a file.
+type Stamp = (u64, u128);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    input_stamp: Stamp,
+    output_stamp: Option<Stamp>,
+    output_sha256: String,
+    output: PathBuf,
+}
+
+impl Journal {
+    /// Loads the journal at `path` for `operation`, such as `"compress"`; a missing file
+    /// is an empty journal.
+    pub fn open(path: &Path, operation: &'static str) -> Result<Self, NszError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let done = content
+            .lines()
+            .filter_map(parse_line)
+            .filter(|(op, ..)| *op == operation)
+            .map(|(_, input, record)| (input, record))
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            operation,
+            done,
+            torn: !content.is_empty() && !content.ends_with('\n'),
+        })
+    }
+
+    /// Returns the output of `input` when an earlier run finished it and the output is
+    /// still intact.
+    ///
+    /// The output is only re-hashed when its size or modification time changed since it
+    /// was recorded, and then part by part rather than read into memory.
+    pub fn completed(&self, input: &Path) -> Option<&Path> {
+        let record = self.done.get(input)?;
+        if stamp(input).ok()? != record.input_stamp {
+            return None;
+        }
+        let output = record.output.as_path();
+        if record.output_stamp.is_some() && stamp(output).ok() == record.output_stamp {
+            return Some(output);
+        }
+        (output_sha256(output).ok()? == record.output_sha256).then_some(output)
+    }
+
+    /// Records `input` as finished with `data` written to `output`, syncing the line to disk.
+    pub fn record(&mut self, input: &Path, output: &Path, data: &[u8]) -> Result<(), NszError> {
+        let (Some(input_str), Some(output_str)) = (recordable(input), recordable(output)) else {
+            return Ok(());
+        };
+        let (input_size, input_mtime) = stamp(input)?;
+        let (output_size, output_mtime) = stamp(output)?;
+        let record = Record {
+            input_stamp: (input_size, input_mtime),
+            output_stamp: Some((output_size, output_mtime)),
+            output_sha256: format!("{:x}", Sha256::digest(data)),
+            output: output.to_path_buf(),
+        };
+        let line = format!(
+            "{}{}\t{input_size}\t{input_mtime}\t{output_size}\t{output_mtime}\t{}\t{input_str}\t{output_str}\n",
+            if self.torn { "\n" } else { "" },
+            self.operation,
+            record.output_sha256
+        );
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        self.torn = false;
+        self.done.insert(input.to_path_buf(), record);
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Option<(&str, PathBuf, Record)> {
+    let fields = line.split('\t').collect::<Vec<_>>();
+    let (operation, input_stamp, output_stamp, output_sha256, input, output) = match fields
+        .as_slice()
+    {
+        [operation, input_size, input_mtime, output_sha256, input, output] => (
+            *operation,
+            parse_stamp(input_size, input_mtime)?,
+            None,
+            *output_sha256,
+            *input,
+            *output,
+        ),
+        [operation, input_size, input_mtime, output_size, output_mtime, output_sha256, input, output] => {
+            (
+                *operation,
+                parse_stamp(input_size, input_mtime)?,
+                Some(parse_stamp(output_size, output_mtime)?),
+                *output_sha256,
+                *input,
+                *output,
+            )
+        }
+        _ => return None,
+    };
+    if output_sha256.len() != 64 {
+        return None;
+    }
+    Some((
+        operation,
+        PathBuf::from(input),
+        Record {
+            input_stamp,
+            output_stamp,
+            output_sha256: output_sha256.to_string(),
+            output: PathBuf::from(output),
+        },
+    ))
+}
+
+fn parse_stamp(size: &str, mtime: &str) -> Option<Stamp> {
+    Some((size.parse().ok()?, mtime.parse().ok()?))
+}
+
+fn recordable(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| !path.contains(['\t', '\n', '\r']))
+}
+
+/// Stamps a file, or every part of a split one with the latest part's modification time.
+fn stamp(path: &Path) -> Result<Stamp, NszError> {
+    let mut size = 0;
+    let mut mtime = 0;
+    for part in input_parts(path)? {
+        let metadata = fs::metadata(part)?;
+        size += metadata.len();
+        mtime = mtime.max(
+            metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos()),
+        );
+    }
+    Ok((size, mtime))
+}
+
+/// Hashes a file, or the parts of a split one in order, without holding it in memory.
+fn output_sha256(path: &Path) -> Result<String, NszError> {
+    let mut hasher = Sha256::new();
+    for part in input_parts(path)? {
+        io::copy(&mut File::open(part)?, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
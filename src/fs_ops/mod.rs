@@ -1,2 +1,6 @@
+pub mod atomic_write;
 pub mod existing_checks;
+pub mod journal;
 pub mod path_tools;
+pub mod scratch;
+pub mod split_files;
@@ -0,0 +1,206 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::NszError;
+
+use super::atomic_write::{write_atomic, write_folder_atomic, write_parts_atomic};
+
+/// Part size for split NSP/NSZ/XCZ folders, as FAT32 installers expect.
+pub const FOLDER_PART_SIZE: u64 = 0xFFFF_0000;
+
+/// Part size for `.xc0`, `.xc1`, ... split XCIs.
+pub const XCI_PART_SIZE: u64 = 0xFFFF_8000;
+
+/// Returns the path an operation should treat `path` as.
+///
+/// `game.xc0` stands for `game.xci`; split folders already carry the container extension.
+pub fn logical_path(path: &Path) -> PathBuf {
+    if is_xci_first_part(path) {
+        return path.with_extension("xci");
+    }
+    path.to_path_buf()
+}
+
+/// Returns whether `path` is a split folder or the `.xc0` part of a split XCI.
+pub fn is_split_input(path: &Path) -> bool {
+    path.is_dir() || is_xci_first_part(path)
+}
+
+/// Reads a whole input, joining split parts into one logical file.
+///
+/// Directories are read as split folders holding numbered parts `00`, `01`, ...;
+/// `.xc0` files are joined with the `.xc1`, `.xc2`, ... parts next to them.
+pub fn read_input(path: &Path) -> Result<Vec<u8>, NszError> {
+    let parts = input_parts(path)?;
+    if let [single] = parts.as_slice() {
+        return Ok(fs::read(single)?);
+    }
+    let mut out = Vec::new();
+    for part in parts {
+        out.extend_from_slice(&fs::read(part)?);
+    }
+    Ok(out)
+}
+
+/// Returns the files holding the logical input at `path` in order: the numbered parts of
+/// a split folder or split XCI, and `path` itself otherwise.
+pub(crate) fn input_parts(path: &Path) -> Result<Vec<PathBuf>, NszError> {
+    if path.is_dir() {
+        return folder_parts(path);
+    }
+    if is_xci_first_part(path) {
+        return xci_parts(path);
+    }
+    Ok(vec![path.to_path_buf()])
+}
+
+/// Returns the size of the logical file at `path`, summing every part of split inputs.
+///
+/// Other directories, such as extraction outputs, count every file below them.
+pub fn logical_len(path: &Path) -> Result<u64, NszError> {
+    if path.is_dir() {
+        let mut total = 0;
+        for entry in fs::read_dir(path)? {
+            total += logical_len(&entry?.path())?;
+        }
+        return Ok(total);
+    }
+    if is_xci_first_part(path) {
+        let mut total = fs::metadata(path)?.len();
+        for index in 1.. {
+            match fs::metadata(path.with_extension(format!("xc{index}"))) {
+                Ok(metadata) => total += metadata.len(),
+                Err(err) if err.kind() == ErrorKind::NotFound => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        return Ok(total);
+    }
+    Ok(fs::metadata(path)?.len())
+}
+
+/// Writes `data` to `path`, or as numbered parts when `split` is set.
+///
+/// Outputs are staged in hidden temp files and renamed into place once synced.
+///
+/// Split `.xci` outputs become `.xc0`, `.xc1`, ... files; every other container becomes a
+/// folder named like the file holding parts `00`, `01`, .... FAT32 installers only treat the
+/// folder as one file once its archive bit is set (`attrib +A` on Windows).
+/// Returns the path written, which is the `.xc0` part or the folder for split outputs.
+pub fn write_output(path: &Path, data: &[u8], split: bool) -> Result<PathBuf, NszError> {
+    if !split {
+        write_atomic(path, data)?;
+        return Ok(path.to_path_buf());
+    }
+    let is_xci = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xci"));
+    if is_xci {
+        write_xci_parts(path, data, XCI_PART_SIZE)
+    } else {
+        write_folder_parts(path, data, FOLDER_PART_SIZE)
+    }
+}
+
+/// Writes `data` as a split folder at `path` with parts of at most `part_size` bytes.
+pub fn write_folder_parts(path: &Path, data: &[u8], part_size: u64) -> Result<PathBuf, NszError> {
+    write_folder_atomic(path, parts(data, part_size)?, data.len() as u64)?;
+    Ok(path.to_path_buf())
+}
+
+/// Writes `data` as `.xc0`, `.xc1`, ... files next to `path` with parts of at most
+/// `part_size` bytes, returning the `.xc0` path.
+pub fn write_xci_parts(path: &Path, data: &[u8], part_size: u64) -> Result<PathBuf, NszError> {
+    let parts = parts(data, part_size)?
+        .enumerate()
+        .map(|(index, part)| (path.with_extension(format!("xc{index}")), part));
+    write_parts_atomic(parts, data.len() as u64)?;
+    Ok(path.with_extension("xc0"))
+}
+
+/// Removes an output written by [`write_output`], or an extraction folder; missing paths
+/// are ignored.
+pub fn remove_output(path: &Path) -> Result<(), NszError> {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if is_xci_first_part(path) {
+        for index in 1.. {
+            match fs::remove_file(path.with_extension(format!("xc{index}"))) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => break,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        fs::remove_file(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+fn parts(data: &[u8], part_size: u64) -> Result<impl Iterator<Item = &[u8]>, NszError> {
+    let part_size = usize::try_from(part_size)
+        .ok()
+        .filter(|size| *size > 0)
+        .ok_or_else(|| NszError::ContainerFormat {
+            message: format!("invalid split part size {part_size:#x}"),
+        })?;
+    // An empty output still gets its first part.
+    let count = data.len().div_ceil(part_size).max(1);
+    Ok((0..count).map(move |index| {
+        let start = (index * part_size).min(data.len());
+        &data[start..data.len().min(start + part_size)]
+    }))
+}
+
+fn is_xci_first_part(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xc0"))
+}
+
+fn folder_parts(path: &Path) -> Result<Vec<PathBuf>, NszError> {
+    let mut indices = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let name = entry?.file_name();
+        let Some(index) = name
+            .to_str()
+            .filter(|name| name.len() == 2 && name.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|name| name.parse::<usize>().ok())
+        else {
+            continue;
+        };
+        indices.push(index);
+    }
+    indices.sort_unstable();
+    if indices.is_empty() || indices.iter().enumerate().any(|(pos, index)| pos != *index) {
+        return Err(NszError::ContainerFormat {
+            message: format!(
+                "{} is not a split folder with parts 00, 01, ...",
+                path.display()
+            ),
+        });
+    }
+    Ok(indices
+        .into_iter()
+        .map(|index| path.join(format!("{index:02}")))
+        .collect())
+}
+
+fn xci_parts(first: &Path) -> Result<Vec<PathBuf>, NszError> {
+    let mut out = vec![first.to_path_buf()];
+    for index in 1.. {
+        let part = first.with_extension(format!("xc{index}"));
+        match fs::metadata(&part) {
+            Ok(_) => out.push(part),
+            Err(err) if err.kind() == ErrorKind::NotFound => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(out)
+}
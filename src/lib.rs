@@ -17,6 +17,7 @@
     clippy::useless_conversion
 )]
 
+pub mod cancel;
 pub mod config;
 pub mod container;
 pub mod crypto;
@@ -25,15 +26,25 @@ pub mod fs_ops;
 pub mod ncz;
 pub mod ops;
 pub mod parity;
+pub mod progress;
 #[cfg(feature = "python")]
 mod python;
+pub mod report;
 
+pub use cancel::CancellationToken;
 pub use config::{
-    CompressRequest, CreateRequest, DecompressRequest, ExtractRequest, TitleKeysRequest,
-    UndupeRequest, VerifyRequest,
+    CompressRequest, ConvertRequest, CreateRequest, DecompressRequest, ExtractRequest,
+    MergeRequest, SplitRequest, TitleKeysRequest, TrimRequest, UndupeRequest, VerifyRequest,
 };
+pub use container::nca::KeyOrigin;
+pub use crypto::keystore::KeyStore;
+pub use crypto::validate::{KeyFingerprints, KeyStatus, KeyValidationReport};
 pub use error::NszError;
 pub use ops::{OperationReport, VerifyReport};
+pub use progress::{Progress, ProgressEvent, ProgressObserver};
+pub use report::{
+    EntryReport, EntryStatus, ErrorDetails, FileReport, FileStatus, VerifySummary, VerifyVerdict,
+};
 
 /// Compresses input files according to [`CompressRequest`].
 ///
@@ -79,6 +90,117 @@ pub fn decompress(request: &DecompressRequest) -> Result<OperationReport, NszErr
     ops::decompress::run(request)
 }
 
+/// Converts gamecard images to installable packages according to [`ConvertRequest`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use nsz_rs::{convert, ConvertRequest, NszError};
+///
+/// fn run() -> Result<(), NszError> {
+///     let request = ConvertRequest {
+///         files: vec![PathBuf::from("/games/game.xci")],
+///         output_dir: Some(PathBuf::from("/tmp/out")),
+///         ..Default::default()
+///     };
+///     let _report = convert(&request)?;
+///     Ok(())
+/// }
+/// ```
+pub fn convert(request: &ConvertRequest) -> Result<OperationReport, NszError> {
+    ops::convert::run(request)
+}
+
+/// Merges base, update and DLC packages into one container according to [`MergeRequest`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use nsz_rs::{merge, MergeRequest, NszError};
+///
+/// fn run() -> Result<(), NszError> {
+///     let request = MergeRequest {
+///         files: vec![
+///             PathBuf::from("/games/base.nsz"),
+///             PathBuf::from("/games/update.nsz"),
+///         ],
+///         output_file: Some(PathBuf::from("/tmp/out/complete.nsz")),
+///         ..Default::default()
+///     };
+///     let _report = merge(&request)?;
+///     Ok(())
+/// }
+/// ```
+pub fn merge(request: &MergeRequest) -> Result<OperationReport, NszError> {
+    ops::merge::run(request)
+}
+
+/// Splits multi-title containers into one package per title according to [`SplitRequest`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use nsz_rs::{split, SplitRequest, NszError};
+///
+/// fn run() -> Result<(), NszError> {
+///     let request = SplitRequest {
+///         files: vec![PathBuf::from("/games/complete.nsz")],
+///         output_dir: Some(PathBuf::from("/tmp/out")),
+///         ..Default::default()
+///     };
+///     let _report = split(&request)?;
+///     Ok(())
+/// }
+/// ```
+pub fn split(request: &SplitRequest) -> Result<OperationReport, NszError> {
+    ops::split::run(request)
+}
+
+/// Trims `.xci` images to their used data size according to [`TrimRequest`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use nsz_rs::{trim, TrimRequest, NszError};
+///
+/// fn run() -> Result<(), NszError> {
+///     let request = TrimRequest {
+///         files: vec![PathBuf::from("/games/game.xci")],
+///         output_dir: Some(PathBuf::from("/tmp/out")),
+///     };
+///     let _report = trim(&request)?;
+///     Ok(())
+/// }
+/// ```
+pub fn trim(request: &TrimRequest) -> Result<OperationReport, NszError> {
+    ops::trim::run(request)
+}
+
+/// Restores trimmed `.xci` images to their full card size according to [`TrimRequest`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use nsz_rs::{untrim, TrimRequest, NszError};
+///
+/// fn run() -> Result<(), NszError> {
+///     let request = TrimRequest {
+///         files: vec![PathBuf::from("/games/game.xci")],
+///         output_dir: Some(PathBuf::from("/tmp/out")),
+///     };
+///     let _report = untrim(&request)?;
+///     Ok(())
+/// }
+/// ```
+pub fn untrim(request: &TrimRequest) -> Result<OperationReport, NszError> {
+    ops::trim::run_untrim(request)
+}
+
 /// Verifies content integrity according to [`VerifyRequest`].
 ///
 /// # Examples
@@ -1,17 +1,20 @@
 use std::io::Write;
+use std::time::{Duration, Instant};
 
 use aes::Aes128;
 use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use zeroize::Zeroize;
 use zstd::stream::write::Encoder;
 
 use crate::container::nca::{NcaCompressionPlan, NcaEncryptionSection};
 use crate::error::NszError;
+use crate::ncz::NczCryptoMode;
+use crate::progress::EntryProgress;
 
 const UNCOMPRESSABLE_HEADER_SIZE: usize = 0x4000;
 const CHUNK_SIZE: usize = 0x0100_0000;
 type AesCtr = ctr::Ctr128BE<Aes128>;
 
-#[derive(Debug, Clone, Copy)]
 struct PayloadPart {
     offset: u64,
     size: u64,
@@ -21,6 +24,12 @@ struct PayloadPart {
     encrypted: bool,
 }
 
+impl Drop for PayloadPart {
+    fn drop(&mut self) {
+        self.crypto_key.zeroize();
+    }
+}
+
 /// Compresses an NCA into NCZ using default non-planned behavior.
 pub fn compress_nca_to_ncz_vec(data: &[u8], level: i32) -> Result<Vec<u8>, NszError> {
     compress_nca_to_ncz_vec_with_plan(data, level, false, 1, None)
@@ -33,6 +42,25 @@ pub fn compress_nca_to_ncz_vec_with_plan(
     long_distance_mode: bool,
     threads: i32,
     plan: Option<&NcaCompressionPlan>,
+) -> Result<Vec<u8>, NszError> {
+    compress_solid_with_progress(
+        data,
+        level,
+        long_distance_mode,
+        threads,
+        plan,
+        &mut EntryProgress::disabled(),
+    )
+}
+
+/// Solid NCZ compression reporting payload bytes to `progress`.
+pub(crate) fn compress_solid_with_progress(
+    data: &[u8],
+    level: i32,
+    long_distance_mode: bool,
+    threads: i32,
+    plan: Option<&NcaCompressionPlan>,
+    progress: &mut EntryProgress<'_>,
 ) -> Result<Vec<u8>, NszError> {
     if data.len() < UNCOMPRESSABLE_HEADER_SIZE {
         return Err(NszError::ContainerFormat {
@@ -41,11 +69,13 @@ pub fn compress_nca_to_ncz_vec_with_plan(
     }
 
     if let Some(plan) = plan {
-        return compress_with_plan(data, level, long_distance_mode, threads, plan);
+        return compress_with_plan(data, level, long_distance_mode, threads, plan, progress);
     }
 
     let payload_size = (data.len() - UNCOMPRESSABLE_HEADER_SIZE) as u64;
+    progress.set_total(payload_size);
     let compressed = zstd::stream::encode_all(&data[UNCOMPRESSABLE_HEADER_SIZE..], level)?;
+    progress.advance(payload_size)?;
 
     let mut out = Vec::with_capacity(UNCOMPRESSABLE_HEADER_SIZE + 16 + 64 + compressed.len());
     out.extend_from_slice(&data[..UNCOMPRESSABLE_HEADER_SIZE]);
@@ -68,6 +98,25 @@ pub fn compress_nca_to_ncz_block_vec_with_plan(
     long_distance_mode: bool,
     block_size_exponent: u8,
     plan: Option<&NcaCompressionPlan>,
+) -> Result<Vec<u8>, NszError> {
+    compress_block_with_progress(
+        data,
+        level,
+        long_distance_mode,
+        block_size_exponent,
+        plan,
+        &mut EntryProgress::disabled(),
+    )
+}
+
+/// Block NCZ compression reporting payload bytes to `progress`.
+pub(crate) fn compress_block_with_progress(
+    data: &[u8],
+    level: i32,
+    long_distance_mode: bool,
+    block_size_exponent: u8,
+    plan: Option<&NcaCompressionPlan>,
+    progress: &mut EntryProgress<'_>,
 ) -> Result<Vec<u8>, NszError> {
     if data.len() < UNCOMPRESSABLE_HEADER_SIZE {
         return Err(NszError::ContainerFormat {
@@ -83,6 +132,7 @@ pub fn compress_nca_to_ncz_block_vec_with_plan(
             block_size_exponent,
             &plan.sections,
             &build_parts(plan),
+            progress,
         );
     }
 
@@ -108,6 +158,7 @@ pub fn compress_nca_to_ncz_block_vec_with_plan(
         block_size_exponent,
         &[section],
         &[part],
+        progress,
     )
 }
 
@@ -117,6 +168,7 @@ fn compress_with_plan(
     long_distance_mode: bool,
     threads: i32,
     plan: &NcaCompressionPlan,
+    progress: &mut EntryProgress<'_>,
 ) -> Result<Vec<u8>, NszError> {
     let profile = std::env::var("NSZ_PROFILE_COMPRESS").ok().as_deref() == Some("1");
     let started = std::time::Instant::now();
@@ -134,6 +186,7 @@ fn compress_with_plan(
     }
 
     let parts = build_parts(plan);
+    progress.set_total(parts.iter().map(|part| part.size).sum());
 
     let mut encoder = Encoder::new(output, level)?;
     encoder.long_distance_matching(long_distance_mode)?;
@@ -146,7 +199,8 @@ fn compress_with_plan(
     let mut aes_elapsed = std::time::Duration::default();
     let mut write_elapsed = std::time::Duration::default();
     for part in &parts {
-        let mut cipher = (part.encrypted && matches!(part.crypto_type, 3 | 4)).then(|| {
+        let mode = NczCryptoMode::from_crypto_type(part.crypto_type)?;
+        let mut cipher = (part.encrypted && mode == NczCryptoMode::AesCtr).then(|| {
             init_aes_ctr(
                 &part.crypto_key,
                 &part.crypto_counter,
@@ -180,6 +234,7 @@ fn compress_with_plan(
             }
             payload_bytes = payload_bytes.saturating_add(to_read as u64);
             processed = processed.saturating_add(to_read as u64);
+            progress.advance(to_read as u64)?;
         }
     }
     let finish_started = std::time::Instant::now();
@@ -198,6 +253,137 @@ fn compress_with_plan(
     Ok(result)
 }
 
+/// NCZ size and compression time extrapolated from a sample of an NCA's payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct NczEstimate {
+    pub size: u64,
+    pub duration: Duration,
+}
+
+/// Estimates the NCZ an NCA compresses to by compressing `sample_chunks` evenly spaced
+/// 16 MiB chunks of its payload, decrypted as planned, with the encoder setup of the
+/// solid (`block_size_exponent` unset) or block writer.
+///
+/// Chunks are compressed independently, so solid NCZs tend to come out slightly smaller
+/// than estimated.
+pub(crate) fn estimate_ncz(
+    data: &[u8],
+    level: i32,
+    long_distance_mode: bool,
+    threads: i32,
+    block_size_exponent: Option<u8>,
+    plan: Option<&NcaCompressionPlan>,
+    sample_chunks: usize,
+) -> Result<NczEstimate, NszError> {
+    if data.len() < UNCOMPRESSABLE_HEADER_SIZE {
+        return Err(NszError::ContainerFormat {
+            message: "NCA data too short for NCZ conversion".to_string(),
+        });
+    }
+    if block_size_exponent.is_some_and(|exponent| !(14..=32).contains(&exponent)) {
+        return Err(NszError::ContainerFormat {
+            message: "NCZBLOCK block size exponent out of range".to_string(),
+        });
+    }
+    let (parts, section_count) = plan.map_or_else(
+        || {
+            let part = PayloadPart {
+                offset: UNCOMPRESSABLE_HEADER_SIZE as u64,
+                size: (data.len() - UNCOMPRESSABLE_HEADER_SIZE) as u64,
+                crypto_type: 0,
+                crypto_key: [0u8; 16],
+                crypto_counter: [0u8; 16],
+                encrypted: false,
+            };
+            (vec![part], 1)
+        },
+        |plan| (build_parts(plan), plan.sections.len()),
+    );
+    let mut header_size = (UNCOMPRESSABLE_HEADER_SIZE + 16 + 0x40 * section_count) as u64;
+    let payload_size: u64 = parts.iter().map(|part| part.size).sum();
+    if let Some(exponent) = block_size_exponent {
+        header_size += 24 + 4 * payload_size.div_ceil(1u64 << exponent);
+    }
+
+    // The chunks the writers feed the encoder, as (part, offset into the part, length).
+    let chunks: Vec<(&PayloadPart, u64, usize)> = parts
+        .iter()
+        .flat_map(|part| {
+            (0..part.size).step_by(CHUNK_SIZE).map(move |processed| {
+                let len = (part.size - processed).min(CHUNK_SIZE as u64) as usize;
+                (part, processed, len)
+            })
+        })
+        .collect();
+    let samples = sample_chunks.clamp(1, chunks.len().max(1));
+    let started = Instant::now();
+    let mut sampled = 0u64;
+    let mut compressed = 0u64;
+    let mut scratch = Vec::with_capacity(CHUNK_SIZE);
+    for index in 0..samples.min(chunks.len()) {
+        let (part, processed, len) = chunks[index * chunks.len() / samples];
+        let start = part.offset.saturating_add(processed) as usize;
+        let chunk = data.get(start..start.saturating_add(len)).ok_or_else(|| {
+            NszError::ContainerFormat {
+                message: "NCZ compression part exceeds source bounds".to_string(),
+            }
+        })?;
+        scratch.clear();
+        scratch.extend_from_slice(chunk);
+        if part.encrypted
+            && NczCryptoMode::from_crypto_type(part.crypto_type)? == NczCryptoMode::AesCtr
+        {
+            init_aes_ctr(
+                &part.crypto_key,
+                &part.crypto_counter,
+                u128::from(part.offset + processed),
+            )
+            .apply_keystream(&mut scratch);
+        }
+        let size = if let Some(exponent) = block_size_exponent {
+            let block_size = 1usize << exponent;
+            let mut total = 0;
+            for block in scratch.chunks(block_size) {
+                total += encode_block_payload(block, block_size, level, long_distance_mode)?.len();
+            }
+            total
+        } else {
+            let mut encoder = Encoder::new(Vec::new(), level)?;
+            encoder.long_distance_matching(long_distance_mode)?;
+            if threads > 1 {
+                encoder.multithread(threads as u32)?;
+            }
+            encoder.write_all(&scratch)?;
+            encoder.finish()?.len()
+        };
+        sampled += len as u64;
+        compressed += size as u64;
+    }
+
+    let elapsed = started.elapsed();
+    Ok(NczEstimate {
+        size: header_size + scale(payload_size, compressed, sampled),
+        duration: elapsed.mul_f64(ratio(payload_size, sampled)),
+    })
+}
+
+/// `value * numerator / denominator`, rounded down; an empty sample scales to nothing.
+fn scale(value: u64, numerator: u64, denominator: u64) -> u64 {
+    if denominator == 0 {
+        return 0;
+    }
+    (u128::from(value) * u128::from(numerator) / u128::from(denominator)) as u64
+}
+
+// Sizes beyond 2^52 bytes lose precision, which does not matter for a time estimate.
+#[allow(clippy::cast_precision_loss)]
+fn ratio(total: u64, sampled: u64) -> f64 {
+    if sampled == 0 {
+        return 0.0;
+    }
+    total as f64 / sampled as f64
+}
+
 fn build_parts(plan: &NcaCompressionPlan) -> Vec<PayloadPart> {
     let mut parts = Vec::with_capacity(plan.sections.len() + 1);
     if plan.offset_first_section > UNCOMPRESSABLE_HEADER_SIZE as u64 {
@@ -243,6 +429,7 @@ fn compress_block_with_sections(
     block_size_exponent: u8,
     sections: &[NcaEncryptionSection],
     parts: &[PayloadPart],
+    progress: &mut EntryProgress<'_>,
 ) -> Result<Vec<u8>, NszError> {
     if !(14..=32).contains(&block_size_exponent) {
         return Err(NszError::ContainerFormat {
@@ -267,13 +454,15 @@ fn compress_block_with_sections(
     let decompressed_size = parts
         .iter()
         .fold(0u64, |acc, part| acc.saturating_add(part.size));
+    progress.set_total(decompressed_size);
     let mut block_payloads = Vec::new();
     let mut block_sizes = Vec::new();
     let mut pending = Vec::with_capacity(block_size);
     let mut scratch = Vec::with_capacity(CHUNK_SIZE);
 
     for part in parts {
-        let mut cipher = (part.encrypted && matches!(part.crypto_type, 3 | 4)).then(|| {
+        let mode = NczCryptoMode::from_crypto_type(part.crypto_type)?;
+        let mut cipher = (part.encrypted && mode == NczCryptoMode::AesCtr).then(|| {
             init_aes_ctr(
                 &part.crypto_key,
                 &part.crypto_counter,
@@ -317,6 +506,7 @@ fn compress_block_with_sections(
             }
 
             processed = processed.saturating_add(to_read as u64);
+            progress.advance(to_read as u64)?;
         }
     }
 
@@ -1,10 +1,18 @@
+use std::fmt;
+use std::io::Read;
+
+use crate::crypto::Redacted;
 use crate::error::NszError;
+use crate::ncz::NczCryptoMode;
+use crate::progress::EntryProgress;
 use aes::Aes128;
 use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use zeroize::Zeroize;
 
 const UNCOMPRESSABLE_HEADER_SIZE: usize = 0x4000;
+const READ_CHUNK_SIZE: usize = 0x0010_0000;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct NczSection {
     /// Section offset in the decompressed NCA payload.
     pub offset: u64,
@@ -18,6 +26,24 @@ pub struct NczSection {
     pub crypto_counter: [u8; 16],
 }
 
+impl fmt::Debug for NczSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NczSection")
+            .field("offset", &self.offset)
+            .field("size", &self.size)
+            .field("crypto_type", &self.crypto_type)
+            .field("crypto_key", &Redacted)
+            .field("crypto_counter", &self.crypto_counter)
+            .finish()
+    }
+}
+
+impl Drop for NczSection {
+    fn drop(&mut self) {
+        self.crypto_key.zeroize();
+    }
+}
+
 /// Returns the decompressed NCA size encoded by an NCZ payload.
 pub fn decompressed_nca_size_from_bytes(data: &[u8]) -> Result<u64, NszError> {
     let (sections, _) = parse_sections_with_end(data)?;
@@ -31,6 +57,14 @@ pub fn decompressed_nca_size_from_bytes(data: &[u8]) -> Result<u64, NszError> {
 
 /// Decompresses an NCZ image back into raw NCA bytes.
 pub fn decompress_ncz_to_vec(data: &[u8]) -> Result<Vec<u8>, NszError> {
+    decompress_ncz_with_progress(data, &mut EntryProgress::disabled())
+}
+
+/// NCZ decompression reporting decoded payload bytes to `progress`.
+pub(crate) fn decompress_ncz_with_progress(
+    data: &[u8],
+    progress: &mut EntryProgress<'_>,
+) -> Result<Vec<u8>, NszError> {
     let (sections, stream_offset) = parse_sections_with_end(data)?;
 
     if data.len() < stream_offset {
@@ -39,10 +73,11 @@ pub fn decompress_ncz_to_vec(data: &[u8]) -> Result<Vec<u8>, NszError> {
         });
     }
 
-    let decompressed = decode_ncz_stream(data, stream_offset)?;
-
     let payload_size = payload_size_from_sections(&sections)?;
     let leading_gap = leading_gap_from_sections(&sections)?;
+    progress.set_total(payload_size.saturating_add(leading_gap));
+    let decompressed = decode_ncz_stream(data, stream_offset, progress)?;
+
     let output_capacity_u64 = (UNCOMPRESSABLE_HEADER_SIZE as u64)
         .checked_add(payload_size)
         .and_then(|value| value.checked_add(leading_gap))
@@ -81,7 +116,7 @@ pub fn decompress_ncz_to_vec(data: &[u8]) -> Result<Vec<u8>, NszError> {
         }
         let write_start = output.len();
         output.extend_from_slice(&decompressed[read_cursor..end]);
-        if matches!(section.crypto_type, 3 | 4) {
+        if NczCryptoMode::from_crypto_type(section.crypto_type)? == NczCryptoMode::AesCtr {
             apply_aes_ctr(
                 &mut output[write_start..],
                 &section.crypto_key,
@@ -173,14 +208,32 @@ fn leading_gap_from_sections(sections: &[NczSection]) -> Result<u64, NszError> {
         })
 }
 
-fn decode_ncz_stream(data: &[u8], stream_offset: usize) -> Result<Vec<u8>, NszError> {
+fn decode_ncz_stream(
+    data: &[u8],
+    stream_offset: usize,
+    progress: &mut EntryProgress<'_>,
+) -> Result<Vec<u8>, NszError> {
     if data.len() >= stream_offset + 8 && &data[stream_offset..stream_offset + 8] == b"NCZBLOCK" {
-        return decode_ncz_block_stream(&data[stream_offset..]);
+        return decode_ncz_block_stream(&data[stream_offset..], progress);
+    }
+    let mut decoder = zstd::stream::read::Decoder::new(&data[stream_offset..])?;
+    let mut out = Vec::new();
+    loop {
+        let start = out.len();
+        out.resize(start + READ_CHUNK_SIZE, 0);
+        let read = decoder.read(&mut out[start..])?;
+        out.truncate(start + read);
+        if read == 0 {
+            return Ok(out);
+        }
+        progress.advance(read as u64)?;
     }
-    zstd::stream::decode_all(&data[stream_offset..]).map_err(NszError::from)
 }
 
-fn decode_ncz_block_stream(data: &[u8]) -> Result<Vec<u8>, NszError> {
+fn decode_ncz_block_stream(
+    data: &[u8],
+    progress: &mut EntryProgress<'_>,
+) -> Result<Vec<u8>, NszError> {
     if data.len() < 24 {
         return Err(NszError::ContainerFormat {
             message: "NCZBLOCK header too short".to_string(),
@@ -233,6 +286,7 @@ fn decode_ncz_block_stream(data: &[u8]) -> Result<Vec<u8>, NszError> {
 
         if compressed_size == expected_block {
             out.extend_from_slice(block_data);
+            progress.advance(expected_block as u64)?;
             continue;
         }
 
@@ -243,6 +297,7 @@ fn decode_ncz_block_stream(data: &[u8]) -> Result<Vec<u8>, NszError> {
             });
         }
         out.extend_from_slice(&decoded);
+        progress.advance(expected_block as u64)?;
     }
 
     if out.len() != decompressed_size {
@@ -2,3 +2,33 @@ pub mod block_reader;
 pub mod compress;
 pub mod decompress;
 pub mod header;
+
+use crate::error::NszError;
+
+/// How the payload of an NCZ section is stored in the compressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NczCryptoMode {
+    /// Bytes are stored exactly as they appear in the NCA.
+    Passthrough,
+    /// Bytes are stored decrypted and re-encrypted with AES-CTR on decompression.
+    AesCtr,
+}
+
+impl NczCryptoMode {
+    /// Classifies an NCZ section crypto type.
+    ///
+    /// Type 0 marks payload written without a compression plan and type 1 is
+    /// unencrypted. Type 2 (AES-XTS) stays encrypted because an NCZ section only
+    /// carries a single 16-byte key. Types 3 (CTR), 4 (BKTR/CTR-EX) and the 5/6
+    /// skip-layer-hash variants are re-encrypted with AES-CTR, matching the NCA
+    /// section types compression accepts.
+    pub fn from_crypto_type(crypto_type: u64) -> Result<Self, NszError> {
+        match crypto_type {
+            0..=2 => Ok(Self::Passthrough),
+            3..=6 => Ok(Self::AesCtr),
+            other => Err(NszError::UnsupportedFeature {
+                feature: format!("NCZ section crypto type {other}"),
+            }),
+        }
+    }
+}
@@ -1,17 +1,23 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::config::CompressRequest;
 use crate::container::hfs0::{encode_hfs0, Hfs0Archive};
-use crate::container::nca::{NcaKeySet, TicketRecord};
+use crate::container::nca::{parse_title_keys_str, NcaCompressionPlan, NcaKeySet, TicketRecord};
 use crate::container::nsp::{encode_pfs0, NspArchive};
 use crate::container::xci::{encode_xci_like, XciArchive};
+use crate::crypto::keystore::KeyStore;
+use crate::crypto::validate::{check_header_key, KeyFingerprints};
 use crate::error::NszError;
-use crate::ops::OperationReport;
-use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli};
+use crate::fs_ops::journal::Journal;
+use crate::fs_ops::split_files::{logical_path, read_input, write_output};
+use crate::ncz::compress::NczEstimate;
+use crate::ops::{run_fallback, OperationReport};
+use crate::parity::python_runner::resolve_python_repo_root;
+use crate::progress::ProgressEvent;
 
 const UNCOMPRESSABLE_HEADER_SIZE: usize = 0x4000;
 const XCI_HFS0_FIRST_FILE_OFFSET: u64 = 0x8000;
@@ -22,66 +28,63 @@ pub fn run(request: &CompressRequest) -> Result<OperationReport, NszError> {
         return Ok(OperationReport::default());
     }
 
+    let key_store = KeyStore::resolve(request.keys_file.as_deref())?;
+    if request.require_keys && key_store.is_none() {
+        return Err(NszError::MissingRequiredKey {
+            key: "header_key (no keys file found; set keys_file or NSZ_KEYS_FILE)".to_string(),
+        });
+    }
+    if let Some(store) = &key_store {
+        store
+            .validate(&KeyFingerprints::load_default()?)
+            .into_result()?;
+    }
+    let keyset = key_store.as_ref().map(KeyStore::nca_keys);
+    let title_keys = resolve_title_keys(request.title_keys_file.as_deref())?;
+    let solid_threads = effective_solid_threads(request.threads);
+    if request.dry_run {
+        return estimate_files(request, keyset, &title_keys, solid_threads);
+    }
+
     if let Some(out_dir) = &request.output_dir {
         fs::create_dir_all(out_dir)?;
     }
-
-    let mut processed_files = Vec::new();
+    let mut report = OperationReport::default();
+    for dir in output_dirs(request) {
+        report.clean_stale_temps(&dir)?;
+    }
     let mut fallback_files = Vec::new();
-    let keyset = resolve_keyset();
-    let solid_threads = effective_solid_threads(request.threads);
-
+    let mut journal = request
+        .journal
+        .as_deref()
+        .map(|path| Journal::open(path, "compress"))
+        .transpose()?;
     for file in &request.files {
-        match normalized_extension(file) {
-            Some("nsp") => {
-                let input = fs::read(file)?;
-                let output = compress_nsp_to_nsz(&input, request, keyset.as_ref(), solid_threads)?;
-                let out_file = expected_compressed_output(file, request.output_dir.as_deref())
-                    .ok_or_else(|| NszError::ContainerFormat {
-                        message: format!("could not resolve output path for {}", file.display()),
-                    })?;
-                fs::write(&out_file, output)?;
-                processed_files.push(out_file);
-            }
-            Some("xci") => {
-                let input = fs::read(file)?;
-                let output = compress_xci_to_xcz(&input, request, keyset.as_ref(), solid_threads)?;
-                let out_file = expected_compressed_output(file, request.output_dir.as_deref())
-                    .ok_or_else(|| NszError::ContainerFormat {
-                        message: format!("could not resolve output path for {}", file.display()),
-                    })?;
-                fs::write(&out_file, output)?;
-                processed_files.push(out_file);
-            }
-            Some("nca") => {
-                let input = fs::read(file)?;
-                let empty_tickets = HashMap::new();
-                let plan = keyset.as_ref().and_then(|keys| {
-                    crate::container::nca::build_compression_plan(&input, keys, &empty_tickets).ok()
-                });
-                let output = crate::ncz::compress::compress_nca_to_ncz_vec_with_plan(
-                    &input,
-                    request.level,
-                    request.long_distance_mode,
-                    solid_threads,
-                    plan.as_ref(),
-                )?;
-                let out_file = expected_compressed_output(file, request.output_dir.as_deref())
-                    .ok_or_else(|| NszError::ContainerFormat {
-                        message: format!("could not resolve output path for {}", file.display()),
-                    })?;
-                fs::write(&out_file, output)?;
-                processed_files.push(out_file);
+        request.cancel.check()?;
+        if let Some(output) = journal.as_ref().and_then(|journal| journal.completed(file)) {
+            report.record_resumed(file, output);
+            continue;
+        }
+        let started = Instant::now();
+        match compress_file(
+            file,
+            request,
+            keyset,
+            &title_keys,
+            solid_threads,
+            journal.as_mut(),
+        ) {
+            Ok(Some(out_file)) => report.record_processed(file, out_file, started),
+            Ok(None) => fallback_files.push(file.clone()),
+            Err(err) if request.continue_on_error && !err.is_cancelled() => {
+                report.record_failed(file, started, &err);
             }
-            _ => fallback_files.push(file.clone()),
+            Err(err) => return Err(err),
         }
     }
 
     if fallback_files.is_empty() {
-        return Ok(OperationReport {
-            processed_files,
-            skipped_files: Vec::new(),
-        });
+        return report.finish(request.files.len());
     }
 
     let repo_root = resolve_python_repo_root(request.python_repo_root.as_deref());
@@ -89,26 +92,117 @@ pub fn run(request: &CompressRequest) -> Result<OperationReport, NszError> {
     for file in &fallback_files {
         args.push(file.display().to_string());
     }
-    run_nsz_cli(&repo_root, &args)?;
-
-    for file in &fallback_files {
-        if let Some(path) = expected_compressed_output(file, request.output_dir.as_deref()) {
-            if path.exists() {
-                processed_files.push(path);
+    let outputs: Vec<PathBuf> = fallback_files
+        .iter()
+        .filter_map(|file| expected_compressed_output(file, request.output_dir.as_deref()))
+        .collect();
+    let started = Instant::now();
+    match run_fallback(
+        &repo_root,
+        &args,
+        request.keys_file.as_deref(),
+        &outputs,
+        &request.cancel,
+    ) {
+        Ok(()) => {
+            for file in &fallback_files {
+                if let Some(path) = expected_compressed_output(file, request.output_dir.as_deref())
+                {
+                    if path.exists() {
+                        report.record_processed(file, path, started);
+                    }
+                }
+            }
+        }
+        Err(err) if request.continue_on_error && !err.is_cancelled() => {
+            for file in &fallback_files {
+                report.record_failed(file, started, &err);
             }
         }
+        Err(err) => return Err(err),
     }
 
-    Ok(OperationReport {
-        processed_files,
-        skipped_files: Vec::new(),
-    })
+    report.finish(request.files.len())
+}
+
+/// Compresses one input natively, returning `None` for formats left to the Python fallback.
+fn compress_file(
+    file: &Path,
+    request: &CompressRequest,
+    keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+    solid_threads: i32,
+    journal: Option<&mut Journal>,
+) -> Result<Option<PathBuf>, NszError> {
+    let logical = logical_path(file);
+    let output = match normalized_extension(&logical) {
+        Some("nsp") => {
+            let input = read_input(file)?;
+            compress_nsp_to_nsz(file, &input, request, keyset, title_keys, solid_threads)?
+        }
+        Some("xci") => {
+            let input = read_input(file)?;
+            compress_xci_to_xcz(file, &input, request, keyset, title_keys, solid_threads)?
+        }
+        Some("nca") => {
+            let input = read_input(file)?;
+            let name = file.display().to_string();
+            probe_header_key(keyset, [(name.as_str(), input.as_slice())])?;
+            request.progress.emit(ProgressEvent::ContainerOpened {
+                path: file,
+                entries: 1,
+            });
+            let mut progress =
+                request
+                    .progress
+                    .start_entry(&name, input.len() as u64, &request.cancel)?;
+            let plan = plan_for_entry(
+                &name,
+                &input,
+                keyset,
+                &HashMap::new(),
+                title_keys,
+                request.require_keys,
+            )
+            .map_err(|err| err.in_entry(&name))?;
+            let output = crate::ncz::compress::compress_solid_with_progress(
+                &input,
+                request.level,
+                request.long_distance_mode,
+                solid_threads,
+                plan.as_ref(),
+                &mut progress,
+            )?;
+            progress.finish();
+            output
+        }
+        _ => return Ok(None),
+    };
+    let out_file =
+        expected_compressed_output(&logical, request.output_dir.as_deref()).ok_or_else(|| {
+            NszError::ContainerFormat {
+                message: format!("could not resolve output path for {}", file.display()),
+            }
+        })?;
+    // Outputs are built in memory, so a cancelled input never leaves a partial file.
+    request.cancel.check()?;
+    let written = write_output(&out_file, &output, request.split_output)?;
+    if let Some(journal) = journal {
+        journal.record(file, &written, &output)?;
+    }
+    request.progress.emit(ProgressEvent::OutputWritten {
+        path: &written,
+        size: output.len() as u64,
+    });
+    Ok(Some(written))
 }
 
 fn compress_nsp_to_nsz(
+    path: &Path,
     data: &[u8],
     request: &CompressRequest,
     keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
     solid_threads: i32,
 ) -> Result<Vec<u8>, NszError> {
     let profile = std::env::var("NSZ_PROFILE_COMPRESS").ok().as_deref() == Some("1");
@@ -118,16 +212,23 @@ fn compress_nsp_to_nsz(
     let tickets_started = Instant::now();
     let tickets = collect_nsp_tickets(&archive, data);
     let tickets_elapsed = tickets_started.elapsed();
-    let largest_convertible_nca = archive
-        .entries()
-        .iter()
-        .filter(|entry| {
-            entry.name.to_ascii_lowercase().ends_with(".nca")
-                && !entry.name.to_ascii_lowercase().ends_with(".cnmt.nca")
-                && entry.size as usize > UNCOMPRESSABLE_HEADER_SIZE
-        })
-        .map(|entry| entry.size)
-        .max();
+    probe_header_key(
+        keyset,
+        archive
+            .entries()
+            .iter()
+            .map(|entry| (entry.name.as_str(), archive.entry_bytes(data, entry))),
+    )?;
+    request.progress.emit(ProgressEvent::ContainerOpened {
+        path,
+        entries: archive.entries().len(),
+    });
+    let largest_convertible_nca = largest_nca_size(
+        archive
+            .entries()
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.size)),
+    );
     let mut output_entries: Vec<(String, Cow<'_, [u8]>)> =
         Vec::with_capacity(archive.entries().len());
     let mut converted_entries = 0usize;
@@ -138,6 +239,10 @@ fn compress_nsp_to_nsz(
 
     for entry in archive.entries() {
         let entry_bytes = archive.entry_bytes(data, entry);
+        let mut progress =
+            request
+                .progress
+                .start_entry(&entry.name, entry.size, &request.cancel)?;
         if should_convert_nca_entry(
             &entry.name,
             entry_bytes,
@@ -153,25 +258,25 @@ fn compress_nsp_to_nsz(
                     message: format!("invalid UTF-8 output name for {}", entry.name),
                 })?
                 .to_string();
-            let plan =
-                keyset.and_then(|keys| {
-                    match crate::container::nca::build_compression_plan(entry_bytes, keys, &tickets)
-                    {
-                        Ok(plan) => Some(plan),
-                        Err(err) => {
-                            debug_plan_failure(&entry.name, &err);
-                            None
-                        }
-                    }
-                });
+            let plan = plan_for_entry(
+                &entry.name,
+                entry_bytes,
+                keyset,
+                &tickets,
+                title_keys,
+                request.require_keys,
+            )
+            .map_err(|err| err.in_entry(&entry.name))?;
             let nca_started = Instant::now();
-            let output = crate::ncz::compress::compress_nca_to_ncz_vec_with_plan(
+            let output = crate::ncz::compress::compress_solid_with_progress(
                 entry_bytes,
                 request.level,
                 request.long_distance_mode,
                 solid_threads,
                 plan.as_ref(),
-            )?;
+                &mut progress,
+            )
+            .map_err(|err| err.in_entry(&entry.name))?;
             convert_elapsed += nca_started.elapsed();
             converted_entries += 1;
             converted_bytes = converted_bytes.saturating_add(entry.size);
@@ -181,6 +286,7 @@ fn compress_nsp_to_nsz(
             passthrough_bytes = passthrough_bytes.saturating_add(entry.size);
             output_entries.push((entry.name.clone(), Cow::Borrowed(entry_bytes)));
         }
+        progress.finish();
     }
 
     let encode_started = Instant::now();
@@ -209,15 +315,21 @@ fn compress_nsp_to_nsz(
 }
 
 fn compress_xci_to_xcz(
+    path: &Path,
     data: &[u8],
     request: &CompressRequest,
     keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
     solid_threads: i32,
 ) -> Result<Vec<u8>, NszError> {
     let use_block_ncz = request.block || !request.solid;
     let xci = XciArchive::from_bytes(data)?;
     let root_bytes = xci.root_hfs0_bytes(data)?;
     let root = xci.root_hfs0_archive(data)?;
+    request.progress.emit(ProgressEvent::ContainerOpened {
+        path,
+        entries: kept_partition_entries(&root, root_bytes, request.keep),
+    });
 
     let mut root_output_entries = Vec::with_capacity(root.entries().len());
     let mut trailing_padding_to_trim = 0usize;
@@ -243,28 +355,37 @@ fn compress_xci_to_xcz(
                     partition.name
                 ),
             })?;
+        probe_header_key(
+            keyset,
+            partition_archive.entries().iter().map(|entry| {
+                (
+                    entry.name.as_str(),
+                    partition_archive.entry_bytes(partition_bytes, entry),
+                )
+            }),
+        )?;
         let partition_tickets = collect_hfs0_tickets(&partition_archive, partition_bytes);
-        let largest_convertible_nca = partition_archive
-            .entries()
-            .iter()
-            .filter(|entry| {
-                entry.name.to_ascii_lowercase().ends_with(".nca")
-                    && !entry.name.to_ascii_lowercase().ends_with(".cnmt.nca")
-                    && entry.size as usize > UNCOMPRESSABLE_HEADER_SIZE
-            })
-            .map(|entry| entry.size)
-            .max();
+        let largest_convertible_nca = largest_nca_size(
+            partition_archive
+                .entries()
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.size)),
+        );
 
         let mut partition_output_entries: Vec<(String, Cow<'_, [u8]>)> =
             Vec::with_capacity(partition_archive.entries().len());
         for entry in partition_archive.entries() {
             let entry_bytes = partition_archive.entry_bytes(partition_bytes, entry);
+            let mut progress =
+                request
+                    .progress
+                    .start_entry(&entry.name, entry.size, &request.cancel)?;
             if should_convert_nca_entry(
                 &entry.name,
                 entry_bytes,
                 entry.size,
                 largest_convertible_nca,
-                keyset.as_ref().map(|keys| &keys.header_key),
+                keyset.map(|keys| &keys.header_key),
             ) {
                 let mut new_name = PathBuf::from(&entry.name);
                 new_name.set_extension("ncz");
@@ -274,38 +395,41 @@ fn compress_xci_to_xcz(
                         message: format!("invalid UTF-8 output name for {}", entry.name),
                     })?
                     .to_string();
-                let plan = keyset.and_then(|keys| {
-                    crate::container::nca::build_compression_plan(
-                        entry_bytes,
-                        keys,
-                        &partition_tickets,
-                    )
-                    .inspect_err(|err| {
-                        debug_plan_failure(&entry.name, err);
-                    })
-                    .ok()
-                });
+                let plan = plan_for_entry(
+                    &entry.name,
+                    entry_bytes,
+                    keyset,
+                    &partition_tickets,
+                    title_keys,
+                    request.require_keys,
+                )
+                .map_err(|err| err.in_entry(&entry.name))?;
                 let output = if use_block_ncz {
-                    crate::ncz::compress::compress_nca_to_ncz_block_vec_with_plan(
+                    crate::ncz::compress::compress_block_with_progress(
                         entry_bytes,
                         request.level,
                         request.long_distance_mode,
                         request.block_size_exponent,
                         plan.as_ref(),
-                    )?
+                        &mut progress,
+                    )
+                    .map_err(|err| err.in_entry(&entry.name))?
                 } else {
-                    crate::ncz::compress::compress_nca_to_ncz_vec_with_plan(
+                    crate::ncz::compress::compress_solid_with_progress(
                         entry_bytes,
                         request.level,
                         request.long_distance_mode,
                         solid_threads,
                         plan.as_ref(),
-                    )?
+                        &mut progress,
+                    )
+                    .map_err(|err| err.in_entry(&entry.name))?
                 };
                 partition_output_entries.push((name, Cow::Owned(output)));
             } else {
                 partition_output_entries.push((entry.name.clone(), Cow::Borrowed(entry_bytes)));
             }
+            progress.finish();
         }
 
         let partition_output =
@@ -328,6 +452,251 @@ fn compress_xci_to_xcz(
     Ok(output)
 }
 
+/// Estimates every input of a dry run without writing anything; formats left to the Python
+/// fallback are skipped.
+fn estimate_files(
+    request: &CompressRequest,
+    keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+    solid_threads: i32,
+) -> Result<OperationReport, NszError> {
+    let mut report = OperationReport::default();
+    for file in &request.files {
+        request.cancel.check()?;
+        let started = Instant::now();
+        match estimate_file(file, request, keyset, title_keys, solid_threads) {
+            Ok(Some((out_file, estimate))) => {
+                report.record_estimated(file, out_file, estimate.size, estimate.duration, started);
+            }
+            Ok(None) => report.record_skipped(file),
+            Err(err) if request.continue_on_error && !err.is_cancelled() => {
+                report.record_failed(file, started, &err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    report.finish(request.files.len())
+}
+
+/// Estimates the output of one input the way [`compress_file`] would build it.
+fn estimate_file(
+    file: &Path,
+    request: &CompressRequest,
+    keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+    solid_threads: i32,
+) -> Result<Option<(PathBuf, NczEstimate)>, NszError> {
+    let logical = logical_path(file);
+    let input = match normalized_extension(&logical) {
+        Some("nsp" | "xci" | "nca") => read_input(file)?,
+        _ => return Ok(None),
+    };
+    let estimate = match normalized_extension(&logical) {
+        Some("nsp") => {
+            let archive = NspArchive::from_bytes(&input)?;
+            probe_header_key(
+                keyset,
+                archive
+                    .entries()
+                    .iter()
+                    .map(|entry| (entry.name.as_str(), archive.entry_bytes(&input, entry))),
+            )?;
+            let tickets = collect_nsp_tickets(&archive, &input);
+            let largest_convertible_nca = largest_nca_size(
+                archive
+                    .entries()
+                    .iter()
+                    .map(|entry| (entry.name.as_str(), entry.size)),
+            );
+            let mut estimate = NczEstimate {
+                size: input.len() as u64,
+                duration: Duration::ZERO,
+            };
+            for entry in archive.entries() {
+                request.cancel.check()?;
+                let entry_bytes = archive.entry_bytes(&input, entry);
+                let entry_estimate = estimate_entry(
+                    &entry.name,
+                    entry_bytes,
+                    largest_convertible_nca,
+                    keyset,
+                    &tickets,
+                    title_keys,
+                    request,
+                    solid_threads,
+                    None,
+                )
+                .map_err(|err| err.in_entry(&entry.name))?;
+                estimate.size = estimate.size - entry.size + entry_estimate.size;
+                estimate.duration += entry_estimate.duration;
+            }
+            estimate
+        }
+        Some("xci") => estimate_xci(&input, request, keyset, title_keys, solid_threads)?,
+        _ => {
+            let name = file.display().to_string();
+            probe_header_key(keyset, [(name.as_str(), input.as_slice())])?;
+            let plan = plan_for_entry(
+                &name,
+                &input,
+                keyset,
+                &HashMap::new(),
+                title_keys,
+                request.require_keys,
+            )
+            .map_err(|err| err.in_entry(&name))?;
+            crate::ncz::compress::estimate_ncz(
+                &input,
+                request.level,
+                request.long_distance_mode,
+                solid_threads,
+                None,
+                plan.as_ref(),
+                request.estimate_sample_chunks,
+            )?
+        }
+    };
+    let out_file =
+        expected_compressed_output(&logical, request.output_dir.as_deref()).ok_or_else(|| {
+            NszError::ContainerFormat {
+                message: format!("could not resolve output path for {}", file.display()),
+            }
+        })?;
+    Ok(Some((out_file, estimate)))
+}
+
+/// Estimates an XCZ: partitions [`compress_xci_to_xcz`] empties shrink to their empty
+/// HFS0, and the NCAs of kept ones are estimated as their NCZs.
+fn estimate_xci(
+    data: &[u8],
+    request: &CompressRequest,
+    keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+    solid_threads: i32,
+) -> Result<NczEstimate, NszError> {
+    let block_size_exponent =
+        (request.block || !request.solid).then_some(request.block_size_exponent);
+    let xci = XciArchive::from_bytes(data)?;
+    let root_bytes = xci.root_hfs0_bytes(data)?;
+    let root = xci.root_hfs0_archive(data)?;
+    let mut estimate = NczEstimate {
+        size: data.len() as u64,
+        duration: Duration::ZERO,
+    };
+    for partition in root.entries() {
+        if !request.keep && !partition.name.eq_ignore_ascii_case("secure") {
+            estimate.size = estimate.size - partition.size + 0x200;
+            continue;
+        }
+        let partition_bytes = root.entry_bytes(root_bytes, partition);
+        let partition_archive = Hfs0Archive::from_bytes(partition_bytes)?;
+        probe_header_key(
+            keyset,
+            partition_archive.entries().iter().map(|entry| {
+                (
+                    entry.name.as_str(),
+                    partition_archive.entry_bytes(partition_bytes, entry),
+                )
+            }),
+        )?;
+        let tickets = collect_hfs0_tickets(&partition_archive, partition_bytes);
+        let largest_convertible_nca = largest_nca_size(
+            partition_archive
+                .entries()
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.size)),
+        );
+        for entry in partition_archive.entries() {
+            request.cancel.check()?;
+            let entry_estimate = estimate_entry(
+                &entry.name,
+                partition_archive.entry_bytes(partition_bytes, entry),
+                largest_convertible_nca,
+                keyset,
+                &tickets,
+                title_keys,
+                request,
+                solid_threads,
+                block_size_exponent,
+            )
+            .map_err(|err| err.in_entry(&entry.name))?;
+            estimate.size = estimate.size - entry.size + entry_estimate.size;
+            estimate.duration += entry_estimate.duration;
+        }
+    }
+    Ok(estimate)
+}
+
+/// Estimates one container entry: NCAs compression converts by their NCZ, anything else
+/// as stored.
+fn estimate_entry(
+    name: &str,
+    bytes: &[u8],
+    largest_convertible_nca: Option<u64>,
+    keyset: Option<&NcaKeySet>,
+    tickets: &HashMap<[u8; 16], TicketRecord>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+    request: &CompressRequest,
+    solid_threads: i32,
+    block_size_exponent: Option<u8>,
+) -> Result<NczEstimate, NszError> {
+    let size = bytes.len() as u64;
+    if !should_convert_nca_entry(
+        name,
+        bytes,
+        size,
+        largest_convertible_nca,
+        keyset.map(|keys| &keys.header_key),
+    ) {
+        return Ok(NczEstimate {
+            size,
+            duration: Duration::ZERO,
+        });
+    }
+    let plan = plan_for_entry(
+        name,
+        bytes,
+        keyset,
+        tickets,
+        title_keys,
+        request.require_keys,
+    )?;
+    crate::ncz::compress::estimate_ncz(
+        bytes,
+        request.level,
+        request.long_distance_mode,
+        solid_threads,
+        block_size_exponent,
+        plan.as_ref(),
+        request.estimate_sample_chunks,
+    )
+}
+
+/// Size of the largest NCA that could be converted, which is compressed when its header
+/// cannot be read for lack of keys.
+fn largest_nca_size<'a>(entries: impl Iterator<Item = (&'a str, u64)>) -> Option<u64> {
+    entries
+        .filter(|(name, size)| {
+            name.to_ascii_lowercase().ends_with(".nca")
+                && !name.to_ascii_lowercase().ends_with(".cnmt.nca")
+                && *size as usize > UNCOMPRESSABLE_HEADER_SIZE
+        })
+        .map(|(_, size)| size)
+        .max()
+}
+
+/// Counts the files in the partitions [`compress_xci_to_xcz`] rewrites rather than empties.
+fn kept_partition_entries(root: &Hfs0Archive, root_bytes: &[u8], keep: bool) -> usize {
+    root.entries()
+        .iter()
+        .filter(|partition| keep || partition.name.eq_ignore_ascii_case("secure"))
+        .filter_map(|partition| {
+            Hfs0Archive::from_bytes(root.entry_bytes(root_bytes, partition)).ok()
+        })
+        .map(|archive| archive.entries().len())
+        .sum()
+}
+
 fn align_xci_partition_size(mut bytes: Vec<u8>) -> Vec<u8> {
     let padding = 0x200 - (bytes.len() % 0x200);
     bytes.resize(bytes.len() + padding, 0);
@@ -395,6 +764,26 @@ fn should_convert_nca_entry(
     Some(size) == fallback_largest_size
 }
 
+/// Trial-decrypts the first NCA among `entries` with the loaded header key, so a wrong
+/// `header_key` fails before compressing instead of quietly leaving every NCA unplanned.
+fn probe_header_key<'a>(
+    keyset: Option<&NcaKeySet>,
+    entries: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+) -> Result<(), NszError> {
+    let Some(keys) = keyset else {
+        return Ok(());
+    };
+    let first_nca = entries.into_iter().find(|(name, bytes)| {
+        bytes.len() > UNCOMPRESSABLE_HEADER_SIZE
+            && Path::new(name)
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("nca"))
+    });
+    first_nca.map_or(Ok(()), |(name, bytes)| {
+        check_header_key(&keys.header_key, bytes).map_err(|err| err.in_entry(name))
+    })
+}
+
 const fn effective_solid_threads(request_threads: i32) -> i32 {
     if request_threads > 0 {
         request_threads
@@ -409,38 +798,73 @@ fn debug_plan_failure(entry_name: &str, error: &NszError) {
     }
 }
 
-fn resolve_keyset() -> Option<NcaKeySet> {
-    for candidate in candidate_key_paths() {
-        let Ok(content) = fs::read_to_string(&candidate) else {
-            continue;
-        };
-        if let Ok(keyset) = NcaKeySet::from_keys_str(&content) {
-            return Some(keyset);
+/// Builds the NCZ crypto plan for one NCA.
+///
+/// Without `require_keys`, missing keys or tickets fall back to an unplanned NCZ
+/// (`Ok(None)`); with it, the failure is returned for the caller to attach the entry name.
+/// Content this crate cannot plan, such as NCA0, and keys that do not decrypt the NCA are
+/// always errors.
+fn plan_for_entry(
+    entry_name: &str,
+    bytes: &[u8],
+    keyset: Option<&NcaKeySet>,
+    tickets: &HashMap<[u8; 16], TicketRecord>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+    require_keys: bool,
+) -> Result<Option<NcaCompressionPlan>, NszError> {
+    let Some(keys) = keyset else {
+        return Ok(None);
+    };
+    match crate::container::nca::build_compression_plan_with_title_keys(
+        bytes, keys, tickets, title_keys,
+    ) {
+        Ok(plan) => Ok(Some(plan)),
+        Err(err @ (NszError::UnsupportedFeature { .. } | NszError::InvalidKeys { .. })) => Err(err),
+        Err(err) if require_keys => Err(err),
+        Err(err) => {
+            debug_plan_failure(entry_name, &err);
+            Ok(None)
         }
     }
-    None
 }
 
-fn candidate_key_paths() -> Vec<PathBuf> {
+pub(crate) fn resolve_title_keys(
+    explicit: Option<&Path>,
+) -> Result<HashMap<[u8; 16], TicketRecord>, NszError> {
+    if let Some(path) = explicit {
+        return load_title_keys(path, &fs::read_to_string(path)?);
+    }
+    for candidate in candidate_title_key_paths() {
+        match fs::read_to_string(&candidate) {
+            Ok(content) => return load_title_keys(&candidate, &content),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(HashMap::new())
+}
+
+fn load_title_keys(
+    path: &Path,
+    content: &str,
+) -> Result<HashMap<[u8; 16], TicketRecord>, NszError> {
+    parse_title_keys_str(content).map_err(|err| match err {
+        NszError::ContainerFormat { message } => NszError::ContainerFormat {
+            message: format!("{}: {message}", path.display()),
+        },
+        other => other,
+    })
+}
+
+fn candidate_title_key_paths() -> Vec<PathBuf> {
     let mut out = Vec::new();
-    if let Ok(path) = std::env::var("NSZ_KEYS_FILE") {
+    if let Ok(path) = std::env::var("NSZ_TITLE_KEYS_FILE") {
         out.push(PathBuf::from(path));
     }
-    out.push(PathBuf::from("prod.keys"));
-    out.push(PathBuf::from("keys.txt"));
-    out.push(PathBuf::from(
-        "/home/matteo/Documents/prog/python/nsz/prod.keys",
-    ));
-    out.push(PathBuf::from(
-        "/home/matteo/Documents/prog/python/nsz/keys.txt",
-    ));
-
+    out.push(PathBuf::from("title.keys"));
     if let Ok(home) = std::env::var("HOME") {
-        let home_dir = PathBuf::from(home);
-        out.push(home_dir.join(".switch").join("prod.keys"));
-        out.push(home_dir.join(".switch").join("keys.txt"));
+        out.push(PathBuf::from(home).join(".switch").join("title.keys"));
     }
-
     out
 }
 
@@ -510,6 +934,20 @@ fn normalized_extension(path: &Path) -> Option<&str> {
     }
 }
 
+/// Returns the directories outputs are written to: the output directory, or else the
+/// directories holding the inputs.
+fn output_dirs(request: &CompressRequest) -> BTreeSet<PathBuf> {
+    if let Some(out_dir) = &request.output_dir {
+        return BTreeSet::from([out_dir.clone()]);
+    }
+    request
+        .files
+        .iter()
+        .filter_map(|file| file.parent())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
 fn expected_compressed_output(input: &Path, output_dir: Option<&Path>) -> Option<PathBuf> {
     let mut output_name = PathBuf::from(input.file_name()?);
     let extension = input.extension()?.to_string_lossy().to_ascii_lowercase();
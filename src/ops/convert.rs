@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::ConvertRequest;
+use crate::container::nca::{NcaKeySet, TicketRecord};
+use crate::container::nsp::encode_new_pfs0;
+use crate::crypto::keystore::KeyStore;
+use crate::error::NszError;
+use crate::fs_ops::atomic_write::write_atomic;
+use crate::ops::package::{
+    collect_ticket_files, is_content, package_with_tickets, secure_partition_entries,
+    split_by_title, Package, PackageEntry,
+};
+use crate::ops::OperationReport;
+
+/// Converts `.xci`/`.xcz` gamecard images into installable `.nsp`/`.nsz` packages.
+///
+/// Outputs hold the secure partition's NCAs (or NCZs) followed by the tickets and
+/// certificates their rights IDs need; standard-crypto titles get neither.
+pub fn run(request: &ConvertRequest) -> Result<OperationReport, NszError> {
+    if request.files.is_empty() {
+        return Ok(OperationReport::default());
+    }
+
+    let out_dir = request
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("nsz-rs-out"));
+    fs::create_dir_all(&out_dir)?;
+
+    let key_store = KeyStore::resolve(request.keys_file.as_deref())?;
+    if request.split_titles && key_store.is_none() {
+        return Err(NszError::MissingRequiredKey {
+            key: "header_key (splitting by title reads CNMTs; set keys_file or NSZ_KEYS_FILE)"
+                .to_string(),
+        });
+    }
+    let keyset = key_store.as_ref().map(KeyStore::nca_keys);
+    let title_keys = crate::ops::compress::resolve_title_keys(request.title_keys_file.as_deref())?;
+
+    let mut report = OperationReport::default();
+    report.clean_stale_temps(&out_dir)?;
+    for file in &request.files {
+        let started = Instant::now();
+        let Some(extension) = output_extension(file) else {
+            report.record_skipped(file);
+            continue;
+        };
+        let input = fs::read(file)?;
+        let entries = secure_partition_entries(&input).map_err(|err| match err {
+            NszError::ContainerFormat { message } => NszError::ContainerFormat {
+                message: format!("{}: {message}", file.display()),
+            },
+            other => other,
+        })?;
+
+        let stem = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let packages = if request.split_titles {
+            let keys = keyset.ok_or_else(|| NszError::MissingRequiredKey {
+                key: "header_key".to_string(),
+            })?;
+            split_by_title(&entries, keys, &title_keys)?
+                .into_iter()
+                .map(|(title_id, package)| (format!("{stem} [{title_id:016X}]"), package))
+                .collect()
+        } else {
+            vec![(stem, whole_package(&entries, keyset, &title_keys)?)]
+        };
+
+        for (name, package) in packages {
+            let out_file = out_dir.join(format!("{name}.{extension}"));
+            write_atomic(&out_file, &encode_new_pfs0(&package)?)?;
+            report.record_processed(file, out_file, started);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Packages every content file of the partition into one NSP.
+fn whole_package(
+    entries: &[PackageEntry<'_>],
+    keyset: Option<&NcaKeySet>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+) -> Result<Package, NszError> {
+    let contents = entries
+        .iter()
+        .filter(|entry| is_content(&entry.name))
+        .collect::<Vec<_>>();
+    let Some(keys) = keyset else {
+        // Without keys rights IDs are unknown, so every ticket and cert is carried over.
+        let mut package = contents
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.bytes.to_vec()))
+            .collect::<Vec<_>>();
+        package.extend(
+            entries
+                .iter()
+                .filter(|entry| !is_content(&entry.name))
+                .map(|entry| (entry.name.clone(), entry.bytes.to_vec())),
+        );
+        return Ok(package);
+    };
+
+    package_with_tickets(&contents, keys, &collect_ticket_files(entries), title_keys)
+}
+
+fn output_extension(input: &Path) -> Option<&'static str> {
+    match input.extension()?.to_str()? {
+        ext if ext.eq_ignore_ascii_case("xci") => Some("nsp"),
+        ext if ext.eq_ignore_ascii_case("xcz") => Some("nsz"),
+        _ => None,
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use crate::config::CreateRequest;
 use crate::error::NszError;
 use crate::ops::OperationReport;
@@ -21,10 +23,10 @@ pub fn run(request: &CreateRequest) -> Result<OperationReport, NszError> {
         args.push(source.display().to_string());
     }
 
+    let started = Instant::now();
     run_nsz_cli(&repo_root, &args)?;
 
-    Ok(OperationReport {
-        processed_files: vec![output_file.clone()],
-        skipped_files: Vec::new(),
-    })
+    let mut report = OperationReport::default();
+    report.record_merged(&request.sources, output_file.clone(), started);
+    Ok(report)
 }
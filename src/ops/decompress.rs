@@ -1,16 +1,22 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use crate::config::DecompressRequest;
 use crate::container::hfs0::{encode_hfs0, Hfs0Archive};
 use crate::container::nsp::{encode_pfs0, NspArchive};
-use crate::container::xci::{encode_xci_like, XciArchive};
+use crate::container::xci::{encode_xci_like, refresh_root_hfs0_header_hash, XciArchive};
 use crate::error::NszError;
-use crate::ops::OperationReport;
-use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli};
+use crate::fs_ops::journal::Journal;
+use crate::fs_ops::split_files::{logical_path, read_input, write_output};
+use crate::ops::{run_fallback, OperationReport};
+use crate::parity::python_runner::resolve_python_repo_root;
+use crate::progress::ProgressEvent;
 
 /// Decompresses supported inputs natively and falls back to Python `nsz` when needed.
 pub fn run(request: &DecompressRequest) -> Result<OperationReport, NszError> {
+    crate::ops::check_keys_file(request.keys_file.as_deref())?;
+
     let out_dir = request
         .output_dir
         .clone()
@@ -19,77 +25,120 @@ pub fn run(request: &DecompressRequest) -> Result<OperationReport, NszError> {
     fs::create_dir_all(&out_dir)?;
 
     let repo_root = resolve_python_repo_root(request.python_repo_root.as_deref());
-    let mut processed_files = Vec::new();
+    let mut report = OperationReport::default();
+    report.clean_stale_temps(&out_dir)?;
 
+    let mut journal = request
+        .journal
+        .as_deref()
+        .map(|path| Journal::open(path, "decompress"))
+        .transpose()?;
     for file in &request.files {
-        match normalized_extension(file) {
-            Some("ncz") => {
-                let input = fs::read(file)?;
-                let output = crate::ncz::decompress::decompress_ncz_to_vec(&input)?;
-                let out_file = expected_decompressed_output(file, &out_dir).ok_or_else(|| {
-                    NszError::ContainerFormat {
-                        message: format!("could not resolve output path for {}", file.display()),
-                    }
-                })?;
-                fs::write(&out_file, output)?;
-                processed_files.push(out_file);
-                continue;
-            }
-            Some("nsz") => {
-                let input = fs::read(file)?;
-                let output = decompress_nsz_to_nsp(&input)?;
-                let out_file = expected_decompressed_output(file, &out_dir).ok_or_else(|| {
-                    NszError::ContainerFormat {
-                        message: format!("could not resolve output path for {}", file.display()),
-                    }
-                })?;
-                fs::write(&out_file, output)?;
-                processed_files.push(out_file);
-                continue;
-            }
-            Some("xcz") => {
-                let input = fs::read(file)?;
-                let output = decompress_xcz_to_xci(&input)?;
-                let out_file = expected_decompressed_output(file, &out_dir).ok_or_else(|| {
-                    NszError::ContainerFormat {
-                        message: format!("could not resolve output path for {}", file.display()),
-                    }
-                })?;
-                fs::write(&out_file, output)?;
-                processed_files.push(out_file);
-                continue;
+        request.cancel.check()?;
+        if let Some(output) = journal.as_ref().and_then(|journal| journal.completed(file)) {
+            report.record_resumed(file, output);
+            continue;
+        }
+        let started = Instant::now();
+        match decompress_file(file, request, &out_dir, &repo_root, journal.as_mut()) {
+            Ok(Some(out_file)) => report.record_processed(file, out_file, started),
+            Ok(None) => {}
+            Err(err) if request.continue_on_error && !err.is_cancelled() => {
+                report.record_failed(file, started, &err);
             }
-            _ => {}
+            Err(err) => return Err(err),
         }
+    }
+
+    report.finish(request.files.len())
+}
 
-        let mut args = vec![
-            "-D".to_string(),
-            "-o".to_string(),
-            out_dir.display().to_string(),
-        ];
-        if request.fix_padding {
-            args.push("-F".to_string());
+/// Decompresses one input, returning its output path when one is known.
+fn decompress_file(
+    file: &Path,
+    request: &DecompressRequest,
+    out_dir: &Path,
+    repo_root: &Path,
+    journal: Option<&mut Journal>,
+) -> Result<Option<PathBuf>, NszError> {
+    let logical = logical_path(file);
+    let output = match normalized_extension(&logical) {
+        Some("ncz") => {
+            let input = read_input(file)?;
+            let name = file.display().to_string();
+            request.progress.emit(ProgressEvent::ContainerOpened {
+                path: file,
+                entries: 1,
+            });
+            let mut progress =
+                request
+                    .progress
+                    .start_entry(&name, input.len() as u64, &request.cancel)?;
+            let output =
+                crate::ncz::decompress::decompress_ncz_with_progress(&input, &mut progress)?;
+            progress.finish();
+            output
         }
-        args.push(file.display().to_string());
+        Some("nsz") => decompress_nsz_to_nsp(file, &read_input(file)?, request)?,
+        Some("xcz") => decompress_xcz_to_xci(file, &read_input(file)?, request)?,
+        _ => {
+            let mut args = vec![
+                "-D".to_string(),
+                "-o".to_string(),
+                out_dir.display().to_string(),
+            ];
+            if request.fix_padding {
+                args.push("-F".to_string());
+            }
+            args.push(file.display().to_string());
 
-        run_nsz_cli(&repo_root, &args)?;
-        if let Some(out) = expected_decompressed_output(file, &out_dir) {
-            processed_files.push(out);
+            let output = expected_decompressed_output(file, out_dir);
+            run_fallback(
+                repo_root,
+                &args,
+                request.keys_file.as_deref(),
+                output.as_slice(),
+                &request.cancel,
+            )?;
+            return Ok(output);
+        }
+    };
+    let out_file = expected_decompressed_output(&logical, out_dir).ok_or_else(|| {
+        NszError::ContainerFormat {
+            message: format!("could not resolve output path for {}", file.display()),
         }
+    })?;
+    // Outputs are built in memory, so a cancelled input never leaves a partial file.
+    request.cancel.check()?;
+    let written = write_output(&out_file, &output, request.split_output)?;
+    if let Some(journal) = journal {
+        journal.record(file, &written, &output)?;
     }
-
-    Ok(OperationReport {
-        processed_files,
-        skipped_files: Vec::new(),
-    })
+    request.progress.emit(ProgressEvent::OutputWritten {
+        path: &written,
+        size: output.len() as u64,
+    });
+    Ok(Some(written))
 }
 
-fn decompress_nsz_to_nsp(data: &[u8]) -> Result<Vec<u8>, NszError> {
+fn decompress_nsz_to_nsp(
+    path: &Path,
+    data: &[u8],
+    request: &DecompressRequest,
+) -> Result<Vec<u8>, NszError> {
     let archive = NspArchive::from_bytes(data)?;
+    request.progress.emit(ProgressEvent::ContainerOpened {
+        path,
+        entries: archive.entries().len(),
+    });
     let mut output_entries = Vec::with_capacity(archive.entries().len());
 
     for entry in archive.entries() {
         let entry_bytes = archive.entry_bytes(data, entry);
+        let mut entry_progress =
+            request
+                .progress
+                .start_entry(&entry.name, entry.size, &request.cancel)?;
         if entry.name.to_ascii_lowercase().ends_with(".ncz") {
             let mut new_name = PathBuf::from(&entry.name);
             new_name.set_extension("nca");
@@ -99,11 +148,16 @@ fn decompress_nsz_to_nsp(data: &[u8]) -> Result<Vec<u8>, NszError> {
                     message: format!("invalid UTF-8 output name for {}", entry.name),
                 })?
                 .to_string();
-            let output = crate::ncz::decompress::decompress_ncz_to_vec(entry_bytes)?;
+            let output = crate::ncz::decompress::decompress_ncz_with_progress(
+                entry_bytes,
+                &mut entry_progress,
+            )
+            .map_err(|err| err.in_entry(&entry.name))?;
             output_entries.push((name, output));
         } else {
             output_entries.push((entry.name.clone(), entry_bytes.to_vec()));
         }
+        entry_progress.finish();
     }
 
     encode_pfs0(
@@ -113,10 +167,25 @@ fn decompress_nsz_to_nsp(data: &[u8]) -> Result<Vec<u8>, NszError> {
     )
 }
 
-fn decompress_xcz_to_xci(data: &[u8]) -> Result<Vec<u8>, NszError> {
+fn decompress_xcz_to_xci(
+    path: &Path,
+    data: &[u8],
+    request: &DecompressRequest,
+) -> Result<Vec<u8>, NszError> {
     let xci = XciArchive::from_bytes(data)?;
     let root_bytes = xci.root_hfs0_bytes(data)?;
     let root = xci.root_hfs0_archive(data)?;
+    request.progress.emit(ProgressEvent::ContainerOpened {
+        path,
+        entries: root
+            .entries()
+            .iter()
+            .filter_map(|partition| {
+                Hfs0Archive::from_bytes(root.entry_bytes(root_bytes, partition)).ok()
+            })
+            .map(|partition| partition.entries().len())
+            .sum(),
+    });
 
     let mut root_output_entries = Vec::with_capacity(root.entries().len());
     for partition in root.entries() {
@@ -126,6 +195,10 @@ fn decompress_xcz_to_xci(data: &[u8]) -> Result<Vec<u8>, NszError> {
         let mut partition_output_entries = Vec::with_capacity(partition_archive.entries().len());
         for entry in partition_archive.entries() {
             let entry_bytes = partition_archive.entry_bytes(partition_bytes, entry);
+            let mut entry_progress =
+                request
+                    .progress
+                    .start_entry(&entry.name, entry.size, &request.cancel)?;
             if entry.name.to_ascii_lowercase().ends_with(".ncz") {
                 let mut new_name = PathBuf::from(&entry.name);
                 new_name.set_extension("nca");
@@ -135,11 +208,16 @@ fn decompress_xcz_to_xci(data: &[u8]) -> Result<Vec<u8>, NszError> {
                         message: format!("invalid UTF-8 output name for {}", entry.name),
                     })?
                     .to_string();
-                let output = crate::ncz::decompress::decompress_ncz_to_vec(entry_bytes)?;
+                let output = crate::ncz::decompress::decompress_ncz_with_progress(
+                    entry_bytes,
+                    &mut entry_progress,
+                )
+                .map_err(|err| err.in_entry(&entry.name))?;
                 partition_output_entries.push((name, output));
             } else {
                 partition_output_entries.push((entry.name.clone(), entry_bytes.to_vec()));
             }
+            entry_progress.finish();
         }
 
         let partition_output = encode_hfs0(
@@ -155,7 +233,9 @@ fn decompress_xcz_to_xci(data: &[u8]) -> Result<Vec<u8>, NszError> {
         root.first_file_offset(),
         root.string_table_size(),
     )?;
-    encode_xci_like(data, &xci, &root_output)
+    let mut output = encode_xci_like(data, &xci, &root_output)?;
+    refresh_root_hfs0_header_hash(&mut output)?;
+    Ok(output)
 }
 
 fn normalized_extension(path: &Path) -> Option<&str> {
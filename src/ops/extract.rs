@@ -1,56 +1,118 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use crate::cancel::CancellationToken;
 use crate::config::ExtractRequest;
 use crate::error::NszError;
-use crate::ops::OperationReport;
-use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli};
+use crate::fs_ops::scratch::ScratchDir;
+use crate::fs_ops::split_files::{is_split_input, logical_len, logical_path, read_input};
+use crate::ops::{run_fallback, OperationReport};
+use crate::parity::python_runner::resolve_python_repo_root;
+use crate::progress::ProgressEvent;
 
 /// Runs extract flow through Python `nsz` and reports produced output paths.
+///
+/// Python does the extraction, so only [`ProgressEvent::OutputWritten`] reaches the observer.
 pub fn run(request: &ExtractRequest) -> Result<OperationReport, NszError> {
     if request.files.is_empty() {
         return Ok(OperationReport::default());
     }
+    crate::ops::check_keys_file(request.keys_file.as_deref())?;
 
     if let Some(out_dir) = &request.output_dir {
         fs::create_dir_all(out_dir)?;
     }
 
     let repo_root = resolve_python_repo_root(request.python_repo_root.as_deref());
-    let mut args = vec!["-x".to_string()];
-    if let Some(out_dir) = &request.output_dir {
-        args.push("-o".to_string());
-        args.push(out_dir.display().to_string());
-    }
-    if let Some(extract_regex) = &request.extract_regex {
-        args.push("--extractregex".to_string());
-        args.push(extract_regex.clone());
+    let started = Instant::now();
+    let mut report = OperationReport::default();
+    for (target, files) in group_by_target(&request.files, request.output_dir.as_deref()) {
+        // Always pass `-o`: Python would otherwise extract next to the staged copy of a
+        // split input, inside the scratch dir removed below.
+        let mut args = vec![
+            "-x".to_string(),
+            "-o".to_string(),
+            target.display().to_string(),
+        ];
+        if let Some(extract_regex) = &request.extract_regex {
+            args.push("--extractregex".to_string());
+            args.push(extract_regex.clone());
+        }
+
+        let out_dirs: Vec<PathBuf> = files
+            .iter()
+            .map(|file| target.join(logical_path(file).file_stem().unwrap_or_default()))
+            .collect();
+        let scratch = ScratchDir::new("split")?;
+        stage_inputs(&files, scratch.path(), &mut args, &request.cancel)?;
+        run_fallback(
+            &repo_root,
+            &args,
+            request.keys_file.as_deref(),
+            &out_dirs,
+            &request.cancel,
+        )?;
+        drop(scratch);
+
+        for (file, out_dir) in files.into_iter().zip(out_dirs) {
+            if out_dir.is_dir() {
+                request.progress.emit(ProgressEvent::OutputWritten {
+                    path: &out_dir,
+                    size: logical_len(&out_dir)?,
+                });
+                report.record_processed(file, out_dir, started);
+            } else {
+                report.record_handled(file, started);
+            }
+        }
     }
+    Ok(report)
+}
 
-    for file in &request.files {
-        args.push(file.display().to_string());
+/// Groups `files` by the folder Python `nsz` extracts them under: `output_dir`, or the
+/// folder holding each input as the user named it, in first-seen order.
+fn group_by_target<'a>(
+    files: &'a [PathBuf],
+    output_dir: Option<&Path>,
+) -> Vec<(PathBuf, Vec<&'a PathBuf>)> {
+    let mut groups: Vec<(PathBuf, Vec<&PathBuf>)> = Vec::new();
+    for file in files {
+        let target = output_dir.map_or_else(
+            || {
+                logical_path(file)
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new("."))
+                    .to_path_buf()
+            },
+            Path::to_path_buf,
+        );
+        match groups.iter_mut().find(|(existing, _)| *existing == target) {
+            Some((_, group)) => group.push(file),
+            None => groups.push((target, vec![file])),
+        }
     }
+    groups
+}
 
-    run_nsz_cli(&repo_root, &args)?;
-
-    let processed_files = request
-        .files
-        .iter()
-        .map(|file| {
-            let stem = file.file_stem().unwrap_or_default();
-            request.output_dir.as_ref().map_or_else(
-                || {
-                    file.parent()
-                        .unwrap_or_else(|| std::path::Path::new("."))
-                        .join(stem)
-                },
-                |out_dir| out_dir.join(stem),
-            )
-        })
-        .collect::<Vec<PathBuf>>();
-
-    Ok(OperationReport {
-        processed_files,
-        skipped_files: Vec::new(),
-    })
+/// Appends the inputs to `args`. Python `nsz` reads single files only, so split inputs are
+/// joined into a copy under `scratch`.
+fn stage_inputs(
+    files: &[&PathBuf],
+    scratch: &Path,
+    args: &mut Vec<String>,
+    cancel: &CancellationToken,
+) -> Result<(), NszError> {
+    for file in files {
+        cancel.check()?;
+        if is_split_input(file) {
+            let joined = scratch.join(logical_path(file).file_name().unwrap_or_default());
+            fs::write(&joined, read_input(file)?)?;
+            args.push(joined.display().to_string());
+        } else {
+            args.push(file.display().to_string());
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::MergeRequest;
+use crate::container::cnmt::Cnmt;
+use crate::container::hfs0::encode_hfs0;
+use crate::container::nsp::{encode_new_pfs0, NspArchive};
+use crate::container::xci::encode_xci;
+use crate::crypto::keys::hex_string;
+use crate::crypto::keystore::KeyStore;
+use crate::error::NszError;
+use crate::fs_ops::atomic_write::write_atomic;
+use crate::ops::package::{
+    collect_ticket_files, content_id_of, is_content, package_with_tickets, PackageEntry,
+};
+use crate::ops::OperationReport;
+
+/// Merges base, update and DLC packages into one multi-title container.
+///
+/// NCAs/NCZs are deduplicated by content ID. When several CNMTs describe the same title,
+/// only the highest version is kept, together with the contents it lists.
+pub fn run(request: &MergeRequest) -> Result<OperationReport, NszError> {
+    let output_file = request
+        .output_file
+        .as_ref()
+        .ok_or_else(|| NszError::ContainerFormat {
+            message: "merge request missing output file".to_string(),
+        })?;
+    let as_xci = output_is_xci(output_file)?;
+    let key_store =
+        KeyStore::resolve(request.keys_file.as_deref())?.ok_or_else(|| {
+            NszError::MissingRequiredKey {
+                key: "header_key (merging reads CNMTs to pick title versions; set keys_file or NSZ_KEYS_FILE)"
+                    .to_string(),
+            }
+        })?;
+    let keys = key_store.nca_keys();
+    let title_keys = crate::ops::compress::resolve_title_keys(request.title_keys_file.as_deref())?;
+
+    let started = Instant::now();
+    let mut report = OperationReport::default();
+    let mut inputs = Vec::new();
+    for file in &request.files {
+        if is_nsp_like(file) {
+            inputs.push((file, fs::read(file)?));
+        } else {
+            report.record_skipped(file);
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut seen = BTreeSet::new();
+    for (file, data) in &inputs {
+        let archive = NspArchive::from_bytes(data).map_err(|err| NszError::ContainerFormat {
+            message: format!("failed to parse {} as PFS0: {err}", file.display()),
+        })?;
+        for entry in archive.entries() {
+            let key = if is_content(&entry.name) {
+                content_id_of(&entry.name)
+            } else if entry.name.to_ascii_lowercase().ends_with(".tik")
+                || entry.name.to_ascii_lowercase().ends_with(".cert")
+            {
+                entry.name.to_ascii_lowercase()
+            } else {
+                continue;
+            };
+            if seen.insert(key) {
+                entries.push(PackageEntry {
+                    name: entry.name.clone(),
+                    bytes: archive.entry_bytes(data, entry),
+                });
+            }
+        }
+    }
+
+    let ticket_files = collect_ticket_files(&entries);
+    let tickets = ticket_files
+        .tickets
+        .iter()
+        .map(|(rights_id, (record, _))| (*rights_id, record.clone()))
+        .collect::<HashMap<_, _>>();
+    let by_id = entries
+        .iter()
+        .filter(|entry| is_content(&entry.name))
+        .map(|entry| (content_id_of(&entry.name), entry))
+        .collect::<HashMap<_, _>>();
+
+    // Highest version per title wins; ties keep the first meta seen.
+    let mut titles: BTreeMap<u64, (Cnmt, &PackageEntry<'_>)> = BTreeMap::new();
+    for meta in entries
+        .iter()
+        .filter(|entry| entry.name.to_ascii_lowercase().ends_with(".cnmt.nca"))
+    {
+        let cnmt = Cnmt::from_meta_nca(meta.bytes, keys, &tickets)
+            .map_err(|err| err.in_entry(&meta.name))?;
+        match titles.get(&cnmt.title_id) {
+            Some((kept, _)) if kept.version >= cnmt.version => {}
+            _ => {
+                titles.insert(cnmt.title_id, (cnmt, meta));
+            }
+        }
+    }
+
+    let mut contents = Vec::new();
+    let mut included = BTreeSet::new();
+    for (cnmt, meta) in titles.values() {
+        for record in &cnmt.contents {
+            let id = hex_string(&record.content_id);
+            let entry = by_id.get(&id).ok_or_else(|| NszError::ContainerFormat {
+                message: format!(
+                    "content {id} of title {:016X} v{} is missing from the merge inputs",
+                    cnmt.title_id, cnmt.version
+                ),
+            })?;
+            if included.insert(id) {
+                contents.push(*entry);
+            }
+        }
+        contents.push(*meta);
+    }
+
+    let package = package_with_tickets(&contents, keys, &ticket_files, &title_keys)?;
+    let output = if as_xci {
+        let secure = encode_hfs0(&package, hfs0_header_size(&package), 0)?;
+        encode_xci(&secure)?
+    } else {
+        encode_new_pfs0(&package)?
+    };
+    if let Some(parent) = output_file.parent() {
+        fs::create_dir_all(parent)?;
+        report.clean_stale_temps(parent)?;
+    }
+    write_atomic(output_file, &output)?;
+
+    let merged = inputs
+        .iter()
+        .map(|(file, _)| (*file).clone())
+        .collect::<Vec<_>>();
+    report.record_merged(&merged, output_file.clone(), started);
+    Ok(report)
+}
+
+/// Size of an HFS0 header naming `entries`, rounded up to a media unit.
+fn hfs0_header_size(entries: &[(String, Vec<u8>)]) -> u64 {
+    let names = entries
+        .iter()
+        .map(|(name, _)| name.len() + 1)
+        .sum::<usize>();
+    (0x10 + entries.len() * 0x40 + names).next_multiple_of(0x200) as u64
+}
+
+fn output_is_xci(output_file: &Path) -> Result<bool, NszError> {
+    match output_file.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("nsp") || ext.eq_ignore_ascii_case("nsz") => {
+            Ok(false)
+        }
+        Some(ext) if ext.eq_ignore_ascii_case("xci") || ext.eq_ignore_ascii_case("xcz") => Ok(true),
+        _ => Err(NszError::UnsupportedFeature {
+            feature: format!(
+                "merge output {} (expected .nsp, .nsz, .xci or .xcz)",
+                output_file.display()
+            ),
+        }),
+    }
+}
+
+fn is_nsp_like(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("nsp") || ext.eq_ignore_ascii_case("nsz"))
+}
@@ -1,25 +1,223 @@
 pub mod compress;
+pub mod convert;
 pub mod create;
 pub mod decompress;
 pub mod extract;
+pub mod merge;
+mod package;
+pub mod split;
 pub mod titlekeys;
+pub mod trim;
 pub mod undupe;
 pub mod verify;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancellationToken;
+use crate::container::xci::XciSignatureStatus;
+use crate::crypto::keystore::KeyStore;
+use crate::error::NszError;
+use crate::fs_ops::atomic_write::clean_stale_temps;
+use crate::fs_ops::split_files::remove_output;
+use crate::parity::python_runner::run_nsz_cli_with_keys;
+use crate::report::size_ratio;
+pub use crate::report::{
+    EntryReport, EntryStatus, ErrorDetails, FileReport, FileStatus, VerifySummary, VerifyVerdict,
+};
 
 /// Common report returned by non-verify operations.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OperationReport {
     /// Files successfully processed by the operation.
     pub processed_files: Vec<PathBuf>,
     /// Files intentionally skipped by the operation.
     pub skipped_files: Vec<PathBuf>,
+    /// Per-file outcome of every input, in processing order.
+    pub files: Vec<FileReport>,
+    /// Staging files of interrupted earlier runs, removed from the output directories
+    /// before this run wrote anything.
+    pub removed_temp_files: Vec<PathBuf>,
+    /// Inputs skipped because the job journal records them as done by an earlier run.
+    pub resumed_files: Vec<PathBuf>,
+}
+
+impl OperationReport {
+    /// Removes staging files earlier runs left in the output directory `dir`.
+    pub(crate) fn clean_stale_temps(&mut self, dir: &Path) -> Result<(), NszError> {
+        self.removed_temp_files.extend(clean_stale_temps(dir)?);
+        Ok(())
+    }
+
+    /// Records `output` as written from `input` since `started`.
+    pub(crate) fn record_processed(&mut self, input: &Path, output: PathBuf, started: Instant) {
+        self.files.push(FileReport::new(
+            input,
+            Some(&output),
+            FileStatus::Processed,
+            started,
+        ));
+        self.processed_files.push(output);
+    }
+
+    /// Records `input` as finished by an earlier run that wrote `output`.
+    pub(crate) fn record_resumed(&mut self, input: &Path, output: &Path) {
+        self.files.push(FileReport::new(
+            input,
+            Some(output),
+            FileStatus::Resumed,
+            Instant::now(),
+        ));
+        self.resumed_files.push(input.to_path_buf());
+    }
+
+    /// Records the dry-run estimate of compressing `input` to `output` in `duration`.
+    pub(crate) fn record_estimated(
+        &mut self,
+        input: &Path,
+        output: PathBuf,
+        output_size: u64,
+        duration: Duration,
+        started: Instant,
+    ) {
+        let mut file = FileReport::new(input, None, FileStatus::Estimated, started);
+        file.output = Some(output);
+        file.output_size = Some(output_size);
+        file.ratio = size_ratio(file.input_size, file.output_size);
+        file.estimated_duration = Some(duration);
+        self.files.push(file);
+    }
+
+    /// Records `output` as written from all of `inputs` together, so per-input output sizes
+    /// and ratios are left unset.
+    pub(crate) fn record_merged(&mut self, inputs: &[PathBuf], output: PathBuf, started: Instant) {
+        for input in inputs {
+            let mut file = FileReport::new(input, Some(&output), FileStatus::Processed, started);
+            file.output_size = None;
+            file.ratio = None;
+            self.files.push(file);
+        }
+        self.processed_files.push(output);
+    }
+
+    /// Records `input` as handled by an operation that writes no per-input output.
+    pub(crate) fn record_handled(&mut self, input: &Path, started: Instant) {
+        self.files
+            .push(FileReport::new(input, None, FileStatus::Processed, started));
+        self.processed_files.push(input.to_path_buf());
+    }
+
+    /// Records `input` as intentionally left alone.
+    pub(crate) fn record_skipped(&mut self, input: &Path) {
+        self.files.push(FileReport::new(
+            input,
+            None,
+            FileStatus::Skipped,
+            Instant::now(),
+        ));
+        self.skipped_files.push(input.to_path_buf());
+    }
+
+    /// Records `input` as failed with `err`, for batches that continue past errors.
+    pub(crate) fn record_failed(&mut self, input: &Path, started: Instant, err: &NszError) {
+        self.files
+            .push(FileReport::failed(input, started.elapsed(), err));
+    }
+
+    /// Ends a batch of `total` inputs, turning recorded failures into [`NszError::BatchFailed`].
+    pub(crate) fn finish(self, total: usize) -> Result<Self, NszError> {
+        batch_failure(&self.files, total).map_or(Ok(self), Err)
+    }
 }
 
 /// Report returned by verify operations.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct VerifyReport {
     /// Files successfully verified by the operation.
     pub verified_files: Vec<PathBuf>,
+    /// Card header signature status of each verified `.xci`/`.xcz`.
+    pub header_signatures: Vec<(PathBuf, XciSignatureStatus)>,
+    /// Per-file outcome of every input, in processing order.
+    pub files: Vec<FileReport>,
+}
+
+impl VerifyReport {
+    /// Records `input` as verified since `started`, with the results of its NCAs/NCZs.
+    pub(crate) fn record_verified(
+        &mut self,
+        input: &Path,
+        started: Instant,
+        entries: Vec<EntryReport>,
+    ) {
+        let mut file = FileReport::new(input, None, FileStatus::Verified, started);
+        file.entries = entries;
+        self.files.push(file);
+        self.verified_files.push(input.to_path_buf());
+    }
+
+    /// Records `input` as failed with `err`, for batches that continue past errors.
+    pub(crate) fn record_failed(
+        &mut self,
+        input: &Path,
+        started: Instant,
+        err: &NszError,
+        entries: Vec<EntryReport>,
+    ) {
+        let mut file = FileReport::failed(input, started.elapsed(), err);
+        file.entries = entries;
+        self.files.push(file);
+    }
+
+    /// Counts the verified inputs by [`VerifyVerdict`].
+    pub fn summary(&self) -> VerifySummary {
+        VerifySummary::from_files(&self.files)
+    }
+
+    /// Ends a batch of `total` inputs, turning recorded failures into [`NszError::BatchFailed`].
+    pub(crate) fn finish(self, total: usize) -> Result<Self, NszError> {
+        batch_failure(&self.files, total).map_or(Ok(self), Err)
+    }
+}
+
+fn batch_failure(files: &[FileReport], total: usize) -> Option<NszError> {
+    let failed = files
+        .iter()
+        .filter(|file| file.status == FileStatus::Failed)
+        .count();
+    (failed > 0).then(|| NszError::BatchFailed {
+        failed,
+        total,
+        files: files.to_vec(),
+    })
+}
+
+/// Loads an explicitly requested keys file so a bad path or malformed file fails before any work.
+pub(crate) fn check_keys_file(path: Option<&Path>) -> Result<(), NszError> {
+    if let Some(path) = path {
+        KeyStore::from_file(path)?;
+    }
+    Ok(())
+}
+
+/// Runs the Python `nsz` fallback with `keys_file`, removing those of `outputs` it created
+/// when cancelled since they may be half-written.
+pub(crate) fn run_fallback(
+    repo_root: &Path,
+    args: &[String],
+    keys_file: Option<&Path>,
+    outputs: &[PathBuf],
+    cancel: &CancellationToken,
+) -> Result<(), NszError> {
+    let fresh: Vec<&PathBuf> = outputs.iter().filter(|path| !path.exists()).collect();
+    match run_nsz_cli_with_keys(repo_root, args, keys_file, cancel) {
+        Err(err) if err.is_cancelled() => {
+            for path in fresh {
+                remove_output(path)?;
+            }
+            Err(err)
+        }
+        result => result,
+    }
 }
@@ -0,0 +1,203 @@
+//! NSP packaging shared by operations that regroup container entries by title.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::container::cnmt::Cnmt;
+use crate::container::hfs0::Hfs0Archive;
+use crate::container::nca::{
+    build_common_ticket, parse_ticket_record, read_rights_id, NcaKeySet, TicketRecord,
+};
+use crate::container::nsp::NspArchive;
+use crate::container::xci::XciArchive;
+use crate::crypto::keys::hex_string;
+use crate::error::NszError;
+
+/// Named files of one output package, in write order.
+pub type Package = Vec<(String, Vec<u8>)>;
+
+/// One named file of a source container.
+pub struct PackageEntry<'a> {
+    pub name: String,
+    pub bytes: &'a [u8],
+}
+
+/// Tickets and certificates carried by a source container, by rights ID.
+pub struct TicketFiles<'a> {
+    pub tickets: HashMap<[u8; 16], (TicketRecord, &'a PackageEntry<'a>)>,
+    pub certs: HashMap<String, &'a PackageEntry<'a>>,
+    pub any_cert: Option<&'a PackageEntry<'a>>,
+}
+
+/// Packages each title listed by a CNMT among `entries` into its own NSP.
+///
+/// Every content entry must belong to some title; entries keep their stored bytes, so
+/// NCZs are copied without recompression.
+pub fn split_by_title(
+    entries: &[PackageEntry<'_>],
+    keys: &NcaKeySet,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+) -> Result<Vec<(u64, Package)>, NszError> {
+    let ticket_files = collect_ticket_files(entries);
+    let tickets = ticket_files
+        .tickets
+        .iter()
+        .map(|(rights_id, (record, _))| (*rights_id, record.clone()))
+        .collect::<HashMap<_, _>>();
+    let by_id = entries
+        .iter()
+        .filter(|entry| is_content(&entry.name))
+        .map(|entry| (content_id_of(&entry.name), entry))
+        .collect::<HashMap<_, _>>();
+
+    let mut claimed = BTreeSet::new();
+    let mut packages = Vec::new();
+    for meta in entries
+        .iter()
+        .filter(|entry| entry.name.to_ascii_lowercase().ends_with(".cnmt.nca"))
+    {
+        let cnmt = Cnmt::from_meta_nca(meta.bytes, keys, &tickets)
+            .map_err(|err| err.in_entry(&meta.name))?;
+        let mut contents = Vec::with_capacity(cnmt.contents.len() + 1);
+        for record in &cnmt.contents {
+            let id = hex_string(&record.content_id);
+            let entry = by_id.get(&id).ok_or_else(|| NszError::ContainerFormat {
+                message: format!(
+                    "content {id} of title {:016X} is missing from the container",
+                    cnmt.title_id
+                ),
+            })?;
+            claimed.insert(entry.name.clone());
+            contents.push(*entry);
+        }
+        claimed.insert(meta.name.clone());
+        contents.push(meta);
+        packages.push((
+            cnmt.title_id,
+            package_with_tickets(&contents, keys, &ticket_files, title_keys)?,
+        ));
+    }
+
+    if let Some(orphan) = by_id.values().find(|entry| !claimed.contains(&entry.name)) {
+        return Err(NszError::ContainerFormat {
+            message: format!("container entry {} is not listed by any CNMT", orphan.name),
+        });
+    }
+    Ok(packages)
+}
+
+/// Lists the files of an XCI/XCZ secure partition.
+pub fn secure_partition_entries(data: &[u8]) -> Result<Vec<PackageEntry<'_>>, NszError> {
+    let xci = XciArchive::from_bytes(data)?;
+    let root_bytes = xci.root_hfs0_bytes(data)?;
+    let root = xci.root_hfs0_archive(data)?;
+    let secure = root
+        .entries()
+        .iter()
+        .find(|entry| entry.name == "secure")
+        .ok_or_else(|| NszError::ContainerFormat {
+            message: "no secure partition".to_string(),
+        })?;
+    let secure_bytes = root.entry_bytes(root_bytes, secure);
+    let secure_archive = Hfs0Archive::from_bytes(secure_bytes)?;
+    Ok(secure_archive
+        .entries()
+        .iter()
+        .map(|entry| PackageEntry {
+            name: entry.name.clone(),
+            bytes: secure_archive.entry_bytes(secure_bytes, entry),
+        })
+        .collect())
+}
+
+/// Lists the files of an NSP/NSZ.
+pub fn pfs0_entries(data: &[u8]) -> Result<Vec<PackageEntry<'_>>, NszError> {
+    let archive = NspArchive::from_bytes(data)?;
+    Ok(archive
+        .entries()
+        .iter()
+        .map(|entry| PackageEntry {
+            name: entry.name.clone(),
+            bytes: archive.entry_bytes(data, entry),
+        })
+        .collect())
+}
+
+/// Appends the tickets and certificates needed by the rights IDs of `contents`.
+pub fn package_with_tickets(
+    contents: &[&PackageEntry<'_>],
+    keys: &NcaKeySet,
+    ticket_files: &TicketFiles<'_>,
+    title_keys: &HashMap<[u8; 16], TicketRecord>,
+) -> Result<Package, NszError> {
+    let mut rights_ids = BTreeSet::new();
+    for entry in contents {
+        if let Some(rights_id) = read_rights_id(entry.bytes, &keys.header_key)
+            .map_err(|err| err.in_entry(&entry.name))?
+        {
+            rights_ids.insert(rights_id);
+        }
+    }
+
+    let mut package = contents
+        .iter()
+        .map(|entry| (entry.name.clone(), entry.bytes.to_vec()))
+        .collect::<Vec<_>>();
+    for rights_id in rights_ids {
+        let stem = hex_string(&rights_id);
+        if let Some((_, ticket)) = ticket_files.tickets.get(&rights_id) {
+            package.push((ticket.name.clone(), ticket.bytes.to_vec()));
+        } else {
+            let record = title_keys
+                .get(&rights_id)
+                .ok_or_else(|| NszError::ContainerFormat {
+                    message: format!("missing ticket or title.keys entry for rights id {stem}"),
+                })?;
+            package.push((format!("{stem}.tik"), build_common_ticket(record)));
+        }
+        // Certificate chains are identical for every title, so any cert will do.
+        let cert = ticket_files
+            .certs
+            .get(&stem)
+            .copied()
+            .or(ticket_files.any_cert)
+            .ok_or_else(|| NszError::ContainerFormat {
+                message: format!("no certificate chain available for rights id {stem}"),
+            })?;
+        package.push((format!("{stem}.cert"), cert.bytes.to_vec()));
+    }
+    Ok(package)
+}
+
+pub fn collect_ticket_files<'a>(entries: &'a [PackageEntry<'a>]) -> TicketFiles<'a> {
+    let mut tickets = HashMap::new();
+    let mut certs = HashMap::new();
+    for entry in entries {
+        if entry.name.to_ascii_lowercase().ends_with(".tik") {
+            if let Ok(record) = parse_ticket_record(entry.bytes) {
+                tickets.insert(record.rights_id, (record, entry));
+            }
+        } else if let Some(stem) = entry.name.to_ascii_lowercase().strip_suffix(".cert") {
+            certs.insert(stem.to_string(), entry);
+        }
+    }
+    let any_cert = entries
+        .iter()
+        .find(|entry| entry.name.to_ascii_lowercase().ends_with(".cert"));
+    TicketFiles {
+        tickets,
+        certs,
+        any_cert,
+    }
+}
+
+pub fn is_content(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".nca") || name.to_ascii_lowercase().ends_with(".ncz")
+}
+
+/// Returns the lowercase content ID an NCA/NCZ entry is named after.
+pub fn content_id_of(name: &str) -> String {
+    name.split('.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
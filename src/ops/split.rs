@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::SplitRequest;
+use crate::container::nsp::encode_new_pfs0;
+use crate::crypto::keystore::KeyStore;
+use crate::error::NszError;
+use crate::fs_ops::atomic_write::write_atomic;
+use crate::ops::package::{pfs0_entries, secure_partition_entries, split_by_title};
+use crate::ops::OperationReport;
+
+/// Splits multi-title containers into one `.nsp`/`.nsz` per CNMT.
+///
+/// Each output holds the title's contents, its meta NCA and the tickets and certificates
+/// its rights IDs need. NCZs are copied as stored, so `.nsz`/`.xcz` inputs give `.nsz` outputs.
+pub fn run(request: &SplitRequest) -> Result<OperationReport, NszError> {
+    if request.files.is_empty() {
+        return Ok(OperationReport::default());
+    }
+
+    let out_dir = request
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("nsz-rs-out"));
+    fs::create_dir_all(&out_dir)?;
+
+    let key_store = KeyStore::resolve(request.keys_file.as_deref())?.ok_or_else(|| {
+        NszError::MissingRequiredKey {
+            key: "header_key (splitting by title reads CNMTs; set keys_file or NSZ_KEYS_FILE)"
+                .to_string(),
+        }
+    })?;
+    let keys = key_store.nca_keys();
+    let title_keys = crate::ops::compress::resolve_title_keys(request.title_keys_file.as_deref())?;
+
+    let mut report = OperationReport::default();
+    report.clean_stale_temps(&out_dir)?;
+    for file in &request.files {
+        let started = Instant::now();
+        let Some((from_xci, extension)) = input_layout(file) else {
+            report.record_skipped(file);
+            continue;
+        };
+        let input = fs::read(file)?;
+        let entries = if from_xci {
+            secure_partition_entries(&input)
+        } else {
+            pfs0_entries(&input)
+        }
+        .map_err(|err| match err {
+            NszError::ContainerFormat { message } => NszError::ContainerFormat {
+                message: format!("{}: {message}", file.display()),
+            },
+            other => other,
+        })?;
+
+        let stem = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        for (title_id, package) in split_by_title(&entries, keys, &title_keys)? {
+            let out_file = out_dir.join(format!("{stem} [{title_id:016X}].{extension}"));
+            write_atomic(&out_file, &encode_new_pfs0(&package)?)?;
+            report.record_processed(file, out_file, started);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Returns whether the input is a gamecard image and the extension its outputs take.
+fn input_layout(input: &Path) -> Option<(bool, &'static str)> {
+    match input.extension()?.to_str()? {
+        ext if ext.eq_ignore_ascii_case("nsp") => Some((false, "nsp")),
+        ext if ext.eq_ignore_ascii_case("nsz") => Some((false, "nsz")),
+        ext if ext.eq_ignore_ascii_case("xci") => Some((true, "nsp")),
+        ext if ext.eq_ignore_ascii_case("xcz") => Some((true, "nsz")),
+        _ => None,
+    }
+}
@@ -1,13 +1,17 @@
+use std::time::Instant;
+
+use crate::cancel::CancellationToken;
 use crate::config::TitleKeysRequest;
 use crate::error::NszError;
 use crate::ops::OperationReport;
-use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli};
+use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli_with_keys};
 
 /// Runs title key extraction through Python `nsz`.
 pub fn run(request: &TitleKeysRequest) -> Result<OperationReport, NszError> {
     if request.files.is_empty() {
         return Ok(OperationReport::default());
     }
+    crate::ops::check_keys_file(request.keys_file.as_deref())?;
 
     let repo_root = resolve_python_repo_root(request.python_repo_root.as_deref());
     let mut args = vec!["--titlekeys".to_string()];
@@ -15,10 +19,17 @@ pub fn run(request: &TitleKeysRequest) -> Result<OperationReport, NszError> {
         args.push(file.display().to_string());
     }
 
-    run_nsz_cli(&repo_root, &args)?;
+    let started = Instant::now();
+    run_nsz_cli_with_keys(
+        &repo_root,
+        &args,
+        request.keys_file.as_deref(),
+        &CancellationToken::default(),
+    )?;
 
-    Ok(OperationReport {
-        processed_files: request.files.clone(),
-        skipped_files: Vec::new(),
-    })
+    let mut report = OperationReport::default();
+    for file in &request.files {
+        report.record_handled(file, started);
+    }
+    Ok(report)
 }
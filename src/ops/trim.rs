@@ -0,0 +1,75 @@
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::config::TrimRequest;
+use crate::container::xci::{trim_xci, untrim_padding_len, XCI_PADDING_BYTE};
+use crate::error::NszError;
+use crate::fs_ops::atomic_write::{write_atomic, write_atomic_with};
+use crate::ops::OperationReport;
+
+/// Chunk of padding written at a time; the input itself is streamed, so untrimming never
+/// holds a full card image.
+const PADDING_CHUNK_SIZE: usize = 0x10_0000;
+
+/// Cuts `.xci` images down to the used data size declared by their card headers.
+pub fn run(request: &TrimRequest) -> Result<OperationReport, NszError> {
+    rewrite_each(request, |file, out_file| {
+        write_atomic(out_file, trim_xci(&fs::read(file)?)?)
+    })
+}
+
+/// Restores `0xFF` padding on `.xci` images up to the full card size from their ROM size byte.
+pub fn run_untrim(request: &TrimRequest) -> Result<OperationReport, NszError> {
+    rewrite_each(request, |file, out_file| {
+        let mut input = File::open(file)?;
+        let mut remaining = untrim_padding_len(&mut input)?;
+        let size = input.seek(SeekFrom::End(0))? + remaining;
+        input.seek(SeekFrom::Start(0))?;
+        write_atomic_with(out_file, size, |writer| {
+            io::copy(&mut input, writer)?;
+            let chunk = vec![XCI_PADDING_BYTE; PADDING_CHUNK_SIZE];
+            while remaining > 0 {
+                let len = remaining.min(PADDING_CHUNK_SIZE as u64) as usize;
+                writer.write_all(&chunk[..len])?;
+                remaining -= len as u64;
+            }
+            Ok(())
+        })
+    })
+}
+
+fn rewrite_each(
+    request: &TrimRequest,
+    rewrite: impl Fn(&Path, &Path) -> Result<(), NszError>,
+) -> Result<OperationReport, NszError> {
+    if request.files.is_empty() {
+        return Ok(OperationReport::default());
+    }
+
+    let out_dir = request
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("nsz-rs-out"));
+    fs::create_dir_all(&out_dir)?;
+
+    let mut report = OperationReport::default();
+    report.clean_stale_temps(&out_dir)?;
+    for file in &request.files {
+        let started = Instant::now();
+        let is_xci = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xci"));
+        let Some(file_name) = file.file_name().filter(|_| is_xci) else {
+            report.record_skipped(file);
+            continue;
+        };
+        let out_file: PathBuf = out_dir.join(file_name);
+        rewrite(file, &out_file)?;
+        report.record_processed(file, out_file, started);
+    }
+
+    Ok(report)
+}
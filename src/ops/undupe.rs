@@ -1,4 +1,5 @@
 use std::fs;
+use std::time::Instant;
 
 use crate::config::UndupeRequest;
 use crate::error::NszError;
@@ -52,10 +53,12 @@ pub fn run(request: &UndupeRequest) -> Result<OperationReport, NszError> {
         args.push(file.display().to_string());
     }
 
+    let started = Instant::now();
     run_nsz_cli(&repo_root, &args)?;
 
-    Ok(OperationReport {
-        processed_files: request.files.clone(),
-        skipped_files: Vec::new(),
-    })
+    let mut report = OperationReport::default();
+    for file in &request.files {
+        report.record_handled(file, started);
+    }
+    Ok(report)
 }
@@ -1,176 +1,359 @@
 use sha2::{Digest, Sha256};
 use std::path::Path;
+use std::time::Instant;
 
 use crate::config::VerifyRequest;
 use crate::container::hfs0::Hfs0Archive;
+use crate::container::nca::{read_rights_id, NcaKeySet};
 use crate::container::nsp::NspArchive;
-use crate::container::xci::XciArchive;
+use crate::container::xci::{XciArchive, XciSignatureStatus};
+use crate::crypto::keys::hex_string;
+use crate::crypto::keystore::KeyStore;
 use crate::error::NszError;
-use crate::ops::VerifyReport;
-use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli};
+use crate::fs_ops::split_files::{logical_path, read_input};
+use crate::ops::{EntryReport, EntryStatus, ErrorDetails, VerifyReport};
+use crate::parity::python_runner::{resolve_python_repo_root, run_nsz_cli_with_keys};
+use crate::progress::{EntryProgress, ProgressEvent};
+
+const HASH_CHUNK_SIZE: usize = 0x0100_0000;
+/// Entry name reporting the check of an `.xci` root HFS0 header against its card header.
+const ROOT_HFS0_HEADER_ENTRY: &str = "root HFS0 header";
 
 /// Verifies supported inputs natively and falls back to Python `nsz` for unknown formats.
+///
+/// Every NCA/NCZ of an input is checked before a corrupt one fails it, so a report kept
+/// with `continue_on_error` lists all entries of failed inputs too.
 pub fn run(request: &VerifyRequest) -> Result<VerifyReport, NszError> {
+    let key_store = KeyStore::resolve(request.keys_file.as_deref())?;
+    let keys = key_store.as_ref().map(KeyStore::nca_keys);
+    let header_modulus = key_store
+        .as_ref()
+        .and_then(|store| store.key_file().get("xci_header_sign_modulus"));
+
     let repo_root = resolve_python_repo_root(request.python_repo_root.as_deref());
-    let mut verified_files = Vec::new();
+    let mut report = VerifyReport::default();
 
     for file in &request.files {
-        match normalized_extension(file) {
-            Some("nca") => {
-                if !is_cnmt_nca_name(file) {
-                    let input = std::fs::read(file)?;
-                    verify_hash_against_stem(file, &input)?;
+        request.cancel.check()?;
+        let started = Instant::now();
+        match verify_file(file, request, keys, header_modulus.as_ref(), &repo_root) {
+            Ok(Checked {
+                entries,
+                signature,
+                corruption: None,
+            }) => {
+                if let Some(status) = signature {
+                    report.header_signatures.push((file.clone(), status));
                 }
-                verified_files.push(file.clone());
-                continue;
-            }
-            Some("ncz") => {
-                let input = std::fs::read(file)?;
-                let decompressed = crate::ncz::decompress::decompress_ncz_to_vec(&input)?;
-                verify_hash_against_stem(file, &decompressed)?;
-                verified_files.push(file.clone());
-                continue;
+                report.record_verified(file, started, entries);
             }
-            Some("nsp") => {
-                let input = std::fs::read(file)?;
-                verify_nsp_like_container(&input, false)?;
-                verified_files.push(file.clone());
-                continue;
+            Ok(Checked {
+                entries,
+                corruption: Some(err),
+                ..
+            }) if request.continue_on_error => {
+                report.record_failed(file, started, &err, entries);
             }
-            Some("nsz") => {
-                let input = std::fs::read(file)?;
-                verify_nsp_like_container(&input, true)?;
-                verified_files.push(file.clone());
-                continue;
+            Err(err) if request.continue_on_error && !err.is_cancelled() => {
+                report.record_failed(file, started, &err, Vec::new());
             }
-            Some("xci") => {
-                let input = std::fs::read(file)?;
-                verify_xci_like_container(&input, false)?;
-                verified_files.push(file.clone());
-                continue;
-            }
-            Some("xcz") => {
-                let input = std::fs::read(file)?;
-                verify_xci_like_container(&input, true)?;
-                verified_files.push(file.clone());
-                continue;
+            Ok(Checked {
+                corruption: Some(err),
+                ..
+            })
+            | Err(err) => return Err(err),
+        }
+    }
+
+    report.finish(request.files.len())
+}
+
+/// What verifying one input found.
+#[derive(Default)]
+struct Checked {
+    entries: Vec<EntryReport>,
+    /// Card header signature status of `.xci`/`.xcz` inputs.
+    signature: Option<XciSignatureStatus>,
+    /// Error of the first corrupt entry, which fails the input.
+    corruption: Option<NszError>,
+}
+
+impl Checked {
+    /// Checks a container entry, attaching its name to the first corruption found.
+    fn check_entry(
+        &mut self,
+        name: &str,
+        partition: Option<&str>,
+        bytes: &[u8],
+        keys: Option<&NcaKeySet>,
+        request: &VerifyRequest,
+    ) -> Result<(), NszError> {
+        let mut entry_progress =
+            request
+                .progress
+                .start_entry(name, bytes.len() as u64, &request.cancel)?;
+        if let Some(compressed) = content_kind(Path::new(name)) {
+            let (entry, err) = check_content(
+                name,
+                partition,
+                bytes,
+                compressed,
+                keys,
+                &mut entry_progress,
+            )?;
+            if self.corruption.is_none() {
+                self.corruption = err.map(|err| err.in_entry(name));
             }
-            _ => {}
+            self.entries.push(entry);
         }
+        entry_progress.finish();
+        Ok(())
+    }
+}
 
-        let mut args = vec!["-V".to_string()];
-        if request.fix_padding {
-            args.push("-F".to_string());
+fn verify_file(
+    file: &Path,
+    request: &VerifyRequest,
+    keys: Option<&NcaKeySet>,
+    header_modulus: Option<&[u8; 0x100]>,
+    repo_root: &Path,
+) -> Result<Checked, NszError> {
+    let logical = logical_path(file);
+    match normalized_extension(&logical) {
+        Some(ext @ ("nca" | "ncz")) => {
+            let name = logical
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let input = read_input(file)?;
+            request.progress.emit(ProgressEvent::ContainerOpened {
+                path: file,
+                entries: 1,
+            });
+            let mut progress =
+                request
+                    .progress
+                    .start_entry(&name, input.len() as u64, &request.cancel)?;
+            let (entry, corruption) =
+                check_content(&name, None, &input, ext == "ncz", keys, &mut progress)?;
+            progress.finish();
+            Ok(Checked {
+                entries: vec![entry],
+                signature: None,
+                corruption,
+            })
         }
-        args.push(file.display().to_string());
+        Some("nsp" | "nsz") => verify_nsp_like_container(file, &read_input(file)?, keys, request),
+        Some(ext @ ("xci" | "xcz")) => verify_xci_like_container(
+            file,
+            &read_input(file)?,
+            ext == "xcz",
+            keys,
+            header_modulus,
+            request,
+        ),
+        _ => {
+            let mut args = vec!["-V".to_string()];
+            if request.fix_padding {
+                args.push("-F".to_string());
+            }
+            args.push(file.display().to_string());
 
-        run_nsz_cli(&repo_root, &args)?;
-        verified_files.push(file.clone());
+            run_nsz_cli_with_keys(
+                repo_root,
+                &args,
+                request.keys_file.as_deref(),
+                &request.cancel,
+            )?;
+            Ok(Checked::default())
+        }
     }
-
-    Ok(VerifyReport { verified_files })
 }
 
-fn verify_nsp_like_container(data: &[u8], compressed: bool) -> Result<(), NszError> {
+fn verify_nsp_like_container(
+    path: &Path,
+    data: &[u8],
+    keys: Option<&NcaKeySet>,
+    request: &VerifyRequest,
+) -> Result<Checked, NszError> {
     let archive = NspArchive::from_bytes(data)?;
+    request.progress.emit(ProgressEvent::ContainerOpened {
+        path,
+        entries: archive.entries().len(),
+    });
+    let mut checked = Checked::default();
     for entry in archive.entries() {
-        let name_path = Path::new(&entry.name);
-        let ext = name_path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or_default();
-        if ext.eq_ignore_ascii_case("nca") {
-            if is_cnmt_nca_name(name_path) {
-                continue;
-            }
-            let bytes = archive.entry_bytes(data, entry);
-            verify_hash_against_entry_name(&entry.name, bytes)?;
-            continue;
-        }
-
-        if compressed && ext.eq_ignore_ascii_case("ncz") {
-            let bytes = archive.entry_bytes(data, entry);
-            let decompressed = crate::ncz::decompress::decompress_ncz_to_vec(bytes)?;
-            verify_hash_against_entry_name(&entry.name, &decompressed)?;
-        }
+        checked.check_entry(
+            &entry.name,
+            None,
+            archive.entry_bytes(data, entry),
+            keys,
+            request,
+        )?;
     }
-    Ok(())
+    Ok(checked)
 }
 
-fn verify_xci_like_container(data: &[u8], compressed: bool) -> Result<(), NszError> {
+fn verify_xci_like_container(
+    path: &Path,
+    data: &[u8],
+    compressed: bool,
+    keys: Option<&NcaKeySet>,
+    modulus: Option<&[u8; 0x100]>,
+    request: &VerifyRequest,
+) -> Result<Checked, NszError> {
     let xci = XciArchive::from_bytes(data)?;
     let root_bytes = xci.root_hfs0_bytes(data)?;
     let root = xci.root_hfs0_archive(data)?;
 
-    for partition_entry in root.entries() {
-        let partition_bytes = root.entry_bytes(root_bytes, partition_entry);
-        let partition = Hfs0Archive::from_bytes(partition_bytes)?;
-        verify_hfs0_container(&partition, partition_bytes, compressed)?;
+    // Compression rewrites the XCZ root table but, like Python `nsz`, keeps the source card
+    // header byte for byte, so its hash still describes the XCI the XCZ was made from.
+    // Decompression recomputes it; the NCZ entries below carry their own hashes.
+    let mut checked = Checked::default();
+    if !compressed {
+        let expected = hex_string(&xci.card_header.hfs0_header_hash);
+        let actual = hex_string(&xci.compute_root_hfs0_header_hash(data)?);
+        let matched = expected == actual;
+        checked.entries.push(EntryReport {
+            name: ROOT_HFS0_HEADER_ENTRY.to_string(),
+            partition: None,
+            status: if matched {
+                EntryStatus::Matched
+            } else {
+                EntryStatus::Mismatched
+            },
+            expected_sha256: Some(expected.clone()),
+            actual_sha256: Some(actual.clone()),
+            keys_available: false,
+            error: None,
+        });
+        if !matched {
+            checked.corruption = Some(
+                NszError::HashMismatch {
+                    expected_sha256: expected,
+                    actual_sha256: actual,
+                }
+                .in_entry(ROOT_HFS0_HEADER_ENTRY),
+            );
+        }
     }
 
-    Ok(())
-}
+    let partitions = root
+        .entries()
+        .iter()
+        .map(|entry| {
+            let bytes = root.entry_bytes(root_bytes, entry);
+            Ok((entry, bytes, Hfs0Archive::from_bytes(bytes)?))
+        })
+        .collect::<Result<Vec<_>, NszError>>()?;
+    request.progress.emit(ProgressEvent::ContainerOpened {
+        path,
+        entries: partitions
+            .iter()
+            .map(|(_, _, partition)| partition.entries().len())
+            .sum(),
+    });
 
-fn verify_hfs0_container(
-    archive: &Hfs0Archive,
-    bytes: &[u8],
-    compressed: bool,
-) -> Result<(), NszError> {
-    for entry in archive.entries() {
-        let entry_path = Path::new(&entry.name);
-        let ext = entry_path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or_default();
-
-        if ext.eq_ignore_ascii_case("nca") {
-            if is_cnmt_nca_name(entry_path) {
-                continue;
-            }
-            verify_hash_against_entry_name(&entry.name, archive.entry_bytes(bytes, entry))?;
-            continue;
-        }
-
-        if compressed && ext.eq_ignore_ascii_case("ncz") {
-            let decompressed =
-                crate::ncz::decompress::decompress_ncz_to_vec(archive.entry_bytes(bytes, entry))?;
-            verify_hash_against_entry_name(&entry.name, &decompressed)?;
+    for (partition_entry, partition_bytes, partition) in &partitions {
+        for entry in partition.entries() {
+            checked.check_entry(
+                &entry.name,
+                Some(&partition_entry.name),
+                partition.entry_bytes(partition_bytes, entry),
+                keys,
+                request,
+            )?;
         }
     }
-    Ok(())
+    checked.signature = Some(xci.signature_status(data, modulus));
+    Ok(checked)
 }
 
-fn verify_hash_against_stem(path: &Path, bytes: &[u8]) -> Result<(), NszError> {
-    if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-        return verify_hash_against_expected(stem, bytes);
+/// Returns whether `name` is an NCZ (`true`) or an NCA (`false`); other entries are `None`.
+fn content_kind(name: &Path) -> Option<bool> {
+    let ext = name.extension().and_then(|ext| ext.to_str())?;
+    if ext.eq_ignore_ascii_case("ncz") {
+        Some(true)
+    } else if ext.eq_ignore_ascii_case("nca") {
+        Some(false)
+    } else {
+        None
     }
-    Ok(())
 }
 
-fn verify_hash_against_entry_name(name: &str, bytes: &[u8]) -> Result<(), NszError> {
-    let stem = Path::new(name)
+/// Checks one NCA/NCZ against the SHA-256 prefix its name carries, returning the entry
+/// result and, when it is corrupt, the error describing why. Only cancellation fails it.
+fn check_content(
+    name: &str,
+    partition: Option<&str>,
+    bytes: &[u8],
+    compressed: bool,
+    keys: Option<&NcaKeySet>,
+    progress: &mut EntryProgress<'_>,
+) -> Result<(EntryReport, Option<NszError>), NszError> {
+    let path = Path::new(name);
+    let mut entry = EntryReport {
+        name: name.to_string(),
+        partition: partition.map(str::to_string),
+        status: EntryStatus::SkippedMeta,
+        expected_sha256: None,
+        actual_sha256: None,
+        keys_available: keys.is_some_and(|keys| read_rights_id(bytes, &keys.header_key).is_ok()),
+        error: None,
+    };
+    if is_cnmt_nca_name(path) {
+        return Ok((entry, None));
+    }
+    let Some(expected) = path
         .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or_default();
-    verify_hash_against_expected(stem, bytes)
-}
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.get(..32))
+    else {
+        entry.status = EntryStatus::SkippedShortName;
+        return Ok((entry, None));
+    };
+    let expected = expected.to_ascii_lowercase();
+    entry.expected_sha256 = Some(expected.clone());
 
-fn verify_hash_against_expected(expected_stem: &str, bytes: &[u8]) -> Result<(), NszError> {
-    if expected_stem.len() < 32 {
-        return Ok(());
-    }
+    let decompressed;
+    let content = if compressed {
+        match crate::ncz::decompress::decompress_ncz_with_progress(bytes, progress) {
+            Ok(data) => {
+                decompressed = data;
+                decompressed.as_slice()
+            }
+            Err(err) if err.is_cancelled() => return Err(err),
+            Err(err) => {
+                entry.status = EntryStatus::Unreadable;
+                entry.error = Some(ErrorDetails::from(&err));
+                return Ok((entry, Some(err)));
+            }
+        }
+    } else {
+        bytes
+    };
 
-    let hash = format!("{:x}", Sha256::digest(bytes));
-    let expected_prefix = &expected_stem[..32];
-    if !expected_prefix.eq_ignore_ascii_case(&hash[..32]) {
-        return Err(NszError::ParityMismatch {
-            operation: "verify".to_string(),
-            expected_sha256: expected_prefix.to_lowercase(),
-            actual_sha256: hash,
-            first_diff_offset: 0,
-        });
+    let mut hasher = Sha256::new();
+    for chunk in content.chunks(HASH_CHUNK_SIZE) {
+        hasher.update(chunk);
+        if !compressed {
+            progress.advance(chunk.len() as u64)?;
+        }
+    }
+    let actual = format!("{:x}", hasher.finalize());
+    entry.actual_sha256 = Some(actual.clone());
+    if actual.starts_with(&expected) {
+        entry.status = EntryStatus::Matched;
+        return Ok((entry, None));
     }
-    Ok(())
+    entry.status = EntryStatus::Mismatched;
+    Ok((
+        entry,
+        Some(NszError::HashMismatch {
+            expected_sha256: expected,
+            actual_sha256: actual,
+        }),
+    ))
 }
 
 fn normalized_extension(path: &Path) -> Option<&str> {
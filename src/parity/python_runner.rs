@@ -1,8 +1,15 @@
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
 
+use crate::cancel::CancellationToken;
 use crate::error::NszError;
+use crate::fs_ops::scratch::ScratchDir;
+
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 /// Reads upstream Python `nsz` version from `setup.py`.
 pub fn query_version(repo_root: &str) -> Result<String, NszError> {
@@ -46,19 +53,75 @@ pub fn resolve_python_repo_root(explicit: Option<&Path>) -> PathBuf {
 
 /// Executes Python `nsz.py` with the provided arguments.
 pub fn run_nsz_cli(repo_root: &Path, args: &[String]) -> Result<(), NszError> {
+    run_nsz_cli_cancellable(repo_root, args, &CancellationToken::default())
+}
+
+/// Like [`run_nsz_cli`], killing the child and failing with [`NszError::Cancelled`] once
+/// `cancel` is triggered.
+pub fn run_nsz_cli_cancellable(
+    repo_root: &Path,
+    args: &[String],
+    cancel: &CancellationToken,
+) -> Result<(), NszError> {
+    run_nsz_cli_with_keys(repo_root, args, None, cancel)
+}
+
+/// Like [`run_nsz_cli_cancellable`], making Python `nsz` load `keys_file` when given.
+///
+/// Python `nsz` only reads keys from `keys.txt` next to `nsz.py` or from `~/.switch`, so
+/// the child runs with `HOME` pointing at a scratch directory whose `.switch/prod.keys`
+/// refers to `keys_file`. A `keys.txt` in `repo_root` still takes precedence.
+pub fn run_nsz_cli_with_keys(
+    repo_root: &Path,
+    args: &[String],
+    keys_file: Option<&Path>,
+    cancel: &CancellationToken,
+) -> Result<(), NszError> {
+    let keys_home = keys_file.map(keys_home).transpose()?;
     let python_bin = std::env::var("NSZ_PYTHON_BIN").unwrap_or_else(|_| "python3".to_string());
-    let output = Command::new(&python_bin)
+    let mut command = Command::new(&python_bin);
+    command
         .current_dir(repo_root)
         .arg("nsz.py")
         .args(args)
-        .output()?;
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    if let Some(home) = &keys_home {
+        command
+            .env("HOME", home.path())
+            .env("USERPROFILE", home.path());
+    }
+    let mut child = command.spawn()?;
+    // Drained on a thread so a chatty child cannot block on a full pipe while we poll.
+    let stderr = child.stderr.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut text = String::new();
+            let _ = pipe.read_to_string(&mut text);
+            text
+        })
+    });
 
-    if output.status.success() {
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(NszError::Cancelled);
+        }
+        thread::sleep(CANCEL_POLL_INTERVAL);
+    };
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+
+    if status.success() {
         return Ok(());
     }
 
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-    let status = output.status.code().unwrap_or(-1);
+    let stderr = stderr.trim().to_string();
+    let status = status.code().unwrap_or(-1);
     let command = format!("{python_bin} nsz.py {}", args.join(" "));
     Err(NszError::ExternalCommand {
         command,
@@ -66,3 +129,22 @@ pub fn run_nsz_cli(repo_root: &Path, args: &[String]) -> Result<(), NszError> {
         stderr,
     })
 }
+
+/// Builds the scratch home directory that hands `keys_file` to Python `nsz`.
+fn keys_home(keys_file: &Path) -> Result<ScratchDir, NszError> {
+    let home = ScratchDir::new("keys")?;
+    let switch_dir = home.path().join(".switch");
+    fs::create_dir(&switch_dir)?;
+    link_keys(&fs::canonicalize(keys_file)?, &switch_dir.join("prod.keys"))?;
+    Ok(home)
+}
+
+#[cfg(unix)]
+fn link_keys(keys_file: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(keys_file, link)
+}
+
+#[cfg(not(unix))]
+fn link_keys(keys_file: &Path, link: &Path) -> std::io::Result<()> {
+    fs::copy(keys_file, link).map(|_| ())
+}
@@ -0,0 +1,156 @@
+//! Progress reporting for long-running operations.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::cancel::CancellationToken;
+use crate::error::NszError;
+
+/// Minimum payload bytes between two [`ProgressEvent::BytesProcessed`] events of one entry.
+const REPORT_INTERVAL: u64 = 0x0100_0000;
+
+/// Something that happened while an operation ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent<'a> {
+    /// An input was opened; `entries` counts the files inside it (1 for a bare NCA/NCZ).
+    ContainerOpened { path: &'a Path, entries: usize },
+    /// Work on an entry started; `size` is its stored size in bytes.
+    EntryStarted { name: &'a str, size: u64 },
+    /// `done` of `total` payload bytes of an NCA/NCZ entry were compressed, decompressed
+    /// or hashed. Sent at most once per 16 MiB and once at the end.
+    BytesProcessed {
+        name: &'a str,
+        done: u64,
+        total: u64,
+    },
+    /// Work on an entry finished.
+    EntryFinished { name: &'a str },
+    /// An output file or split folder was written; `size` is its logical size.
+    OutputWritten { path: &'a Path, size: u64 },
+}
+
+/// Receives [`ProgressEvent`]s; closures taking an event implement it.
+///
+/// Events are delivered on the thread running the operation, so slow observers slow it down.
+pub trait ProgressObserver: Send + Sync {
+    fn on_event(&self, event: ProgressEvent<'_>);
+}
+
+impl<F> ProgressObserver for F
+where
+    F: Fn(ProgressEvent<'_>) + Send + Sync,
+{
+    fn on_event(&self, event: ProgressEvent<'_>) {
+        self(event);
+    }
+}
+
+/// Optional observer carried by request structs; the default reports nothing.
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<dyn ProgressObserver>>);
+
+impl Progress {
+    /// Reports events to `observer`.
+    pub fn new(observer: impl ProgressObserver + 'static) -> Self {
+        Self(Some(Arc::new(observer)))
+    }
+
+    /// Whether an observer is attached.
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn emit(&self, event: ProgressEvent<'_>) {
+        if let Some(observer) = &self.0 {
+            observer.on_event(event);
+        }
+    }
+
+    /// Emits [`ProgressEvent::EntryStarted`] and returns the tracker for the entry's bytes,
+    /// failing instead when `cancel` was triggered.
+    pub(crate) fn start_entry<'a>(
+        &'a self,
+        name: &'a str,
+        size: u64,
+        cancel: &'a CancellationToken,
+    ) -> Result<EntryProgress<'a>, NszError> {
+        cancel.check()?;
+        self.emit(ProgressEvent::EntryStarted { name, size });
+        Ok(EntryProgress {
+            observer: self.0.as_deref(),
+            cancel: Some(cancel),
+            name,
+            total: size,
+            done: 0,
+            reported: 0,
+        })
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Progress")
+            .field(&if self.is_enabled() {
+                "observer"
+            } else {
+                "none"
+            })
+            .finish()
+    }
+}
+
+/// Byte counter of one entry, throttling [`ProgressEvent::BytesProcessed`] and checking
+/// for cancellation as it advances.
+pub(crate) struct EntryProgress<'a> {
+    observer: Option<&'a dyn ProgressObserver>,
+    cancel: Option<&'a CancellationToken>,
+    name: &'a str,
+    total: u64,
+    done: u64,
+    reported: u64,
+}
+
+impl EntryProgress<'_> {
+    /// A tracker that reports nothing, for the public NCZ functions.
+    pub(crate) const fn disabled() -> Self {
+        Self {
+            observer: None,
+            cancel: None,
+            name: "",
+            total: 0,
+            done: 0,
+            reported: 0,
+        }
+    }
+
+    /// Replaces the entry size with the payload size once the NCZ code knows it.
+    pub(crate) fn set_total(&mut self, total: u64) {
+        self.total = total;
+    }
+
+    /// Counts `bytes` more as processed, failing with [`NszError::Cancelled`] once the
+    /// operation was cancelled.
+    pub(crate) fn advance(&mut self, bytes: u64) -> Result<(), NszError> {
+        self.done = self.done.saturating_add(bytes);
+        let due = self.done - self.reported >= REPORT_INTERVAL || self.done >= self.total;
+        if due && self.done > self.reported {
+            self.reported = self.done;
+            if let Some(observer) = self.observer {
+                observer.on_event(ProgressEvent::BytesProcessed {
+                    name: self.name,
+                    done: self.done,
+                    total: self.total.max(self.done),
+                });
+            }
+        }
+        self.cancel.map_or(Ok(()), CancellationToken::check)
+    }
+
+    /// Emits [`ProgressEvent::EntryFinished`].
+    pub(crate) fn finish(self) {
+        if let Some(observer) = self.observer {
+            observer.on_event(ProgressEvent::EntryFinished { name: self.name });
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 
 use crate::{
-    CompressRequest, CreateRequest, DecompressRequest, ExtractRequest, NszError, TitleKeysRequest,
-    UndupeRequest, VerifyRequest,
+    CancellationToken, CompressRequest, ConvertRequest, CreateRequest, DecompressRequest,
+    ErrorDetails, ExtractRequest, FileReport, MergeRequest, NszError, OperationReport, Progress,
+    ProgressEvent, SplitRequest, TitleKeysRequest, TrimRequest, UndupeRequest, VerifyReport,
+    VerifyRequest,
 };
 
 fn map_error(err: NszError) -> PyErr {
@@ -19,10 +23,119 @@ fn map_paths(paths: Vec<PathBuf>) -> Vec<String> {
         .collect()
 }
 
+fn json_lines(files: &[FileReport]) -> Vec<String> {
+    files.iter().map(FileReport::to_json_line).collect()
+}
+
+/// JSON-lines events for a failed run: every file of a batch that continued past errors,
+/// or a single `error` event otherwise.
+fn error_lines(err: &NszError) -> Vec<String> {
+    match err {
+        NszError::BatchFailed { files, .. } => json_lines(files),
+        err => vec![ErrorDetails::from(err).to_json_line()],
+    }
+}
+
+/// Returns processed paths, or JSON-lines events when `machine_readable` is set; in that
+/// mode a failure is returned as an `error` event instead of raised.
+fn operation_output(
+    result: Result<OperationReport, NszError>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    if machine_readable {
+        return Ok(result.map_or_else(|err| error_lines(&err), |report| json_lines(&report.files)));
+    }
+    Ok(map_paths(result.map_err(map_error)?.processed_files))
+}
+
+/// Like [`operation_output`], returning verified paths.
+fn verify_output(
+    result: Result<VerifyReport, NszError>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    if machine_readable {
+        return Ok(result.map_or_else(|err| error_lines(&err), |report| json_lines(&report.files)));
+    }
+    Ok(map_paths(result.map_err(map_error)?.verified_files))
+}
+
 fn map_input_files(files: Vec<String>) -> Vec<PathBuf> {
     files.into_iter().map(PathBuf::from).collect()
 }
 
+/// Builds the dict passed to a Python progress callback; `event` names the variant.
+fn event_dict<'py>(py: Python<'py>, event: ProgressEvent<'_>) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    match event {
+        ProgressEvent::ContainerOpened { path, entries } => {
+            dict.set_item("event", "container_opened")?;
+            dict.set_item("path", path.to_string_lossy())?;
+            dict.set_item("entries", entries)?;
+        }
+        ProgressEvent::EntryStarted { name, size } => {
+            dict.set_item("event", "entry_started")?;
+            dict.set_item("name", name)?;
+            dict.set_item("size", size)?;
+        }
+        ProgressEvent::BytesProcessed { name, done, total } => {
+            dict.set_item("event", "bytes_processed")?;
+            dict.set_item("name", name)?;
+            dict.set_item("done", done)?;
+            dict.set_item("total", total)?;
+        }
+        ProgressEvent::EntryFinished { name } => {
+            dict.set_item("event", "entry_finished")?;
+            dict.set_item("name", name)?;
+        }
+        ProgressEvent::OutputWritten { path, size } => {
+            dict.set_item("event", "output_written")?;
+            dict.set_item("path", path.to_string_lossy())?;
+            dict.set_item("size", size)?;
+        }
+    }
+    Ok(dict)
+}
+
+/// Cancels an operation when a Python signal handler raises, typically `KeyboardInterrupt`
+/// on Ctrl-C, and re-raises that exception once the operation has stopped.
+#[derive(Default)]
+struct Interrupt(Arc<Mutex<Option<PyErr>>>);
+
+impl Interrupt {
+    // `Python::check_signals` is not generic over the GIL lifetime, so it needs the closure.
+    #[allow(clippy::redundant_closure_for_method_calls)]
+    fn token(&self) -> CancellationToken {
+        let pending = Arc::clone(&self.0);
+        CancellationToken::from_fn(move || {
+            let Err(err) = Python::with_gil(|py| py.check_signals()) else {
+                return false;
+            };
+            *pending.lock().unwrap_or_else(PoisonError::into_inner) = Some(err);
+            true
+        })
+    }
+
+    fn raise_or<T>(self, result: PyResult<T>) -> PyResult<T> {
+        let pending = self.0.lock().unwrap_or_else(PoisonError::into_inner).take();
+        pending.map_or(result, Err)
+    }
+}
+
+/// Wraps an optional Python callable taking one event dict; exceptions it raises are
+/// reported as unraisable instead of aborting the operation.
+fn map_progress(callback: Option<PyObject>) -> Progress {
+    callback.map_or_else(Progress::default, |callback| {
+        Progress::new(move |event: ProgressEvent<'_>| {
+            Python::with_gil(|py| {
+                if let Err(err) = event_dict(py, event).and_then(|dict| callback.call1(py, (dict,)))
+                {
+                    err.write_unraisable_bound(py, Some(callback.bind(py)));
+                }
+            });
+        })
+    })
+}
+
 #[pyfunction]
 #[pyo3(
     signature = (
@@ -43,7 +156,17 @@ fn map_input_files(files: Vec<String>) -> Vec<PathBuf> {
         threads = -1,
         overwrite = false,
         rm_old_version = false,
-        rm_source = false
+        rm_source = false,
+        split_output = false,
+        require_keys = false,
+        keys_file = None,
+        title_keys_file = None,
+        continue_on_error = false,
+        journal = None,
+        dry_run = false,
+        estimate_sample_chunks = 4,
+        progress = None,
+        machine_readable = false
     )
 )]
 fn compress(
@@ -65,7 +188,18 @@ fn compress(
     overwrite: bool,
     rm_old_version: bool,
     rm_source: bool,
+    split_output: bool,
+    require_keys: bool,
+    keys_file: Option<String>,
+    title_keys_file: Option<String>,
+    continue_on_error: bool,
+    journal: Option<String>,
+    dry_run: bool,
+    estimate_sample_chunks: usize,
+    progress: Option<PyObject>,
+    machine_readable: bool,
 ) -> PyResult<Vec<String>> {
+    let interrupt = Interrupt::default();
     let request = CompressRequest {
         files: map_input_files(files),
         output_dir: output_dir.map(PathBuf::from),
@@ -85,64 +219,255 @@ fn compress(
         overwrite,
         rm_old_version,
         rm_source,
+        split_output,
+        require_keys,
+        continue_on_error,
+        journal: journal.map(PathBuf::from),
+        dry_run,
+        estimate_sample_chunks,
+        keys_file: keys_file.map(PathBuf::from),
+        title_keys_file: title_keys_file.map(PathBuf::from),
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: map_progress(progress),
+        cancel: interrupt.token(),
     };
-    let report = crate::compress(&request).map_err(map_error)?;
-    Ok(map_paths(report.processed_files))
+    interrupt.raise_or(operation_output(
+        crate::compress(&request),
+        machine_readable,
+    ))
 }
 
 #[pyfunction]
-#[pyo3(signature = (files, output_dir = None, fix_padding = false))]
+#[pyo3(
+    signature = (
+        files,
+        output_dir = None,
+        fix_padding = false,
+        split_output = false,
+        continue_on_error = false,
+        journal = None,
+        keys_file = None,
+        progress = None,
+        machine_readable = false
+    )
+)]
 fn decompress(
     files: Vec<String>,
     output_dir: Option<String>,
     fix_padding: bool,
+    split_output: bool,
+    continue_on_error: bool,
+    journal: Option<String>,
+    keys_file: Option<String>,
+    progress: Option<PyObject>,
+    machine_readable: bool,
 ) -> PyResult<Vec<String>> {
+    let interrupt = Interrupt::default();
     let request = DecompressRequest {
         files: map_input_files(files),
         output_dir: output_dir.map(PathBuf::from),
         fix_padding,
+        split_output,
+        continue_on_error,
+        journal: journal.map(PathBuf::from),
+        keys_file: keys_file.map(PathBuf::from),
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: map_progress(progress),
+        cancel: interrupt.token(),
+    };
+    interrupt.raise_or(operation_output(
+        crate::decompress(&request),
+        machine_readable,
+    ))
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (
+        files,
+        output_dir = None,
+        split_titles = false,
+        keys_file = None,
+        title_keys_file = None,
+        machine_readable = false
+    )
+)]
+fn convert(
+    files: Vec<String>,
+    output_dir: Option<String>,
+    split_titles: bool,
+    keys_file: Option<String>,
+    title_keys_file: Option<String>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    let request = ConvertRequest {
+        files: map_input_files(files),
+        output_dir: output_dir.map(PathBuf::from),
+        split_titles,
+        keys_file: keys_file.map(PathBuf::from),
+        title_keys_file: title_keys_file.map(PathBuf::from),
+    };
+    operation_output(crate::convert(&request), machine_readable)
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (
+        files,
+        output_file,
+        keys_file = None,
+        title_keys_file = None,
+        machine_readable = false
+    )
+)]
+fn merge(
+    files: Vec<String>,
+    output_file: String,
+    keys_file: Option<String>,
+    title_keys_file: Option<String>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    let request = MergeRequest {
+        files: map_input_files(files),
+        output_file: Some(PathBuf::from(output_file)),
+        keys_file: keys_file.map(PathBuf::from),
+        title_keys_file: title_keys_file.map(PathBuf::from),
     };
-    let report = crate::decompress(&request).map_err(map_error)?;
-    Ok(map_paths(report.processed_files))
+    operation_output(crate::merge(&request), machine_readable)
 }
 
 #[pyfunction]
-#[pyo3(signature = (files, fix_padding = false))]
-fn verify(files: Vec<String>, fix_padding: bool) -> PyResult<Vec<String>> {
+#[pyo3(
+    signature = (
+        files,
+        output_dir = None,
+        keys_file = None,
+        title_keys_file = None,
+        machine_readable = false
+    )
+)]
+fn split(
+    files: Vec<String>,
+    output_dir: Option<String>,
+    keys_file: Option<String>,
+    title_keys_file: Option<String>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    let request = SplitRequest {
+        files: map_input_files(files),
+        output_dir: output_dir.map(PathBuf::from),
+        keys_file: keys_file.map(PathBuf::from),
+        title_keys_file: title_keys_file.map(PathBuf::from),
+    };
+    operation_output(crate::split(&request), machine_readable)
+}
+
+#[pyfunction]
+#[pyo3(signature = (files, output_dir = None, machine_readable = false))]
+fn trim(
+    files: Vec<String>,
+    output_dir: Option<String>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    let request = TrimRequest {
+        files: map_input_files(files),
+        output_dir: output_dir.map(PathBuf::from),
+    };
+    operation_output(crate::trim(&request), machine_readable)
+}
+
+#[pyfunction]
+#[pyo3(signature = (files, output_dir = None, machine_readable = false))]
+fn untrim(
+    files: Vec<String>,
+    output_dir: Option<String>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    let request = TrimRequest {
+        files: map_input_files(files),
+        output_dir: output_dir.map(PathBuf::from),
+    };
+    operation_output(crate::untrim(&request), machine_readable)
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (
+        files,
+        fix_padding = false,
+        continue_on_error = false,
+        keys_file = None,
+        progress = None,
+        machine_readable = false
+    )
+)]
+fn verify(
+    files: Vec<String>,
+    fix_padding: bool,
+    continue_on_error: bool,
+    keys_file: Option<String>,
+    progress: Option<PyObject>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
+    let interrupt = Interrupt::default();
     let request = VerifyRequest {
         files: map_input_files(files),
         fix_padding,
+        continue_on_error,
+        keys_file: keys_file.map(PathBuf::from),
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: map_progress(progress),
+        cancel: interrupt.token(),
     };
-    let report = crate::verify(&request).map_err(map_error)?;
-    Ok(map_paths(report.verified_files))
+    interrupt.raise_or(verify_output(crate::verify(&request), machine_readable))
 }
 
 #[pyfunction]
-#[pyo3(signature = (files, output_dir = None, extract_regex = None))]
+#[pyo3(
+    signature = (
+        files,
+        output_dir = None,
+        extract_regex = None,
+        keys_file = None,
+        progress = None,
+        machine_readable = false
+    )
+)]
 fn extract(
     files: Vec<String>,
     output_dir: Option<String>,
     extract_regex: Option<String>,
+    keys_file: Option<String>,
+    progress: Option<PyObject>,
+    machine_readable: bool,
 ) -> PyResult<Vec<String>> {
+    let interrupt = Interrupt::default();
     let request = ExtractRequest {
         files: map_input_files(files),
         output_dir: output_dir.map(PathBuf::from),
         extract_regex,
+        keys_file: keys_file.map(PathBuf::from),
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: map_progress(progress),
+        cancel: interrupt.token(),
     };
-    let report = crate::extract(&request).map_err(map_error)?;
-    Ok(map_paths(report.processed_files))
+    interrupt.raise_or(operation_output(crate::extract(&request), machine_readable))
 }
 
 #[pyfunction]
-#[pyo3(signature = (sources, output_file = None, fix_padding = false))]
+#[pyo3(
+    signature = (
+        sources,
+        output_file = None,
+        fix_padding = false,
+        machine_readable = false
+    )
+)]
 fn create(
     sources: Vec<String>,
     output_file: Option<String>,
     fix_padding: bool,
+    machine_readable: bool,
 ) -> PyResult<Vec<String>> {
     let request = CreateRequest {
         output_file: output_file.map(PathBuf::from),
@@ -150,18 +475,22 @@ fn create(
         fix_padding,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
     };
-    let report = crate::create(&request).map_err(map_error)?;
-    Ok(map_paths(report.processed_files))
+    operation_output(crate::create(&request), machine_readable)
 }
 
 #[pyfunction]
-fn titlekeys(files: Vec<String>) -> PyResult<Vec<String>> {
+#[pyo3(signature = (files, keys_file = None, machine_readable = false))]
+fn titlekeys(
+    files: Vec<String>,
+    keys_file: Option<String>,
+    machine_readable: bool,
+) -> PyResult<Vec<String>> {
     let request = TitleKeysRequest {
         files: map_input_files(files),
+        keys_file: keys_file.map(PathBuf::from),
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
     };
-    let report = crate::titlekeys(&request).map_err(map_error)?;
-    Ok(map_paths(report.processed_files))
+    operation_output(crate::titlekeys(&request), machine_readable)
 }
 
 #[pyfunction]
@@ -175,7 +504,8 @@ fn titlekeys(files: Vec<String>) -> PyResult<Vec<String>> {
         priority_list = None,
         whitelist = None,
         blacklist = None,
-        old_versions = false
+        old_versions = false,
+        machine_readable = false
     )
 )]
 fn undupe(
@@ -188,6 +518,7 @@ fn undupe(
     whitelist: Option<String>,
     blacklist: Option<String>,
     old_versions: bool,
+    machine_readable: bool,
 ) -> PyResult<Vec<String>> {
     let request = UndupeRequest {
         files: map_input_files(files),
@@ -201,8 +532,7 @@ fn undupe(
         old_versions,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
     };
-    let report = crate::undupe(&request).map_err(map_error)?;
-    Ok(map_paths(report.processed_files))
+    operation_output(crate::undupe(&request), machine_readable)
 }
 
 #[pyfunction]
@@ -214,6 +544,11 @@ fn rust_version() -> &'static str {
 fn _native(_py: Python<'_>, module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(compress, module)?)?;
     module.add_function(wrap_pyfunction!(decompress, module)?)?;
+    module.add_function(wrap_pyfunction!(convert, module)?)?;
+    module.add_function(wrap_pyfunction!(merge, module)?)?;
+    module.add_function(wrap_pyfunction!(split, module)?)?;
+    module.add_function(wrap_pyfunction!(trim, module)?)?;
+    module.add_function(wrap_pyfunction!(untrim, module)?)?;
     module.add_function(wrap_pyfunction!(verify, module)?)?;
     module.add_function(wrap_pyfunction!(extract, module)?)?;
     module.add_function(wrap_pyfunction!(create, module)?)?;
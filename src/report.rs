@@ -0,0 +1,343 @@
+//! Per-file and per-entry outcomes reported by operations and carried by batch errors.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::NszError;
+use crate::fs_ops::split_files::logical_len;
+
+/// Outcome of one input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum FileStatus {
+    /// An output was written from the input.
+    Processed,
+    /// The input was not a format the operation handles.
+    Skipped,
+    /// An earlier run recorded in the job journal already wrote an intact output.
+    Resumed,
+    /// A dry run estimated the output without writing it; sizes and ratio are estimates.
+    Estimated,
+    /// The input passed verification.
+    Verified,
+    /// The operation failed on this input; see [`FileReport::error`].
+    Failed,
+}
+
+/// Per-file entry of an [`OperationReport`] or [`VerifyReport`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileReport {
+    /// Input file as given in the request.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_path"))]
+    pub input: PathBuf,
+    /// File or directory written for this input, if any.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_optional_path"))]
+    pub output: Option<PathBuf>,
+    /// Outcome for this input.
+    pub status: FileStatus,
+    /// Logical input size in bytes, counting every part of split inputs.
+    pub input_size: Option<u64>,
+    /// Logical output size in bytes; unset when the output is shared with other inputs.
+    pub output_size: Option<u64>,
+    /// `output_size / input_size`.
+    pub ratio: Option<f64>,
+    /// Wall time spent on this input.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_secs", serialize_with = "serialize_secs")
+    )]
+    pub duration: Duration,
+    /// Expected compression time of a full run, for [`FileStatus::Estimated`] entries.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "estimated_duration_secs",
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "serialize_optional_secs"
+        )
+    )]
+    pub estimated_duration: Option<Duration>,
+    /// Why the input failed, for [`FileStatus::Failed`] entries.
+    pub error: Option<ErrorDetails>,
+    /// Result of each NCA/NCZ checked by a verify operation.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub entries: Vec<EntryReport>,
+}
+
+impl FileReport {
+    pub(crate) fn new(
+        input: &Path,
+        output: Option<&Path>,
+        status: FileStatus,
+        started: Instant,
+    ) -> Self {
+        let input_size = logical_len(input).ok();
+        let output_size = output.and_then(|output| logical_len(output).ok());
+        Self {
+            input: input.to_path_buf(),
+            output: output.map(Path::to_path_buf),
+            status,
+            input_size,
+            output_size,
+            ratio: size_ratio(input_size, output_size),
+            duration: started.elapsed(),
+            estimated_duration: None,
+            error: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Builds the entry of an input the operation failed on.
+    pub fn failed(input: &Path, duration: Duration, err: &NszError) -> Self {
+        Self {
+            input: input.to_path_buf(),
+            output: None,
+            status: FileStatus::Failed,
+            input_size: logical_len(input).ok(),
+            output_size: None,
+            ratio: None,
+            duration,
+            estimated_duration: None,
+            error: Some(ErrorDetails::from(err)),
+            entries: Vec::new(),
+        }
+    }
+
+    /// The outcome of this input as a `Result`, failing with the recorded error details.
+    pub fn result(&self) -> Result<(), &ErrorDetails> {
+        self.error.as_ref().map_or(Ok(()), Err)
+    }
+
+    /// How a verify operation judged this input, from its entries.
+    ///
+    /// `None` for inputs of other operations and for inputs that failed before any
+    /// entry was checked, such as unreadable containers.
+    pub fn verdict(&self) -> Option<VerifyVerdict> {
+        if self.entries.iter().any(|entry| entry.status.is_corrupt()) {
+            return Some(VerifyVerdict::Corrupt);
+        }
+        if self.status != FileStatus::Verified {
+            return None;
+        }
+        let unchecked = self
+            .entries
+            .iter()
+            .any(|entry| entry.status == EntryStatus::SkippedShortName);
+        let checked = self
+            .entries
+            .iter()
+            .any(|entry| entry.status == EntryStatus::Matched);
+        Some(if checked && !unchecked {
+            VerifyVerdict::Intact
+        } else {
+            VerifyVerdict::Unverifiable
+        })
+    }
+
+    /// Serializes the entry as one JSON-lines `file` event.
+    #[cfg(feature = "serde")]
+    pub fn to_json_line(&self) -> String {
+        #[derive(serde::Serialize)]
+        struct FileEvent<'a> {
+            event: &'static str,
+            #[serde(flatten)]
+            file: &'a FileReport,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            verdict: Option<VerifyVerdict>,
+        }
+
+        serde_json::to_string(&FileEvent {
+            event: "file",
+            file: self,
+            verdict: self.verdict(),
+        })
+        .expect("file reports serialize to JSON")
+    }
+}
+
+/// Verification result of one NCA/NCZ, in a [`FileReport`] of a verify operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntryReport {
+    /// Entry name inside the container, or the file name of a bare `.nca`/`.ncz`.
+    pub name: String,
+    /// XCI partition holding the entry.
+    pub partition: Option<String>,
+    /// Outcome of the hash check.
+    pub status: EntryStatus,
+    /// SHA-256 prefix (32 hex digits) promised by the entry name, or for the `root HFS0
+    /// header` entry of an `.xci` the full hash recorded in its card header.
+    pub expected_sha256: Option<String>,
+    /// SHA-256 of the NCA, decompressed first for NCZ entries.
+    pub actual_sha256: Option<String>,
+    /// Whether a header key was loaded and the NCA header decrypts with it.
+    pub keys_available: bool,
+    /// Why an NCZ entry could not be decompressed.
+    pub error: Option<ErrorDetails>,
+}
+
+/// Outcome of checking one NCA/NCZ against the hash in its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum EntryStatus {
+    /// The content hashes to the name.
+    Matched,
+    /// The content does not hash to the name.
+    Mismatched,
+    /// The NCZ data could not be decompressed.
+    Unreadable,
+    /// Meta (`.cnmt.nca`) entries are not checked.
+    SkippedMeta,
+    /// The name is too short to carry a content hash.
+    SkippedShortName,
+}
+
+impl EntryStatus {
+    /// Whether the entry shows the container is damaged.
+    pub const fn is_corrupt(self) -> bool {
+        matches!(self, Self::Mismatched | Self::Unreadable)
+    }
+}
+
+/// Overall judgement of a verified input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum VerifyVerdict {
+    /// Every content entry was checked and matched.
+    Intact,
+    /// Nothing was found wrong, but some or all content could not be checked.
+    Unverifiable,
+    /// At least one entry mismatched or could not be decompressed.
+    Corrupt,
+}
+
+/// Counts of verify inputs by outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VerifySummary {
+    /// Inputs judged [`VerifyVerdict::Intact`].
+    pub intact: usize,
+    /// Inputs judged [`VerifyVerdict::Unverifiable`].
+    pub unverifiable: usize,
+    /// Inputs judged [`VerifyVerdict::Corrupt`].
+    pub corrupt: usize,
+    /// Inputs that failed without a verdict, such as unreadable containers.
+    pub failed: usize,
+}
+
+impl VerifySummary {
+    /// Summarizes per-file entries, such as those of an [`NszError::BatchFailed`].
+    pub fn from_files(files: &[FileReport]) -> Self {
+        let mut summary = Self::default();
+        for file in files {
+            match file.verdict() {
+                Some(VerifyVerdict::Intact) => summary.intact += 1,
+                Some(VerifyVerdict::Unverifiable) => summary.unverifiable += 1,
+                Some(VerifyVerdict::Corrupt) => summary.corrupt += 1,
+                None if file.status == FileStatus::Failed => summary.failed += 1,
+                None => {}
+            }
+        }
+        summary
+    }
+}
+
+/// Machine-readable description of an [`NszError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ErrorDetails {
+    /// Stable snake-case error kind, see [`NszError::kind`].
+    pub kind: String,
+    /// Container entry the error happened in, when it was inside one.
+    pub entry: Option<String>,
+    /// Human-readable error message.
+    pub message: String,
+}
+
+impl ErrorDetails {
+    /// Serializes the details as one JSON-lines `error` event, for failures not tied to one file.
+    #[cfg(feature = "serde")]
+    pub fn to_json_line(&self) -> String {
+        #[derive(serde::Serialize)]
+        struct ErrorEvent<'a> {
+            event: &'static str,
+            #[serde(flatten)]
+            error: &'a ErrorDetails,
+        }
+
+        serde_json::to_string(&ErrorEvent {
+            event: "error",
+            error: self,
+        })
+        .expect("error details serialize to JSON")
+    }
+}
+
+impl From<&NszError> for ErrorDetails {
+    fn from(err: &NszError) -> Self {
+        Self {
+            kind: err.kind().to_string(),
+            entry: err.entry().map(str::to_string),
+            message: err.to_string(),
+        }
+    }
+}
+
+// Sizes beyond 2^52 bytes lose precision, which does not matter for a ratio.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn size_ratio(input_size: Option<u64>, output_size: Option<u64>) -> Option<f64> {
+    match (input_size, output_size) {
+        (Some(input), Some(output)) if input > 0 => Some(output as f64 / input as f64),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_path<S: serde::Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+#[cfg(feature = "serde")]
+#[allow(clippy::ref_option)]
+fn serialize_optional_path<S: serde::Serializer>(
+    path: &Option<PathBuf>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => serialize_path(path, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_secs<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+#[cfg(feature = "serde")]
+#[allow(clippy::ref_option)]
+fn serialize_optional_secs<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_secs(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
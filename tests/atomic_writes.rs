@@ -0,0 +1,114 @@
+mod common;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use common::{build_pfs0, raw_nca, scratch_dir};
+use nsz_rs::fs_ops::atomic_write::{
+    clean_stale_temps, ensure_free_space, is_temp_path, temp_path_for, write_atomic,
+    write_atomic_with,
+};
+use nsz_rs::fs_ops::split_files::write_output;
+
+fn dir_names(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn writes_replace_outputs_only_once_complete() {
+    let root = scratch_dir("atomic-replace");
+    let out = root.join("game.nsz");
+    assert_eq!(temp_path_for(&out), root.join(".game.nsz.nsz-rs-tmp"));
+    assert!(is_temp_path(&temp_path_for(&out)));
+    assert!(!is_temp_path(&out));
+
+    write_atomic(&out, b"first").unwrap();
+    write_atomic(&out, b"second").unwrap();
+    assert_eq!(fs::read(&out).unwrap(), b"second");
+    assert_eq!(dir_names(&root), ["game.nsz"]);
+
+    // A failed write keeps the previous output and removes its staging file.
+    let err = write_atomic_with(&out, 8, |writer| {
+        writer.write_all(b"trunc")?;
+        Err(io::Error::other("disk went away"))
+    })
+    .unwrap_err();
+    assert!(err.to_string().contains("disk went away"), "{err}");
+    assert_eq!(fs::read(&out).unwrap(), b"second");
+    assert_eq!(dir_names(&root), ["game.nsz"]);
+
+    write_output(&root.join("split.nsz"), &[7u8; 10], true).unwrap();
+    assert_eq!(dir_names(&root), ["game.nsz", "split.nsz"]);
+    assert_eq!(fs::read(root.join("split.nsz/00")).unwrap(), [7u8; 10]);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn next_run_removes_leftover_temp_files() {
+    let root = scratch_dir("atomic-stale");
+    let input = root.join("game.nsp");
+    let nca = raw_nca(std::iter::repeat_n(9u8, 0x8000));
+    fs::write(
+        &input,
+        build_pfs0(&[("fedcba9876543210fedcba9876543210.nca", &nca)]),
+    )
+    .unwrap();
+    let out_dir = root.join("out");
+    fs::create_dir_all(out_dir.join(".game.nsz.nsz-rs-tmp")).unwrap();
+    fs::write(out_dir.join(".game.nsz.nsz-rs-tmp/00"), b"half").unwrap();
+    fs::write(out_dir.join(".other.nsz.nsz-rs-tmp"), b"half").unwrap();
+    fs::write(out_dir.join(".hidden"), b"kept").unwrap();
+
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(out_dir.clone()),
+        level: 1,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+        report.removed_temp_files,
+        vec![
+            out_dir.join(".game.nsz.nsz-rs-tmp"),
+            out_dir.join(".other.nsz.nsz-rs-tmp"),
+        ]
+    );
+    assert_eq!(dir_names(&out_dir), [".hidden", "game.nsz"]);
+    assert!(clean_stale_temps(&root.join("missing")).unwrap().is_empty());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn outputs_larger_than_the_free_space_fail_up_front() {
+    let root = scratch_dir("atomic-space");
+    let out = root.join("huge.nsz");
+    ensure_free_space(&out, 1).unwrap();
+
+    let err = ensure_free_space(&out, u64::MAX).unwrap_err();
+    assert_eq!(err.kind(), "insufficient_space");
+    assert!(err.to_string().contains("huge.nsz"), "{err}");
+
+    let err = write_atomic_with(&out, u64::MAX, |_| Ok(())).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            nsz_rs::NszError::InsufficientSpace {
+                needed: u64::MAX,
+                ..
+            }
+        ),
+        "{err}"
+    );
+    assert_eq!(dir_names(&root), Vec::<String>::new());
+
+    let _ = fs::remove_dir_all(root);
+}
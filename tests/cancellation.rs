@@ -0,0 +1,148 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use common::{build_pfs0, raw_nca, scratch_dir};
+use nsz_rs::{CancellationToken, NszError, Progress, ProgressEvent};
+
+const NCA_NAME: &str = "fedcba9876543210fedcba9876543210.nca";
+
+fn fixture_nsp(payload: u8) -> Vec<u8> {
+    build_pfs0(&[(NCA_NAME, &raw_nca(std::iter::repeat_n(payload, 0x8000)))])
+}
+
+#[test]
+fn cancelled_token_stops_the_batch_despite_continue_on_error() {
+    let root = scratch_dir("cancel-batch");
+    let first = root.join("first.nsp");
+    let second = root.join("second.nsp");
+    fs::write(&first, fixture_nsp(1)).unwrap();
+    fs::write(&second, fixture_nsp(2)).unwrap();
+
+    // Cancel as soon as the second input starts its NCA.
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    let opened = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&opened);
+    let progress = Progress::new(move |event: ProgressEvent<'_>| {
+        if let ProgressEvent::ContainerOpened { path, .. } = event {
+            let mut seen = seen.lock().unwrap();
+            seen.push(path.to_path_buf());
+            if seen.len() == 2 {
+                trigger.cancel();
+            }
+        }
+    });
+    let request = nsz_rs::CompressRequest {
+        files: vec![first, second],
+        output_dir: Some(root.join("out")),
+        level: 1,
+        continue_on_error: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress,
+        cancel: cancel.clone(),
+        ..Default::default()
+    };
+
+    let err = nsz_rs::compress(&request).unwrap_err();
+    assert!(err.is_cancelled(), "{err}");
+    assert_eq!(err.kind(), "cancelled");
+    assert_eq!(err.entry(), None);
+    assert!(cancel.is_cancelled());
+    assert!(root.join("out/first.nsz").is_file());
+    assert!(!root.join("out/second.nsz").exists());
+
+    // A token cancelled up front stops before the first input.
+    let err = nsz_rs::decompress(&nsz_rs::DecompressRequest {
+        files: vec![root.join("out/first.nsz")],
+        output_dir: Some(root.join("restored")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        cancel,
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(matches!(err, NszError::Cancelled), "{err}");
+    assert!(!root.join("restored/first.nsp").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn payload_loops_check_the_token() {
+    let root = scratch_dir("cancel-payload");
+    let input = root.join("game.nsp");
+    fs::write(&input, fixture_nsp(3)).unwrap();
+
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    let err = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        files: vec![input],
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: Progress::new(move |event: ProgressEvent<'_>| {
+            if matches!(event, ProgressEvent::BytesProcessed { .. }) {
+                trigger.cancel();
+            }
+        }),
+        cancel,
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(err.is_cancelled(), "{err}");
+
+    let polled = CancellationToken::from_fn(|| true);
+    assert!(polled.is_cancelled());
+    assert!(format!("{polled:?}").contains("cancelled: true"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn cancelling_the_python_fallback_removes_its_partial_output() {
+    let root = scratch_dir("cancel-fallback");
+    let repo_root = root.join("fake-nsz");
+    fs::create_dir_all(&repo_root).unwrap();
+    fs::write(
+        repo_root.join("nsz.py"),
+        r#"import pathlib, sys, time
+args = sys.argv[1:]
+out = pathlib.Path(args[args.index("-o") + 1]) / pathlib.Path(args[-1]).stem
+out.mkdir(parents=True, exist_ok=True)
+(out / "partial.nca").write_bytes(b"half")
+time.sleep(30)
+"#,
+    )
+    .unwrap();
+    let input = root.join("sample.nsz");
+    fs::write(&input, b"dummy").unwrap();
+    let out_dir = root.join("out");
+
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    let partial = out_dir.join("sample/partial.nca");
+    let watcher = std::thread::spawn(move || {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !partial.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        trigger.cancel();
+    });
+
+    let started = Instant::now();
+    let err = nsz_rs::extract(&nsz_rs::ExtractRequest {
+        files: vec![input],
+        output_dir: Some(out_dir.clone()),
+        python_repo_root: Some(repo_root),
+        cancel,
+        ..Default::default()
+    })
+    .unwrap_err();
+    watcher.join().unwrap();
+    assert!(err.is_cancelled(), "{err}");
+    assert!(started.elapsed() < Duration::from_secs(20));
+    assert!(!out_dir.join("sample").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,245 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{build_pfs0, hex, keys_text, raw_nca, scratch_dir};
+
+fn nsz(root: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_nsz"))
+        .args(args)
+        .current_dir(root)
+        .env("HOME", root)
+        .env_remove("NSZ_KEYS_FILE")
+        .env_remove("NSZ_TITLE_KEYS_FILE")
+        .output()
+        .unwrap()
+}
+
+fn fixture_nsp() -> Vec<u8> {
+    build_pfs0(&[(
+        "0123456789abcdef0123456789abcdef.nca",
+        &raw_nca(*b"cli-binary-payload"),
+    )])
+}
+
+#[test]
+fn compress_then_decompress_with_upstream_flags() {
+    let root = scratch_dir("cli-roundtrip");
+    fs::create_dir_all(root.join("in")).unwrap();
+    fs::create_dir_all(root.join("out")).unwrap();
+    fs::write(root.join("in/game.nsp"), fixture_nsp()).unwrap();
+    fs::write(root.join("in/readme.txt"), b"ignored").unwrap();
+
+    let output = nsz(&root, &["-CL", "-l3", "--threads=1", "-o", "out", "in"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(root.join("out/game.nsz").is_file());
+    assert!(!root.join("out/readme.nsz").exists());
+
+    let output = nsz(&root, &["-D", "out/game.nsz"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("game.nsp"));
+    let nsp = fs::read(root.join("out/game.nsp")).unwrap();
+    let archive = nsz_rs::container::nsp::NspArchive::from_bytes(&nsp).unwrap();
+    let nca = archive
+        .entries()
+        .iter()
+        .find(|entry| entry.name == "0123456789abcdef0123456789abcdef.nca")
+        .unwrap();
+    assert!(archive
+        .entry_bytes(&nsp, nca)
+        .ends_with(b"cli-binary-payload"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn usage_errors_exit_with_status_two() {
+    let root = scratch_dir("cli-usage");
+
+    let output = nsz(&root, &["-C", "--no-such-flag"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unrecognized arguments"));
+
+    let output = nsz(&root, &["-C", "-l", "high"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = nsz(&root, &["-C", "-o", "missing", "game.nsp"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not exist"));
+
+    let output = nsz(&root, &["--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("exit status"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn failures_map_to_distinct_exit_codes() {
+    let root = scratch_dir("cli-failures");
+    fs::write(root.join("broken.nsz"), b"not a container").unwrap();
+    fs::write(root.join("game.nsp"), fixture_nsp()).unwrap();
+
+    let output = nsz(&root, &["-D", "broken.nsz"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+
+    let output = nsz(&root, &["-V", "broken.nsz"]);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    let keys_file = root.join("prod.keys");
+    fs::write(
+        &keys_file,
+        format!(
+            "{}key_area_key_application_00 = {}\n",
+            keys_text(),
+            hex(&[0x01; 16])
+        ),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_nsz"))
+        .args(["-C", "game.nsp"])
+        .current_dir(&root)
+        .env("HOME", &root)
+        .env("NSZ_KEYS_FILE", &keys_file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+    assert!(!root.join("game.nsz").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn malformed_default_title_keys_fail_the_run() {
+    let root = scratch_dir("cli-title-keys");
+    fs::write(root.join("game.nsp"), fixture_nsp()).unwrap();
+    fs::write(root.join("title.keys"), "not a title key line\n").unwrap();
+
+    let output = nsz(&root, &["-C", "game.nsp"]);
+    assert!(!output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("title.keys"));
+    assert!(!root.join("game.nsz").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn keys_and_title_keys_flags_pick_the_key_files() {
+    let root = scratch_dir("cli-key-flags");
+    fs::write(root.join("game.nsp"), fixture_nsp()).unwrap();
+    fs::write(
+        root.join("wrong.keys"),
+        format!(
+            "{}key_area_key_application_00 = {}\n",
+            keys_text(),
+            hex(&[0x01; 16])
+        ),
+    )
+    .unwrap();
+    fs::write(root.join("broken.title.keys"), "not a title key line\n").unwrap();
+
+    let output = nsz(&root, &["-C", "--keys", "wrong.keys", "game.nsp"]);
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+    assert!(!root.join("game.nsz").exists());
+
+    let output = nsz(&root, &["-C", "--title-keys=broken.title.keys", "game.nsp"]);
+    assert!(!output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("broken.title.keys"));
+    assert!(!root.join("game.nsz").exists());
+
+    let output = nsz(&root, &["-V", "--keys", "missing.keys", "game.nsp"]);
+    assert_eq!(output.status.code(), Some(5), "{output:?}");
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn split_output_writes_parts_for_compress_and_decompress() {
+    let root = scratch_dir("cli-split-output");
+    fs::create_dir_all(root.join("out")).unwrap();
+    fs::write(root.join("game.nsp"), fixture_nsp()).unwrap();
+
+    let output = nsz(&root, &["-C", "--split-output", "-o", "out", "game.nsp"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(root.join("out/game.nsz/00").is_file());
+
+    let output = nsz(&root, &["-D", "--split-output", "out/game.nsz"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read(root.join("out/game.nsp/00")).unwrap(),
+        fs::read(root.join("game.nsp")).unwrap()
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn continue_on_error_processes_the_rest_of_the_batch() {
+    let root = scratch_dir("cli-continue-on-error");
+    fs::write(root.join("a-broken.nsp"), b"not a container").unwrap();
+    fs::write(root.join("b-game.nsp"), fixture_nsp()).unwrap();
+
+    let output = nsz(&root, &["-C", "a-broken.nsp", "b-game.nsp"]);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(!root.join("b-game.nsz").exists());
+
+    let output = nsz(
+        &root,
+        &["-C", "--continue-on-error", "a-broken.nsp", "b-game.nsp"],
+    );
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(root.join("b-game.nsz").is_file());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("a-broken.nsp"), "{stderr}");
+    assert!(stderr.contains("1 of 2 files failed"), "{stderr}");
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(not(feature = "serde"))]
+#[test]
+fn machine_readable_needs_the_serde_feature() {
+    let root = scratch_dir("cli-machine-readable-off");
+    let output = nsz(&root, &["--machine-readable", "-C", "game.nsp"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("serde feature"));
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn machine_readable_prints_json_lines() {
+    let root = scratch_dir("cli-machine-readable");
+    fs::write(root.join("game.nsp"), fixture_nsp()).unwrap();
+    fs::write(root.join("broken.nsz"), b"not a container").unwrap();
+
+    let output = nsz(&root, &["--machine-readable", "-C", "game.nsp"]);
+    assert!(output.status.success(), "{output:?}");
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "file");
+    assert_eq!(events[0]["status"], "processed");
+    assert!(events[0]["output_size"].as_u64().is_some());
+    assert_eq!(events[1]["event"], "summary");
+    assert_eq!(events[1]["processed"], 1);
+    assert_eq!(events[1]["exit_code"], 0);
+
+    let output = nsz(&root, &["--machine-readable", "-V", "broken.nsz"]);
+    assert_eq!(output.status.code(), Some(3));
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events[0]["status"], "failed");
+    assert_eq!(events[0]["error"]["kind"], "container_format");
+    assert_eq!(events[1]["failed"], 1);
+    assert_eq!(events[1]["exit_code"], 3);
+    assert_eq!(events[1]["error"]["kind"], "container_format");
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,467 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use sha2::{Digest, Sha256};
+
+pub const HEADER_KEY: [u8; 32] = [
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F,
+    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2A, 0x2B, 0x2C, 0x2D, 0x2E, 0x2F,
+];
+pub const MASTER_KEY_00: [u8; 16] = [0x30; 16];
+pub const AES_KEK_GENERATION_SOURCE: [u8; 16] = [0x41; 16];
+pub const AES_KEY_GENERATION_SOURCE: [u8; 16] = [0x52; 16];
+pub const TITLEKEK_SOURCE: [u8; 16] = [0x63; 16];
+pub const KEY_AREA_KEY_APPLICATION_SOURCE: [u8; 16] = [0x74; 16];
+pub const KEY_AREA_KEY_OCEAN_SOURCE: [u8; 16] = [0x85; 16];
+pub const KEY_AREA_KEY_SYSTEM_SOURCE: [u8; 16] = [0x96; 16];
+
+/// Returns an empty `nsz-rs-{name}-{pid}` directory under the system temp dir.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("nsz-rs-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// Writes [`keys_text`] to `root/prod.keys` and returns its path.
+pub fn write_keys_file(root: &Path) -> PathBuf {
+    let path = root.join("prod.keys");
+    fs::write(&path, keys_text()).unwrap();
+    path
+}
+
+/// Returns a `prod.keys` style document for the fixture key set.
+pub fn keys_text() -> String {
+    [
+        ("header_key", hex(&HEADER_KEY)),
+        ("master_key_00", hex(&MASTER_KEY_00)),
+        ("aes_kek_generation_source", hex(&AES_KEK_GENERATION_SOURCE)),
+        ("aes_key_generation_source", hex(&AES_KEY_GENERATION_SOURCE)),
+        ("titlekek_source", hex(&TITLEKEK_SOURCE)),
+        (
+            "key_area_key_application_source",
+            hex(&KEY_AREA_KEY_APPLICATION_SOURCE),
+        ),
+        ("key_area_key_ocean_source", hex(&KEY_AREA_KEY_OCEAN_SOURCE)),
+        (
+            "key_area_key_system_source",
+            hex(&KEY_AREA_KEY_SYSTEM_SOURCE),
+        ),
+    ]
+    .iter()
+    .fold(String::new(), |mut out, (name, value)| {
+        use std::fmt::Write;
+        let _ = writeln!(out, "{name} = {value}");
+        out
+    })
+}
+
+pub fn title_kek() -> [u8; 16] {
+    ecb_decrypt(&MASTER_KEY_00, &TITLEKEK_SOURCE)
+}
+
+pub fn key_area_key_application() -> [u8; 16] {
+    generate_kek(&KEY_AREA_KEY_APPLICATION_SOURCE)
+}
+
+/// Key area key for header index 0x207 (application, ocean, system).
+pub fn key_area_key(index: u8) -> [u8; 16] {
+    generate_kek(match index {
+        0 => &KEY_AREA_KEY_APPLICATION_SOURCE,
+        1 => &KEY_AREA_KEY_OCEAN_SOURCE,
+        _ => &KEY_AREA_KEY_SYSTEM_SOURCE,
+    })
+}
+
+/// Derives a key from `source` with `master_key_00` the way `generate_kek` does in the crate.
+pub fn generate_kek(source: &[u8; 16]) -> [u8; 16] {
+    let kek = ecb_decrypt(&MASTER_KEY_00, &AES_KEK_GENERATION_SOURCE);
+    let src_kek = ecb_decrypt(&kek, source);
+    ecb_decrypt(&src_kek, &AES_KEY_GENERATION_SOURCE)
+}
+
+/// Encrypts a plain title key the way it is stored in tickets and `title.keys`.
+pub fn encrypt_title_key(title_key: &[u8; 16]) -> [u8; 16] {
+    ecb_encrypt(&title_kek(), title_key)
+}
+
+pub struct SectionSpec {
+    /// Plaintext section payload, padded to 0x200 by the builder.
+    pub data: Vec<u8>,
+    /// FS header encryption type.
+    pub crypto_type: u8,
+    /// Upper 64 bits of the AES-CTR counter (`generation | secure_value << 32`).
+    pub ctr_upper: u64,
+}
+
+pub struct NcaSpec {
+    pub magic: [u8; 4],
+    pub content_type: u8,
+    pub rights_id: [u8; 16],
+    /// Key generation stored at 0x220; master key index is `key_generation - 1`.
+    pub key_generation: u8,
+    /// Key area encryption key index stored at 0x207.
+    pub key_area_key_index: u8,
+    pub title_key: [u8; 16],
+    pub sections: Vec<SectionSpec>,
+}
+
+impl NcaSpec {
+    pub fn program(title_key: [u8; 16], sections: Vec<SectionSpec>) -> Self {
+        Self {
+            magic: *b"NCA3",
+            content_type: 0,
+            rights_id: [0u8; 16],
+            key_generation: 0,
+            key_area_key_index: 0,
+            title_key,
+            sections,
+        }
+    }
+}
+
+pub const FIRST_SECTION_OFFSET: usize = 0x4000;
+
+/// Bytes at the start of every built section that its FS header's master hash covers.
+pub const HASH_TABLE_SIZE: usize = 0x20;
+
+/// Builds an encrypted NCA image for the fixture key set.
+pub fn build_nca(spec: &NcaSpec) -> Vec<u8> {
+    build_nca_with(spec, |_| {})
+}
+
+/// Like [`build_nca`], but lets `patch` edit the plaintext 0xC00-byte header first.
+pub fn build_nca_with(spec: &NcaSpec, patch: impl FnOnce(&mut [u8])) -> Vec<u8> {
+    let mut image = vec![0u8; FIRST_SECTION_OFFSET];
+    let mut table = Vec::new();
+    for section in &spec.sections {
+        let start = image.len();
+        let mut data = section.data.clone();
+        data.resize(data.len().next_multiple_of(0x200).max(0x200), 0);
+        let master_hash: [u8; 32] = Sha256::digest(&data[..HASH_TABLE_SIZE]).into();
+        if matches!(section.crypto_type, 3..=6) {
+            ctr_apply(&mut data, &spec.title_key, section.ctr_upper, start as u64);
+        }
+        image.extend_from_slice(&data);
+        table.push((start, image.len(), master_hash));
+    }
+
+    let mut header = vec![0u8; 0xC00];
+    header[0x200..0x204].copy_from_slice(&spec.magic);
+    header[0x205] = spec.content_type;
+    header[0x208..0x210].copy_from_slice(&(image.len() as u64).to_le_bytes());
+    header[0x207] = spec.key_area_key_index;
+    header[0x220] = spec.key_generation;
+    header[0x230..0x240].copy_from_slice(&spec.rights_id);
+    for (index, (start, end, master_hash)) in table.iter().enumerate() {
+        let cursor = 0x240 + index * 0x10;
+        header[cursor..cursor + 4].copy_from_slice(&((*start / 0x200) as u32).to_le_bytes());
+        header[cursor + 4..cursor + 8].copy_from_slice(&((*end / 0x200) as u32).to_le_bytes());
+        let fs_header = 0x400 + index * 0x200;
+        header[fs_header..fs_header + 2].copy_from_slice(&2u16.to_le_bytes());
+        header[fs_header + 2] = 1;
+        header[fs_header + 3] = 2;
+        header[fs_header + 4] = spec.sections[index].crypto_type;
+        header[fs_header + 0x8..fs_header + 0x28].copy_from_slice(master_hash);
+        header[fs_header + 0x38..fs_header + 0x40]
+            .copy_from_slice(&(HASH_TABLE_SIZE as u64).to_le_bytes());
+        header[fs_header + 0x140..fs_header + 0x148]
+            .copy_from_slice(&spec.sections[index].ctr_upper.to_le_bytes());
+    }
+    if spec.rights_id == [0u8; 16] {
+        let mut key_area = [0u8; 0x40];
+        key_area[0x20..0x30].copy_from_slice(&spec.title_key);
+        let kak = key_area_key(spec.key_area_key_index);
+        for block in key_area.chunks_exact_mut(16) {
+            let plain: [u8; 16] = (&*block).try_into().unwrap();
+            let encrypted = ecb_encrypt(&kak, &plain);
+            block.copy_from_slice(&encrypted);
+        }
+        header[0x300..0x340].copy_from_slice(&key_area);
+    }
+
+    patch(&mut header);
+    match &spec.magic {
+        b"NCA2" => {
+            xts_encrypt(&mut header[..0x400], &HEADER_KEY, 0);
+            for fs_header in header[0x400..].chunks_exact_mut(0x200) {
+                xts_encrypt(fs_header, &HEADER_KEY, 0);
+            }
+        }
+        b"NCA0" => xts_encrypt(&mut header[..0x400], &HEADER_KEY, 0),
+        _ => xts_encrypt(&mut header, &HEADER_KEY, 0),
+    }
+    image[..0xC00].copy_from_slice(&header);
+    image
+}
+
+/// Builds a common ticket carrying `encrypted_title_key` for `rights_id`.
+pub fn build_ticket(rights_id: [u8; 16], encrypted_title_key: [u8; 16]) -> Vec<u8> {
+    let base = 4 + 0x100 + 0x3C;
+    let mut out = vec![0u8; base + 0x180];
+    out[0..4].copy_from_slice(&0x0001_0004u32.to_le_bytes());
+    out[base + 0x40..base + 0x50].copy_from_slice(&encrypted_title_key);
+    out[base + 0x145] = rights_id[15];
+    out[base + 0x160..base + 0x170].copy_from_slice(&rights_id);
+    out
+}
+
+pub fn ctr_apply(data: &mut [u8], key: &[u8; 16], ctr_upper: u64, offset: u64) {
+    type AesCtr = ctr::Ctr128BE<Aes128>;
+    let mut counter = [0u8; 16];
+    counter[..8].copy_from_slice(&ctr_upper.to_be_bytes());
+    let mut cipher = AesCtr::new(key.into(), (&counter).into());
+    cipher.seek(u128::from(offset));
+    cipher.apply_keystream(data);
+}
+
+pub fn xts_encrypt(data: &mut [u8], key: &[u8; 32], first_sector: u64) {
+    let cipher_data = Aes128::new_from_slice(&key[..16]).unwrap();
+    let cipher_tweak = Aes128::new_from_slice(&key[16..]).unwrap();
+    for (index, sector) in data.chunks_exact_mut(0x200).enumerate() {
+        let mut tweak = [0u8; 16];
+        tweak[8..16].copy_from_slice(&(first_sector + index as u64).to_be_bytes());
+        cipher_tweak.encrypt_block(GenericArray::from_mut_slice(&mut tweak));
+        for block in sector.chunks_exact_mut(16) {
+            for (value, mask) in block.iter_mut().zip(tweak.iter()) {
+                *value ^= mask;
+            }
+            cipher_data.encrypt_block(GenericArray::from_mut_slice(block));
+            for (value, mask) in block.iter_mut().zip(tweak.iter()) {
+                *value ^= mask;
+            }
+            let mut carry = 0u8;
+            for byte in &mut tweak {
+                let next = *byte >> 7;
+                *byte = (*byte << 1) | carry;
+                carry = next;
+            }
+            if carry != 0 {
+                tweak[0] ^= 0x87;
+            }
+        }
+    }
+}
+
+pub fn ecb_encrypt(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+    let cipher = Aes128::new_from_slice(key).unwrap();
+    let mut out = *block;
+    cipher.encrypt_block(GenericArray::from_mut_slice(&mut out));
+    out
+}
+
+pub fn ecb_decrypt(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+    let cipher = Aes128::new_from_slice(key).unwrap();
+    let mut out = *block;
+    cipher.decrypt_block(GenericArray::from_mut_slice(&mut out));
+    out
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+pub fn build_pfs0(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut string_table = Vec::new();
+    let mut string_offsets = Vec::with_capacity(entries.len());
+    for (name, _) in entries {
+        string_offsets.push(u32::try_from(string_table.len()).unwrap());
+        string_table.extend_from_slice(name.as_bytes());
+        string_table.push(0);
+    }
+
+    let header_size = 16 + entries.len() * 24 + string_table.len();
+    let mut out = Vec::new();
+    out.extend_from_slice(b"PFS0");
+    out.extend_from_slice(&u32::try_from(entries.len()).unwrap().to_le_bytes());
+    out.extend_from_slice(&u32::try_from(string_table.len()).unwrap().to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut offset = 0u64;
+    for ((_, data), string_offset) in entries.iter().zip(string_offsets.iter()) {
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(&string_offset.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        offset += data.len() as u64;
+    }
+
+    out.extend_from_slice(&string_table);
+    out.resize(header_size, 0);
+    for (_, data) in entries {
+        out.extend_from_slice(data);
+    }
+    out
+}
+
+pub fn build_hfs0(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut string_table = Vec::new();
+    let mut string_offsets = Vec::with_capacity(entries.len());
+    for (name, _) in entries {
+        string_offsets.push(u32::try_from(string_table.len()).unwrap());
+        string_table.extend_from_slice(name.as_bytes());
+        string_table.push(0);
+    }
+
+    let header_size = 16 + entries.len() * 0x40 + string_table.len();
+    let mut out = Vec::new();
+    out.extend_from_slice(b"HFS0");
+    out.extend_from_slice(&u32::try_from(entries.len()).unwrap().to_le_bytes());
+    out.extend_from_slice(&u32::try_from(string_table.len()).unwrap().to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut offset = 0u64;
+    for ((_, data), string_offset) in entries.iter().zip(string_offsets.iter()) {
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(&string_offset.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        out.extend_from_slice(&[0u8; 32]);
+        offset += data.len() as u64;
+    }
+
+    out.extend_from_slice(&string_table);
+    out.resize(header_size, 0);
+    for (_, data) in entries {
+        out.extend_from_slice(data);
+    }
+    out
+}
+
+/// Wraps partitions into a root HFS0 behind a minimal XCI header.
+pub fn build_xci(partitions: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let root_hfs0 = build_hfs0(
+        &partitions
+            .iter()
+            .map(|(name, data)| ((*name).to_string(), data.clone()))
+            .collect::<Vec<_>>(),
+    );
+    let hfs0_offset = 0xF000u64;
+    let mut out = vec![0u8; 0x200];
+    out[0x100..0x104].copy_from_slice(b"HEAD");
+    out[0x130..0x138].copy_from_slice(&hfs0_offset.to_le_bytes());
+    let header_size = hfs0_header_size(&root_hfs0);
+    out[0x138..0x140].copy_from_slice(&header_size.to_le_bytes());
+    out[0x140..0x160].copy_from_slice(&Sha256::digest(&root_hfs0[..header_size as usize]));
+    out.resize(usize::try_from(hfs0_offset).unwrap(), 0);
+    out.extend_from_slice(&root_hfs0);
+    out
+}
+
+/// Builds raw `.cnmt` bytes listing `contents` as `(content id, content type)`.
+pub fn build_cnmt(title_id: u64, meta_type: u8, contents: &[([u8; 16], u8)]) -> Vec<u8> {
+    let mut out = vec![0u8; 0x30];
+    out[0x00..0x08].copy_from_slice(&title_id.to_le_bytes());
+    out[0x0C] = meta_type;
+    out[0x0E..0x10].copy_from_slice(&0x10u16.to_le_bytes());
+    out[0x10..0x12].copy_from_slice(&u16::try_from(contents.len()).unwrap().to_le_bytes());
+    for (content_id, content_type) in contents {
+        let mut record = [0u8; 0x38];
+        record[0x20..0x30].copy_from_slice(content_id);
+        record[0x30..0x36].copy_from_slice(&0x4000u64.to_le_bytes()[..6]);
+        record[0x36] = *content_type;
+        out.extend_from_slice(&record);
+    }
+    out
+}
+
+/// Builds a standard-crypto meta NCA whose first section is a PFS0 holding `cnmt`.
+pub fn build_meta_nca(title_key: [u8; 16], title_id: u64, cnmt: &[u8]) -> Vec<u8> {
+    let name = format!("Application_{title_id:016x}.cnmt");
+    let pfs0 = build_pfs0(&[(name.as_str(), cnmt)]);
+    let mut spec = NcaSpec::program(
+        title_key,
+        vec![SectionSpec {
+            data: pfs0.clone(),
+            crypto_type: 3,
+            ctr_upper: 0x0000_0001_0000_0000,
+        }],
+    );
+    spec.content_type = 1;
+    build_nca_with(&spec, |header| {
+        header[0x440..0x448].copy_from_slice(&0u64.to_le_bytes());
+        header[0x448..0x450].copy_from_slice(&(pfs0.len() as u64).to_le_bytes());
+    })
+}
+
+/// Builds a program NCA holding `0x2000` bytes of `fill`: standard crypto when `rights_id`
+/// is zero, titlekey crypto under `title_key` otherwise.
+pub fn content_nca(rights_id: [u8; 16], title_key: [u8; 16], fill: u8) -> Vec<u8> {
+    let standard = rights_id == [0u8; 16];
+    let mut spec = NcaSpec::program(
+        if standard { [0x19; 16] } else { title_key },
+        vec![SectionSpec {
+            data: vec![fill; 0x2000],
+            crypto_type: 3,
+            ctr_upper: 0x0000_0001_0000_0000,
+        }],
+    );
+    spec.rights_id = rights_id;
+    build_nca(&spec)
+}
+
+/// Builds a `{meta_id}.cnmt.nca` entry for a title at `version` listing one program NCA.
+pub fn cnmt_entry(
+    meta_id: u8,
+    title_id: u64,
+    meta_type: u8,
+    version: u32,
+    content: [u8; 16],
+) -> (String, Vec<u8>) {
+    let mut cnmt = build_cnmt(title_id, meta_type, &[(content, 1)]);
+    cnmt[0x08..0x0C].copy_from_slice(&version.to_le_bytes());
+    (
+        format!("{}.cnmt.nca", hex(&[meta_id; 16])),
+        build_meta_nca([meta_id; 16], title_id, &cnmt),
+    )
+}
+
+/// Stand-in NCA that skips crypto: a zeroed 0x4000-byte header followed by `payload`.
+pub fn raw_nca(payload: impl IntoIterator<Item = u8>) -> Vec<u8> {
+    let mut nca = vec![0u8; 0x4000];
+    nca.extend(payload);
+    nca
+}
+
+/// Writes `entries` to `path` as a PFS0.
+pub fn write_nsp(path: &Path, entries: &[(String, Vec<u8>)]) {
+    let refs = entries
+        .iter()
+        .map(|(name, data)| (name.as_str(), data.as_slice()))
+        .collect::<Vec<_>>();
+    fs::write(path, build_pfs0(&refs)).unwrap();
+}
+
+/// Reads the PFS0 at `path` back as `(name, bytes)` entries.
+pub fn read_nsp(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let data = fs::read(path).unwrap();
+    let archive = nsz_rs::container::nsp::NspArchive::from_bytes(&data).unwrap();
+    archive
+        .entries()
+        .iter()
+        .map(|entry| {
+            (
+                entry.name.clone(),
+                archive.entry_bytes(&data, entry).to_vec(),
+            )
+        })
+        .collect()
+}
+
+/// Size of an HFS0 header: the fixed header, entry table and string table.
+fn hfs0_header_size(hfs0: &[u8]) -> u64 {
+    let count = u32::from_le_bytes(hfs0[4..8].try_into().unwrap());
+    let string_table_size = u32::from_le_bytes(hfs0[8..12].try_into().unwrap());
+    0x10 + u64::from(count) * 0x40 + u64::from(string_table_size)
+}
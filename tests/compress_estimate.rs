@@ -0,0 +1,90 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_pfs0, raw_nca, scratch_dir};
+use nsz_rs::FileStatus;
+
+const NCA_NAME: &str = "fedcba9876543210fedcba9876543210.nca";
+
+/// An NSP whose NCA payload is `len` bytes of a short repeating pattern.
+fn fixture_nsp(len: usize) -> Vec<u8> {
+    let nca = raw_nca((0..len).map(|index| (index % 97) as u8));
+    build_pfs0(&[(NCA_NAME, &nca), ("ticket.tik", b"ticket")])
+}
+
+fn request(files: Vec<PathBuf>, out_dir: PathBuf) -> nsz_rs::CompressRequest {
+    nsz_rs::CompressRequest {
+        files,
+        output_dir: Some(out_dir),
+        level: 1,
+        dry_run: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn dry_run_estimates_without_writing() {
+    let root = scratch_dir("estimate-small");
+    let input = root.join("game.nsp");
+    fs::write(&input, fixture_nsp(0x20000)).unwrap();
+    let other = root.join("game.nsz");
+    fs::write(&other, b"not compressed again").unwrap();
+    let out_dir = root.join("out");
+
+    let report = nsz_rs::compress(&request(
+        vec![input.clone(), other.clone()],
+        out_dir.clone(),
+    ))
+    .unwrap();
+    assert!(!out_dir.exists());
+    assert!(report.processed_files.is_empty());
+    assert_eq!(report.skipped_files, vec![other]);
+    let estimate = &report.files[0];
+    assert_eq!(estimate.status, FileStatus::Estimated);
+    assert_eq!(estimate.output, Some(out_dir.join("game.nsz")));
+    assert!(estimate.estimated_duration.is_some());
+
+    // A payload smaller than one sample chunk is compressed whole, so the estimate only
+    // misses container padding.
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        dry_run: false,
+        ..request(vec![input], out_dir)
+    })
+    .unwrap();
+    let actual = report.files[0].output_size.unwrap();
+    let estimated = estimate.output_size.unwrap();
+    assert!(
+        actual.abs_diff(estimated) <= 0x200,
+        "{actual} vs {estimated}"
+    );
+    assert!(estimate.ratio.unwrap() < 0.5);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn dry_run_samples_large_ncas() {
+    let root = scratch_dir("estimate-sampled");
+    let input = root.join("big.nsp");
+    let payload = 3 * 0x0100_0000;
+    fs::write(&input, fixture_nsp(payload)).unwrap();
+
+    let estimate = |sample_chunks| {
+        let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+            estimate_sample_chunks: sample_chunks,
+            ..request(vec![input.clone()], root.join("out"))
+        })
+        .unwrap();
+        report.files[0].output_size.unwrap()
+    };
+    let one = estimate(0);
+    let all = estimate(3);
+    assert!(one < payload as u64 / 10, "{one}");
+    assert!(one.abs_diff(all) <= all / 100, "{one} vs {all}");
+    assert!(!root.join("out").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
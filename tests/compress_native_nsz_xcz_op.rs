@@ -182,8 +182,15 @@ fn build_xci_like(root_hfs0: &[u8]) -> Vec<u8> {
     let mut out = vec![0u8; 0x200];
     out[0x100..0x104].copy_from_slice(b"HEAD");
     out[0x130..0x138].copy_from_slice(&hfs0_offset.to_le_bytes());
-    out[0x138..0x140].copy_from_slice(&(root_hfs0.len() as u64).to_le_bytes());
+    out[0x138..0x140].copy_from_slice(&hfs0_header_size(root_hfs0).to_le_bytes());
     out.resize(usize::try_from(hfs0_offset).unwrap(), 0);
     out.extend_from_slice(root_hfs0);
     out
 }
+
+/// Size of an HFS0 header: the fixed header, entry table and string table.
+fn hfs0_header_size(hfs0: &[u8]) -> u64 {
+    let count = u32::from_le_bytes(hfs0[4..8].try_into().unwrap());
+    let string_table_size = u32::from_le_bytes(hfs0[8..12].try_into().unwrap());
+    0x10 + u64::from(count) * 0x40 + u64::from(string_table_size)
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_pfs0, hex, raw_nca, scratch_dir};
+use nsz_rs::{FileStatus, NszError};
+use sha2::{Digest, Sha256};
+
+const BAD_NCA: &str = "0123456789abcdef0123456789abcdef.nca";
+
+fn fixture_nsp(payload: &[u8]) -> Vec<u8> {
+    build_pfs0(&[(
+        "fedcba9876543210fedcba9876543210.nca",
+        &raw_nca(payload.iter().copied()),
+    )])
+}
+
+#[test]
+fn compress_records_failures_and_finishes_the_batch() {
+    let root = scratch_dir("continue-on-error-compress");
+    let first = root.join("first.nsp");
+    let broken = root.join("broken.nsp");
+    let last = root.join("last.nsp");
+    fs::write(&first, fixture_nsp(b"first-payload")).unwrap();
+    fs::write(&broken, b"not a container").unwrap();
+    fs::write(&last, fixture_nsp(b"last-payload")).unwrap();
+    let request = nsz_rs::CompressRequest {
+        files: vec![first, broken.clone(), last],
+        output_dir: Some(root.join("out")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    };
+
+    let err = nsz_rs::compress(&request).unwrap_err();
+    assert!(matches!(err, NszError::ContainerFormat { .. }), "{err}");
+    assert!(!root.join("out/last.nsz").exists());
+
+    let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+        continue_on_error: true,
+        ..request
+    })
+    .unwrap_err();
+    let NszError::BatchFailed {
+        failed,
+        total,
+        files,
+    } = &err
+    else {
+        panic!("expected a batch failure, got {err}");
+    };
+    assert_eq!((*failed, *total), (1, 3));
+    assert_eq!(err.kind(), "batch_failed");
+    let statuses: Vec<_> = files.iter().map(|file| file.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            FileStatus::Processed,
+            FileStatus::Failed,
+            FileStatus::Processed
+        ]
+    );
+    assert!(files[0].result().is_ok());
+    let details = files[1].result().unwrap_err();
+    assert_eq!(files[1].input, broken);
+    assert_eq!(details.kind, "container_format");
+    assert_eq!(details.entry, None);
+    assert_eq!(files[2].output.as_ref(), Some(&root.join("out/last.nsz")));
+    assert!(root.join("out/last.nsz").is_file());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn verify_names_the_failing_entry_and_keeps_going() {
+    let root = scratch_dir("continue-on-error-verify");
+    let good_nca = b"good-nca-payload".to_vec();
+    let good_name = format!("{}.nca", hex(&Sha256::digest(&good_nca)[..16]));
+    let corrupt = root.join("corrupt.nsp");
+    let intact = root.join("intact.nsp");
+    fs::write(
+        &corrupt,
+        build_pfs0(&[
+            (good_name.as_str(), good_nca.as_slice()),
+            (BAD_NCA, b"tampered"),
+        ]),
+    )
+    .unwrap();
+    fs::write(
+        &intact,
+        build_pfs0(&[(good_name.as_str(), good_nca.as_slice())]),
+    )
+    .unwrap();
+
+    let err = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        files: vec![corrupt.clone(), intact.clone()],
+        continue_on_error: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    let NszError::BatchFailed { failed, files, .. } = err else {
+        panic!("expected a batch failure, got {err}");
+    };
+    assert_eq!(failed, 1);
+    assert_eq!(files[0].status, FileStatus::Failed);
+    let details = files[0].error.as_ref().unwrap();
+    assert_eq!(details.kind, "hash_mismatch");
+    assert_eq!(details.entry.as_deref(), Some(BAD_NCA));
+    assert!(details.message.starts_with(BAD_NCA), "{}", details.message);
+    assert_eq!(files[1].input, intact);
+    assert_eq!(files[1].status, FileStatus::Verified);
+
+    let err = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        files: vec![corrupt],
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.entry(), Some(BAD_NCA));
+    assert!(matches!(err.root(), NszError::HashMismatch { .. }), "{err}");
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn decompress_reports_the_broken_ncz_entry() {
+    let root = scratch_dir("continue-on-error-decompress");
+    let broken = root.join("broken.nsz");
+    fs::write(
+        &broken,
+        build_pfs0(&[("fedcba9876543210fedcba9876543210.ncz", b"truncated")]),
+    )
+    .unwrap();
+
+    let err = nsz_rs::decompress(&nsz_rs::DecompressRequest {
+        files: vec![broken.clone()],
+        output_dir: Some(root.join("out")),
+        continue_on_error: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    let NszError::BatchFailed { files, .. } = err else {
+        panic!("expected a batch failure, got {err}");
+    };
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].input, broken);
+    assert_eq!(
+        files[0].result().unwrap_err().entry.as_deref(),
+        Some("fedcba9876543210fedcba9876543210.ncz")
+    );
+    assert!(!root.join("out/broken.nsp").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
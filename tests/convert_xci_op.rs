@@ -0,0 +1,185 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{
+    build_cnmt, build_hfs0, build_meta_nca, build_ticket, build_xci, content_nca,
+    encrypt_title_key, hex, read_nsp, scratch_dir, write_keys_file,
+};
+
+const BASE_TITLE: u64 = 0x0100_0000_0000_1000;
+const DLC_TITLE: u64 = 0x0100_0000_0000_2001;
+const PROGRAM_ID: [u8; 16] = [0xA1; 16];
+const DLC_ID: [u8; 16] = [0xB2; 16];
+const DLC_RIGHTS_ID: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const DLC_TITLE_KEY: [u8; 16] = [0x7D; 16];
+
+/// A cart holding a standard-crypto base game and a titlekey-crypto DLC.
+fn cart_entries(with_dlc_ticket: bool) -> Vec<(String, Vec<u8>)> {
+    let mut entries = vec![
+        (
+            format!("{}.nca", hex(&PROGRAM_ID)),
+            content_nca([0u8; 16], DLC_TITLE_KEY, 0x11),
+        ),
+        (
+            "c0000000000000000000000000000001.cnmt.nca".to_string(),
+            build_meta_nca(
+                [0x31; 16],
+                BASE_TITLE,
+                &build_cnmt(BASE_TITLE, 0x80, &[(PROGRAM_ID, 1)]),
+            ),
+        ),
+        (
+            format!("{}.nca", hex(&DLC_ID)),
+            content_nca(DLC_RIGHTS_ID, DLC_TITLE_KEY, 0x22),
+        ),
+        (
+            "c0000000000000000000000000000002.cnmt.nca".to_string(),
+            build_meta_nca(
+                [0x32; 16],
+                DLC_TITLE,
+                &build_cnmt(DLC_TITLE, 0x82, &[(DLC_ID, 2)]),
+            ),
+        ),
+    ];
+    if with_dlc_ticket {
+        entries.push((
+            format!("{}.tik", hex(&DLC_RIGHTS_ID)),
+            build_ticket(DLC_RIGHTS_ID, encrypt_title_key(&DLC_TITLE_KEY)),
+        ));
+    }
+    entries.push((format!("{}.cert", hex(&DLC_RIGHTS_ID)), vec![0xCE; 0x700]));
+    entries
+}
+
+/// Reads a converted NSP, whose first file must start 0x10-aligned like upstream output.
+fn read_aligned_nsp(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let data = fs::read(path).unwrap();
+    let archive = nsz_rs::container::nsp::NspArchive::from_bytes(&data).unwrap();
+    assert_eq!(archive.first_file_offset() % 0x10, 0);
+    read_nsp(path)
+}
+
+#[test]
+fn convert_xci_writes_secure_contents_then_tickets() {
+    let root = scratch_dir("convert-whole");
+    let keys_file = write_keys_file(&root);
+    let entries = cart_entries(true);
+    let input = root.join("cart.xci");
+    fs::write(
+        &input,
+        build_xci(&[
+            ("update", build_hfs0(&[])),
+            ("secure", build_hfs0(&entries)),
+        ]),
+    )
+    .unwrap();
+
+    let report = nsz_rs::convert(&nsz_rs::ConvertRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        ..Default::default()
+    })
+    .unwrap();
+
+    assert_eq!(
+        report.processed_files,
+        vec![root.join("out").join("cart.nsp")]
+    );
+    assert_eq!(read_aligned_nsp(&report.processed_files[0]), entries);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn convert_splits_titles_and_generates_missing_tickets() {
+    let root = scratch_dir("convert-split");
+    let keys_file = write_keys_file(&root);
+    let title_keys_file = root.join("title.keys");
+    fs::write(
+        &title_keys_file,
+        format!(
+            "{} = {}\n",
+            hex(&DLC_RIGHTS_ID),
+            hex(&encrypt_title_key(&DLC_TITLE_KEY))
+        ),
+    )
+    .unwrap();
+    let entries = cart_entries(false);
+    let input = root.join("cart.xci");
+    fs::write(&input, build_xci(&[("secure", build_hfs0(&entries))])).unwrap();
+
+    let report = nsz_rs::convert(&nsz_rs::ConvertRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        split_titles: true,
+        keys_file: Some(keys_file),
+        title_keys_file: Some(title_keys_file),
+    })
+    .unwrap();
+
+    assert_eq!(
+        report.processed_files,
+        vec![
+            root.join("out").join("cart [0100000000001000].nsp"),
+            root.join("out").join("cart [0100000000002001].nsp"),
+        ]
+    );
+
+    let base = read_aligned_nsp(&report.processed_files[0]);
+    assert_eq!(base, entries[..2].to_vec());
+
+    let dlc = read_aligned_nsp(&report.processed_files[1]);
+    let names = dlc
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    let rights = hex(&DLC_RIGHTS_ID);
+    assert_eq!(
+        names,
+        vec![
+            entries[2].0.as_str(),
+            entries[3].0.as_str(),
+            &format!("{rights}.tik"),
+            &format!("{rights}.cert"),
+        ]
+    );
+    let ticket = nsz_rs::container::nca::parse_ticket_record(&dlc[2].1).unwrap();
+    assert_eq!(ticket.rights_id, DLC_RIGHTS_ID);
+    assert_eq!(
+        ticket.encrypted_title_key,
+        encrypt_title_key(&DLC_TITLE_KEY)
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn convert_reports_missing_ticket_for_rights_id_content() {
+    let root = scratch_dir("convert-missing-ticket");
+    let keys_file = write_keys_file(&root);
+    let title_keys_file = root.join("title.keys");
+    fs::write(&title_keys_file, "").unwrap();
+    let input = root.join("cart.xcz");
+    fs::write(
+        &input,
+        build_xci(&[("secure", build_hfs0(&cart_entries(false)))]),
+    )
+    .unwrap();
+
+    let err = nsz_rs::convert(&nsz_rs::ConvertRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        title_keys_file: Some(title_keys_file),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(err.to_string().contains("rights id"), "{err}");
+
+    let _ = fs::remove_dir_all(root);
+}
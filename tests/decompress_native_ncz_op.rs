@@ -30,7 +30,13 @@ fn decompress_uses_native_path_for_ncz_inputs() {
         files: vec![input],
         output_dir: Some(out_dir.clone()),
         fix_padding: false,
+        split_output: false,
+        continue_on_error: false,
+        journal: None,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
@@ -39,7 +39,13 @@ fn decompress_uses_native_path_for_xcz_inputs() {
         files: vec![input],
         output_dir: Some(out_dir.clone()),
         fix_padding: false,
+        split_output: false,
+        continue_on_error: false,
+        journal: None,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
@@ -114,8 +120,15 @@ fn build_xci_like(root_hfs0: &[u8]) -> Vec<u8> {
     let mut out = vec![0u8; 0x200];
     out[0x100..0x104].copy_from_slice(b"HEAD");
     out[0x130..0x138].copy_from_slice(&hfs0_offset.to_le_bytes());
-    out[0x138..0x140].copy_from_slice(&(root_hfs0.len() as u64).to_le_bytes());
+    out[0x138..0x140].copy_from_slice(&hfs0_header_size(root_hfs0).to_le_bytes());
     out.resize(usize::try_from(hfs0_offset).unwrap(), 0);
     out.extend_from_slice(root_hfs0);
     out
 }
+
+/// Size of an HFS0 header: the fixed header, entry table and string table.
+fn hfs0_header_size(hfs0: &[u8]) -> u64 {
+    let count = u32::from_le_bytes(hfs0[4..8].try_into().unwrap());
+    let string_table_size = u32::from_le_bytes(hfs0[8..12].try_into().unwrap());
+    0x10 + u64::from(count) * 0x40 + u64::from(string_table_size)
+}
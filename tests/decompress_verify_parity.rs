@@ -51,7 +51,13 @@ fn decompress_verify_matches_python_for_fixture() {
             files: vec![source_nsz.clone()],
             output_dir: Some(rust_out.clone()),
             fix_padding: false,
+            split_output: false,
+            continue_on_error: false,
+            journal: None,
+            keys_file: None,
             python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            progress: nsz_rs::Progress::default(),
+            cancel: nsz_rs::CancellationToken::default(),
         })
         .unwrap();
 
@@ -74,7 +80,11 @@ fn decompress_verify_matches_python_for_fixture() {
         let verify_nsz = nsz_rs::verify(&nsz_rs::VerifyRequest {
             files: vec![source_nsz.clone()],
             fix_padding: false,
+            continue_on_error: false,
+            keys_file: None,
             python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            progress: nsz_rs::Progress::default(),
+            cancel: nsz_rs::CancellationToken::default(),
         })
         .unwrap();
         assert_eq!(verify_nsz.verified_files, vec![source_nsz.clone()]);
@@ -82,7 +92,11 @@ fn decompress_verify_matches_python_for_fixture() {
         let verify_decompressed_nsp = nsz_rs::verify(&nsz_rs::VerifyRequest {
             files: vec![rust_nsp.clone()],
             fix_padding: false,
+            continue_on_error: false,
+            keys_file: None,
             python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            progress: nsz_rs::Progress::default(),
+            cancel: nsz_rs::CancellationToken::default(),
         })
         .unwrap();
         assert_eq!(verify_decompressed_nsp.verified_files, vec![rust_nsp]);
@@ -99,7 +113,11 @@ fn decompress_verify_matches_python_for_fixture() {
         let verify_nsp = nsz_rs::verify(&nsz_rs::VerifyRequest {
             files: vec![source_nsp.clone()],
             fix_padding: false,
+            continue_on_error: false,
+            keys_file: None,
             python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            progress: nsz_rs::Progress::default(),
+            cancel: nsz_rs::CancellationToken::default(),
         })
         .unwrap();
         assert_eq!(verify_nsp.verified_files, vec![source_nsp.clone()]);
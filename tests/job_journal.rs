@@ -0,0 +1,162 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{build_pfs0, raw_nca, scratch_dir};
+use nsz_rs::FileStatus;
+
+const NCA_NAME: &str = "fedcba9876543210fedcba9876543210.nca";
+
+fn write_nsp(path: &Path, payload: u8, len: usize) {
+    let nca = raw_nca(std::iter::repeat_n(payload, len));
+    fs::write(path, build_pfs0(&[(NCA_NAME, &nca)])).unwrap();
+}
+
+fn compress(files: &[PathBuf], out_dir: &Path, journal: &Path) -> nsz_rs::OperationReport {
+    nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: files.to_vec(),
+        output_dir: Some(out_dir.to_path_buf()),
+        level: 1,
+        journal: Some(journal.to_path_buf()),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn rerun_skips_finished_inputs_and_redoes_broken_ones() {
+    let root = scratch_dir("journal-compress");
+    let files = ["a.nsp", "b.nsp", "c.nsp"].map(|name| root.join(name));
+    for (payload, file) in files.iter().enumerate() {
+        write_nsp(file, payload as u8, 0x8000);
+    }
+    let out_dir = root.join("out");
+    let journal = root.join("job.journal");
+
+    let first = compress(&files, &out_dir, &journal);
+    assert_eq!(first.processed_files.len(), 3);
+    assert!(first.resumed_files.is_empty());
+    assert_eq!(fs::read_to_string(&journal).unwrap().lines().count(), 3);
+
+    let second = compress(&files, &out_dir, &journal);
+    assert_eq!(second.resumed_files, files);
+    assert!(second.processed_files.is_empty());
+    assert!(second
+        .files
+        .iter()
+        .all(|file| file.status == FileStatus::Resumed && file.output.is_some()));
+
+    // A truncated output and a changed input are both redone.
+    let a_out = out_dir.join("a.nsz");
+    let intact = fs::read(&a_out).unwrap();
+    fs::write(&a_out, &intact[..intact.len() / 2]).unwrap();
+    write_nsp(&files[2], 7, 0x9000);
+
+    let third = compress(&files, &out_dir, &journal);
+    assert_eq!(third.resumed_files, vec![files[1].clone()]);
+    assert_eq!(
+        third.processed_files,
+        vec![a_out.clone(), out_dir.join("c.nsz")]
+    );
+    assert_eq!(fs::read(&a_out).unwrap(), intact);
+    assert_eq!(fs::read_to_string(&journal).unwrap().lines().count(), 5);
+
+    let fourth = compress(&files, &out_dir, &journal);
+    assert_eq!(fourth.resumed_files, files);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn journal_tolerates_torn_lines_and_keeps_operations_apart() {
+    let root = scratch_dir("journal-torn");
+    let input = root.join("game.nsp");
+    write_nsp(&input, 3, 0x8000);
+    let out_dir = root.join("out");
+    let journal = root.join("job.journal");
+    fs::write(&journal, "compress\t123\t45").unwrap();
+
+    let report = compress(std::slice::from_ref(&input), &out_dir, &journal);
+    assert_eq!(report.processed_files, vec![out_dir.join("game.nsz")]);
+    let content = fs::read_to_string(&journal).unwrap();
+    assert!(
+        content.starts_with("compress\t123\t45\ncompress\t"),
+        "{content}"
+    );
+    assert_eq!(
+        compress(std::slice::from_ref(&input), &out_dir, &journal).resumed_files,
+        vec![input]
+    );
+
+    let decompress = || {
+        nsz_rs::decompress(&nsz_rs::DecompressRequest {
+            files: vec![out_dir.join("game.nsz")],
+            output_dir: Some(root.join("restored")),
+            journal: Some(journal.clone()),
+            python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            ..Default::default()
+        })
+        .unwrap()
+    };
+    let report = decompress();
+    assert_eq!(report.processed_files, vec![root.join("restored/game.nsp")]);
+    assert!(report.resumed_files.is_empty());
+    assert_eq!(decompress().resumed_files, vec![out_dir.join("game.nsz")]);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn outputs_are_rehashed_only_when_their_stamp_changed() {
+    let root = scratch_dir("journal-stamps");
+    let input = root.join("game.nsp");
+    write_nsp(&input, 5, 0x8000);
+    let out_dir = root.join("out");
+    let journal = root.join("job.journal");
+    compress(std::slice::from_ref(&input), &out_dir, &journal);
+    let output = out_dir.join("game.nsz");
+    let intact = fs::read(&output).unwrap();
+    let recorded = fs::metadata(&output).unwrap().modified().unwrap();
+
+    // Same size and modification time: trusted without reading the output.
+    let mut flipped = intact.clone();
+    *flipped.last_mut().unwrap() ^= 0xFF;
+    fs::write(&output, &flipped).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&output)
+        .unwrap()
+        .set_modified(recorded)
+        .unwrap();
+    let report = compress(std::slice::from_ref(&input), &out_dir, &journal);
+    assert_eq!(report.resumed_files, vec![input.clone()]);
+
+    // A newer modification time falls back to the hash, which catches the change.
+    fs::File::options()
+        .write(true)
+        .open(&output)
+        .unwrap()
+        .set_modified(recorded + std::time::Duration::from_secs(1))
+        .unwrap();
+    let report = compress(std::slice::from_ref(&input), &out_dir, &journal);
+    assert_eq!(report.processed_files, vec![output.clone()]);
+    assert_eq!(fs::read(&output).unwrap(), intact);
+
+    // Lines written before outputs were stamped are still honoured by hash.
+    let line = fs::read_to_string(&journal)
+        .unwrap()
+        .lines()
+        .last()
+        .unwrap()
+        .to_string();
+    let fields = line.split('\t').collect::<Vec<_>>();
+    assert_eq!(fields.len(), 8);
+    let legacy = [&fields[..3], &fields[5..]].concat().join("\t");
+    fs::write(&journal, format!("{legacy}\n")).unwrap();
+    let report = compress(std::slice::from_ref(&input), &out_dir, &journal);
+    assert_eq!(report.resumed_files, vec![input]);
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{
+    build_nca, ecb_encrypt, generate_kek, hex, NcaSpec, SectionSpec, AES_KEK_GENERATION_SOURCE,
+    AES_KEY_GENERATION_SOURCE, HEADER_KEY, KEY_AREA_KEY_OCEAN_SOURCE, MASTER_KEY_00,
+};
+use nsz_rs::KeyOrigin;
+
+const MASTER_KEK_00: [u8; 16] = [0xC1; 16];
+const HEADER_KEK_SOURCE: [u8; 16] = [0xD2; 16];
+
+/// Keys file with no `header_key` and no `master_key_00`, only their sources.
+fn reduced_keys_text() -> String {
+    let master_key_source = ecb_encrypt(&MASTER_KEK_00, &MASTER_KEY_00);
+    let header_kek = generate_kek(&HEADER_KEK_SOURCE);
+    let mut header_key_source = [0u8; 32];
+    for (source, key) in header_key_source
+        .chunks_exact_mut(16)
+        .zip(HEADER_KEY.chunks_exact(16))
+    {
+        source.copy_from_slice(&ecb_encrypt(&header_kek, key.try_into().unwrap()));
+    }
+
+    [
+        ("master_kek_00", hex(&MASTER_KEK_00)),
+        ("master_key_source", hex(&master_key_source)),
+        ("header_kek_source", hex(&HEADER_KEK_SOURCE)),
+        ("header_key_source", hex(&header_key_source)),
+        ("aes_kek_generation_source", hex(&AES_KEK_GENERATION_SOURCE)),
+        ("aes_key_generation_source", hex(&AES_KEY_GENERATION_SOURCE)),
+        ("key_area_key_ocean_source", hex(&KEY_AREA_KEY_OCEAN_SOURCE)),
+    ]
+    .iter()
+    .map(|(name, value)| format!("{name} = {value}"))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn ocean_nca(title_key: [u8; 16]) -> Vec<u8> {
+    let mut spec = NcaSpec::program(
+        title_key,
+        vec![SectionSpec {
+            data: vec![0x33; 0x4000],
+            crypto_type: 3,
+            ctr_upper: 0,
+        }],
+    );
+    spec.key_area_key_index = 1;
+    build_nca(&spec)
+}
+
+#[test]
+fn reduced_keys_derive_header_and_master_keys() {
+    let store = nsz_rs::KeyStore::from_keys_str(&reduced_keys_text()).unwrap();
+    assert_eq!(store.nca_keys().header_key, HEADER_KEY);
+
+    let origins = store.key_origins();
+    assert_eq!(origins["master_kek_00"], KeyOrigin::Supplied);
+    assert_eq!(origins["master_key_00"], KeyOrigin::Derived);
+    assert_eq!(origins["header_key"], KeyOrigin::Derived);
+    assert_eq!(origins["key_area_key_ocean_00"], KeyOrigin::Derived);
+    assert!(!origins.contains_key("key_area_key_application_00"));
+
+    let plan = nsz_rs::container::nca::build_compression_plan(
+        &ocean_nca([0x6B; 16]),
+        store.nca_keys(),
+        &HashMap::new(),
+    )
+    .unwrap();
+    assert_eq!(plan.sections[0].crypto_key, [0x6B; 16]);
+}
+
+#[test]
+fn missing_key_area_source_is_named() {
+    let content = reduced_keys_text().replace("key_area_key_ocean_source", "unused_source");
+    let store = nsz_rs::KeyStore::from_keys_str(&content).unwrap();
+
+    let err = nsz_rs::container::nca::build_compression_plan(
+        &ocean_nca([0x6B; 16]),
+        store.nca_keys(),
+        &HashMap::new(),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("key_area_key_ocean_source"),
+        "unexpected error: {err}"
+    );
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{build_nca, hex, keys_text, NcaSpec, SectionSpec};
+
+#[test]
+fn keystore_debug_output_hides_key_material() {
+    let store = nsz_rs::KeyStore::from_keys_str(&keys_text()).unwrap();
+    let rendered = format!("{store:?}");
+
+    assert!(rendered.contains("<redacted>"));
+    assert!(rendered.contains("header_key"));
+    assert!(!rendered.contains(&hex(&common::HEADER_KEY)));
+    assert!(!rendered.contains(&hex(&common::MASTER_KEY_00)));
+    assert!(!rendered.contains(&hex(&common::title_kek())));
+    assert!(!rendered.contains(&format!("{:?}", common::MASTER_KEY_00)));
+}
+
+#[test]
+fn plan_and_ticket_debug_output_hide_title_keys() {
+    let store = nsz_rs::KeyStore::from_keys_str(&keys_text()).unwrap();
+    let title_key = [0x5A; 16];
+    let spec = NcaSpec::program(
+        title_key,
+        vec![SectionSpec {
+            data: vec![0u8; 0x4000],
+            crypto_type: 3,
+            ctr_upper: 0,
+        }],
+    );
+    let plan = nsz_rs::container::nca::build_compression_plan(
+        &build_nca(&spec),
+        store.nca_keys(),
+        &HashMap::new(),
+    )
+    .unwrap();
+    assert_eq!(plan.sections[0].crypto_key, title_key);
+    let rendered = format!("{plan:?}");
+    assert!(rendered.contains("<redacted>"));
+    assert!(!rendered.contains(&format!("{title_key:?}")));
+
+    let ticket = nsz_rs::container::nca::TicketRecord {
+        rights_id: [0x77; 16],
+        encrypted_title_key: [0x11; 16],
+        master_key_revision: 0,
+    };
+    let rendered = format!("{ticket:?}");
+    assert!(rendered.contains("<redacted>"));
+    assert!(!rendered.contains(&format!("{:?}", [0x11u8; 16])));
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{
+    build_nca, build_pfs0, hex, keys_text, scratch_dir, NcaSpec, SectionSpec, HEADER_KEY,
+    MASTER_KEY_00,
+};
+use nsz_rs::{KeyFingerprints, KeyStatus, KeyStore};
+use sha2::{Digest, Sha256};
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[test]
+fn fingerprints_verify_matching_keys_and_flag_mismatches() {
+    let store = KeyStore::from_keys_str(&keys_text()).unwrap();
+    let fingerprints = KeyFingerprints::parse(&format!(
+        "# known-good hashes\nheader_key = {}\nMASTER_KEY_00 = {}\n",
+        hex(&sha256(&HEADER_KEY)),
+        hex(&sha256(&[0xEE; 16]))
+    ))
+    .unwrap();
+
+    let report = store.validate(&fingerprints);
+    assert_eq!(report.keys["header_key"], KeyStatus::Verified);
+    assert_eq!(report.keys["master_key_00"], KeyStatus::Mismatch);
+    assert_eq!(report.keys["titlekek_source"], KeyStatus::Unchecked);
+
+    let err = report.into_result().unwrap_err();
+    assert!(err.to_string().contains("master_key_00"), "{err}");
+
+    let mut good = KeyFingerprints::builtin();
+    good.insert("master_key_00", sha256(&MASTER_KEY_00));
+    assert!(store.validate(&good).is_valid());
+}
+
+#[test]
+fn compress_rejects_keys_that_contradict_their_sources() {
+    let root = std::env::temp_dir().join(format!("nsz-rs-key-validation-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let keys_file = root.join("prod.keys");
+    fs::write(
+        &keys_file,
+        format!(
+            "{}key_area_key_application_00 = {}\n",
+            keys_text(),
+            hex(&[0x01; 16])
+        ),
+    )
+    .unwrap();
+    let store = KeyStore::from_file(&keys_file).unwrap();
+    assert_eq!(
+        store.validate(&KeyFingerprints::builtin()).keys["key_area_key_application_00"],
+        KeyStatus::Inconsistent
+    );
+
+    let input = root.join("fixture.nca");
+    fs::write(&input, vec![0u8; 0x8000]).unwrap();
+    let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(
+        matches!(&err, nsz_rs::NszError::InvalidKeys { details } if details.contains("key_area_key_application_00")),
+        "unexpected error: {err}"
+    );
+    assert!(!root.join("out").join("fixture.ncz").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn compress_rejects_a_header_key_that_does_not_decrypt_the_nca() {
+    let root = std::env::temp_dir().join(format!("nsz-rs-header-probe-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let nca = build_nca(&NcaSpec::program(
+        [0x3C; 16],
+        vec![SectionSpec {
+            data: vec![0x11; 0x4000],
+            crypto_type: 3,
+            ctr_upper: 0x0000_0002_0000_0000,
+        }],
+    ));
+    assert!(nsz_rs::crypto::validate::check_header_key(&HEADER_KEY, &nca).is_ok());
+
+    let name = "0123456789abcdef0123456789abcdef.nca";
+    let input = root.join("game.nsp");
+    fs::write(&input, build_pfs0(&[(name, &nca)])).unwrap();
+    let keys_file = root.join("prod.keys");
+    fs::write(
+        &keys_file,
+        keys_text().replace(&hex(&HEADER_KEY), &hex(&[0x5A; 32])),
+    )
+    .unwrap();
+
+    let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.entry(), Some(name));
+    assert!(
+        matches!(err.root(), nsz_rs::NszError::InvalidKeys { details } if details.contains("header_key")),
+        "unexpected error: {err}"
+    );
+    assert!(!root.join("out").join("game.nsz").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn compress_rejects_content_keys_that_do_not_decrypt_the_nca() {
+    let root = scratch_dir("content-key-probe");
+    let nca = build_nca(&NcaSpec::program(
+        [0x3C; 16],
+        vec![SectionSpec {
+            data: vec![0x11; 0x4000],
+            crypto_type: 3,
+            ctr_upper: 0x0000_0002_0000_0000,
+        }],
+    ));
+    let name = "0123456789abcdef0123456789abcdef.nca";
+    let input = root.join("game.nsp");
+    fs::write(&input, build_pfs0(&[(name, &nca)])).unwrap();
+
+    let wrong_key_area_key = format!(
+        "header_key = {}\nkey_area_key_application_00 = {}\n",
+        hex(&HEADER_KEY),
+        hex(&[0x01; 16])
+    );
+    let wrong_master_key = keys_text().replace(&hex(&MASTER_KEY_00), &hex(&[0x02; 16]));
+    for (keys, bad_key) in [
+        (wrong_key_area_key, "key_area_key_application_00"),
+        (wrong_master_key, "key_area_key_application_00"),
+    ] {
+        let keys_file = root.join("prod.keys");
+        fs::write(&keys_file, keys).unwrap();
+        let store = KeyStore::from_file(&keys_file).unwrap();
+        assert!(store.validate(&KeyFingerprints::builtin()).is_valid());
+
+        let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+            files: vec![input.clone()],
+            output_dir: Some(root.join("out")),
+            keys_file: Some(keys_file),
+            python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(err.entry(), Some(name));
+        assert!(
+            matches!(err.root(), nsz_rs::NszError::InvalidKeys { details } if details.contains(bad_key)),
+            "unexpected error: {err}"
+        );
+        assert!(!root.join("out").join("game.nsz").exists());
+    }
+
+    let _ = fs::remove_dir_all(root);
+}
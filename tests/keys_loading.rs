@@ -1,5 +1,48 @@
 #[test]
-fn key_loader_checks_required_entries() {
-    let err = nsz_rs::crypto::keys::load_from_str("master_key_00 = 00").unwrap_err();
-    assert!(format!("{err}").contains("aes_kek_generation_source"));
+fn key_loader_only_requires_a_header_key() {
+    let master_key = "master_key_00 = 30303030303030303030303030303030\n";
+    let err = nsz_rs::crypto::keys::load_from_str(master_key).unwrap_err();
+    assert!(format!("{err}").contains("header_key"));
+
+    let file = nsz_rs::crypto::keys::load_from_str(&format!(
+        "header_key = {}\n{master_key}",
+        "11".repeat(32)
+    ))
+    .unwrap();
+    assert_eq!(file.len(), 2);
+}
+
+#[test]
+fn key_file_accepts_hactool_and_prod_keys_conventions() {
+    let content = "; hactool comment\n# prod.keys comment\nHEADER_KEY = 000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F\nmaster_key_00,30303030303030303030303030303030\nmaster_key_00 = 30303030303030303030303030303030\n";
+    let file = nsz_rs::crypto::keys::KeyFile::parse(content).unwrap();
+
+    assert_eq!(file.len(), 2);
+    assert_eq!(file.get::<32>("header_key").unwrap()[31], 0x1F);
+    assert_eq!(file.entry("Master_Key_00").unwrap().line, 4);
+    assert_eq!(file.duplicates(), &[("master_key_00".to_string(), 5)]);
+}
+
+#[test]
+fn key_file_reports_line_numbers_for_bad_entries() {
+    let conflict = nsz_rs::crypto::keys::KeyFile::parse(
+        "master_key_00 = 30303030303030303030303030303030\n\nmaster_key_00 = 31313131313131313131313131313131\n",
+    )
+    .unwrap_err();
+    assert!(
+        matches!(conflict, nsz_rs::NszError::InvalidKeyFile { line: 3, ref message } if message.contains("line 1")),
+        "unexpected error: {conflict}"
+    );
+
+    let short = nsz_rs::crypto::keys::KeyFile::parse("header_key = 0011").unwrap_err();
+    assert!(
+        matches!(short, nsz_rs::NszError::InvalidKeyFile { line: 1, ref message } if message.contains("32 bytes")),
+        "unexpected error: {short}"
+    );
+
+    let not_hex = nsz_rs::crypto::keys::KeyFile::parse("\ntsec_root_key_00 = xyz").unwrap_err();
+    assert!(
+        matches!(not_hex, nsz_rs::NszError::InvalidKeyFile { line: 2, .. }),
+        "unexpected error: {not_hex}"
+    );
 }
@@ -0,0 +1,123 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_nca, keys_text, scratch_dir, write_keys_file, NcaSpec, SectionSpec};
+
+#[test]
+fn keystore_loads_explicit_file() {
+    let root = scratch_dir("keystore-explicit");
+    let keys_file = write_keys_file(&root);
+
+    let store = nsz_rs::KeyStore::from_file(&keys_file).unwrap();
+    assert_eq!(store.source(), Some(keys_file.as_path()));
+    assert_eq!(store.nca_keys().header_key, common::HEADER_KEY);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn keystore_names_missing_source_key_when_needed() {
+    let content = keys_text()
+        .lines()
+        .filter(|line| !line.starts_with("titlekek_source"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let store = nsz_rs::KeyStore::from_keys_str(&content).unwrap();
+
+    let rights_id = [0x77; 16];
+    let mut spec = NcaSpec::program(
+        [0x5A; 16],
+        vec![SectionSpec {
+            data: vec![0u8; 0x4000],
+            crypto_type: 3,
+            ctr_upper: 0,
+        }],
+    );
+    spec.rights_id = rights_id;
+    let tickets = HashMap::from([(
+        rights_id,
+        nsz_rs::container::nca::TicketRecord {
+            rights_id,
+            encrypted_title_key: [0x11; 16],
+            master_key_revision: 0,
+        },
+    )]);
+
+    let err = nsz_rs::container::nca::build_compression_plan(
+        &build_nca(&spec),
+        store.nca_keys(),
+        &tickets,
+    )
+    .unwrap_err();
+    assert!(
+        matches!(&err, nsz_rs::NszError::MissingRequiredKey { key } if key == "titlekek_source"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn compress_rejects_unreadable_explicit_keys_file() {
+    let root = scratch_dir("keystore-missing-file");
+    let input = root.join("fixture.nca");
+    fs::write(&input, vec![0u8; 0x8000]).unwrap();
+
+    let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(root.join("absent.keys")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(
+        matches!(err, nsz_rs::NszError::Io(_)),
+        "unexpected error: {err}"
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn strict_compress_names_missing_master_key_for_nca() {
+    let root = scratch_dir("keystore-strict");
+    let keys_file = write_keys_file(&root);
+
+    let mut spec = NcaSpec::program(
+        [0x5A; 16],
+        vec![SectionSpec {
+            data: vec![0u8; 0x8000],
+            crypto_type: 3,
+            ctr_upper: 0,
+        }],
+    );
+    spec.key_generation = 6;
+    let input = root.join("fixture.nca");
+    fs::write(&input, build_nca(&spec)).unwrap();
+
+    let request = nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    };
+    nsz_rs::compress(&request).unwrap();
+
+    let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+        require_keys: true,
+        ..request
+    })
+    .unwrap_err();
+    let message = err.to_string();
+    assert!(
+        matches!(err.root(), nsz_rs::NszError::MissingRequiredKey { .. }),
+        "unexpected error: {message}"
+    );
+    assert!(message.contains("master_key_05"), "{message}");
+    assert!(message.contains("fixture.nca"), "{message}");
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{
+    build_ticket, cnmt_entry, content_nca, encrypt_title_key, hex, read_nsp, scratch_dir,
+    write_keys_file, write_nsp,
+};
+use nsz_rs::container::hfs0::Hfs0Archive;
+use nsz_rs::container::nsp::NspArchive;
+use nsz_rs::container::xci::XciArchive;
+use nsz_rs::{merge, MergeRequest, NszError};
+
+const BASE_TITLE: u64 = 0x0100_0000_0000_1000;
+const UPDATE_TITLE: u64 = 0x0100_0000_0000_1800;
+const BASE_PROGRAM: [u8; 16] = [0xA1; 16];
+const OLD_UPDATE_PROGRAM: [u8; 16] = [0xB1; 16];
+const NEW_UPDATE_PROGRAM: [u8; 16] = [0xB2; 16];
+const UPDATE_RIGHTS_ID: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const OLD_UPDATE_RIGHTS_ID: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+];
+const UPDATE_TITLE_KEY: [u8; 16] = [0x5E; 16];
+
+/// Writes a base game, two updates of the same title and a repeat of the base.
+fn write_inputs(root: &Path) -> (Vec<PathBuf>, Vec<(String, Vec<u8>)>) {
+    let base = vec![
+        (
+            format!("{}.nca", hex(&BASE_PROGRAM)),
+            content_nca([0; 16], UPDATE_TITLE_KEY, 0x11),
+        ),
+        cnmt_entry(0xC1, BASE_TITLE, 0x80, 0, BASE_PROGRAM),
+    ];
+    let old_update = vec![
+        (
+            format!("{}.nca", hex(&OLD_UPDATE_PROGRAM)),
+            content_nca(OLD_UPDATE_RIGHTS_ID, UPDATE_TITLE_KEY, 0x22),
+        ),
+        cnmt_entry(0xC2, UPDATE_TITLE, 0x81, 0x0001_0000, OLD_UPDATE_PROGRAM),
+        (
+            format!("{}.tik", hex(&OLD_UPDATE_RIGHTS_ID)),
+            build_ticket(OLD_UPDATE_RIGHTS_ID, encrypt_title_key(&UPDATE_TITLE_KEY)),
+        ),
+        (
+            format!("{}.cert", hex(&OLD_UPDATE_RIGHTS_ID)),
+            vec![0xCE; 0x700],
+        ),
+    ];
+    let new_update = vec![
+        (
+            format!("{}.nca", hex(&NEW_UPDATE_PROGRAM)),
+            content_nca(UPDATE_RIGHTS_ID, UPDATE_TITLE_KEY, 0x33),
+        ),
+        cnmt_entry(0xC3, UPDATE_TITLE, 0x81, 0x0002_0000, NEW_UPDATE_PROGRAM),
+        (
+            format!("{}.tik", hex(&UPDATE_RIGHTS_ID)),
+            build_ticket(UPDATE_RIGHTS_ID, encrypt_title_key(&UPDATE_TITLE_KEY)),
+        ),
+        (
+            format!("{}.cert", hex(&UPDATE_RIGHTS_ID)),
+            vec![0xCF; 0x700],
+        ),
+        ("notes.xml".to_string(), b"<xml/>".to_vec()),
+    ];
+
+    let files = vec![
+        root.join("base.nsp"),
+        root.join("update-v2.nsp"),
+        root.join("update-v1.nsp"),
+        root.join("base-copy.nsp"),
+    ];
+    write_nsp(&files[0], &base);
+    write_nsp(&files[1], &new_update);
+    write_nsp(&files[2], &old_update);
+    write_nsp(&files[3], &base);
+
+    let mut expected = base;
+    expected.extend(new_update[..4].iter().cloned());
+    (files, expected)
+}
+
+#[test]
+fn merge_dedupes_contents_and_keeps_newest_update() {
+    let root = scratch_dir("merge-nsp");
+    let (files, expected) = write_inputs(&root);
+    let output = root.join("out").join("complete.nsp");
+
+    let report = merge(&MergeRequest {
+        files,
+        output_file: Some(output.clone()),
+        keys_file: Some(write_keys_file(&root)),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(report.processed_files, vec![output.clone()]);
+
+    assert_eq!(read_nsp(&output), expected);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn merge_writes_xci_secure_partition() {
+    let root = scratch_dir("merge-xci");
+    let (files, expected) = write_inputs(&root);
+    let output = root.join("complete.xci");
+
+    merge(&MergeRequest {
+        files,
+        output_file: Some(output.clone()),
+        keys_file: Some(write_keys_file(&root)),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let data = fs::read(&output).unwrap();
+    let xci = XciArchive::from_bytes(&data).unwrap();
+    assert_eq!(xci.used_size(), data.len() as u64);
+    assert_eq!(
+        xci.compute_root_hfs0_header_hash(&data).unwrap(),
+        xci.card_header.hfs0_header_hash
+    );
+    let root_bytes = xci.root_hfs0_bytes(&data).unwrap();
+    let root_hfs0 = xci.root_hfs0_archive(&data).unwrap();
+    let names = root_hfs0
+        .entries()
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["update", "normal", "secure"]);
+    let secure_entry = &root_hfs0.entries()[2];
+    let secure_bytes = root_hfs0.entry_bytes(root_bytes, secure_entry);
+    let secure = Hfs0Archive::from_bytes(secure_bytes).unwrap();
+    let merged = secure
+        .entries()
+        .iter()
+        .map(|entry| {
+            (
+                entry.name.clone(),
+                secure.entry_bytes(secure_bytes, entry).to_vec(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(merged, expected);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn merge_rejects_unknown_output_extension() {
+    let root = scratch_dir("merge-bad-output");
+    let err = merge(&MergeRequest {
+        files: Vec::new(),
+        output_file: Some(root.join("complete.zip")),
+        keys_file: Some(write_keys_file(&root)),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(matches!(err, NszError::UnsupportedFeature { .. }), "{err}");
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn merge_generates_missing_tickets_from_title_keys_file() {
+    let root = scratch_dir("merge-title-keys");
+    let input = root.join("update.nsp");
+    write_nsp(
+        &input,
+        &[
+            (
+                format!("{}.nca", hex(&NEW_UPDATE_PROGRAM)),
+                content_nca(UPDATE_RIGHTS_ID, UPDATE_TITLE_KEY, 0x33),
+            ),
+            cnmt_entry(0xC3, UPDATE_TITLE, 0x81, 0x0002_0000, NEW_UPDATE_PROGRAM),
+            (
+                format!("{}.cert", hex(&OLD_UPDATE_RIGHTS_ID)),
+                vec![0xCE; 0x700],
+            ),
+        ],
+    );
+    let title_keys_file = root.join("title.keys");
+    fs::write(
+        &title_keys_file,
+        format!(
+            "{} = {}\n",
+            hex(&UPDATE_RIGHTS_ID),
+            hex(&encrypt_title_key(&UPDATE_TITLE_KEY))
+        ),
+    )
+    .unwrap();
+    let output = root.join("complete.nsp");
+
+    merge(&MergeRequest {
+        files: vec![input],
+        output_file: Some(output.clone()),
+        keys_file: Some(write_keys_file(&root)),
+        title_keys_file: Some(title_keys_file),
+    })
+    .unwrap();
+
+    let data = fs::read(&output).unwrap();
+    let archive = NspArchive::from_bytes(&data).unwrap();
+    let ticket = archive
+        .entries()
+        .iter()
+        .find(|entry| entry.name == format!("{}.tik", hex(&UPDATE_RIGHTS_ID)))
+        .expect("generated ticket");
+    let ticket =
+        nsz_rs::container::nca::parse_ticket_record(archive.entry_bytes(&data, ticket)).unwrap();
+    assert_eq!(
+        ticket.encrypted_title_key,
+        encrypt_title_key(&UPDATE_TITLE_KEY)
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
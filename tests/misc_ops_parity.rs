@@ -74,7 +74,10 @@ fn run_extract_create_parity_for_fixture(python_repo: &Path, temp_root: &Path, s
         files: vec![source_nsp.to_path_buf()],
         output_dir: Some(rust_out.clone()),
         extract_regex: Some(extract_regex),
+        keys_file: None,
         python_repo_root: Some(python_repo.to_path_buf()),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
@@ -155,6 +158,7 @@ fn run_titlekeys_parity_for_fixture(base_python_repo: &Path, temp_root: &Path, s
 
     let report = nsz_rs::titlekeys(&nsz_rs::TitleKeysRequest {
         files: vec![source_nsp.to_path_buf()],
+        keys_file: None,
         python_repo_root: Some(rust_repo.clone()),
     })
     .unwrap();
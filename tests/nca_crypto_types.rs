@@ -0,0 +1,119 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{build_nca, keys_text, NcaSpec, SectionSpec};
+
+const TITLE_KEY: [u8; 16] = [0x4E; 16];
+
+fn nca_with_types(crypto_types: &[u8]) -> Vec<u8> {
+    build_nca(&NcaSpec::program(
+        TITLE_KEY,
+        crypto_types
+            .iter()
+            .map(|crypto_type| SectionSpec {
+                data: vec![*crypto_type; 0x1000],
+                crypto_type: *crypto_type,
+                ctr_upper: 0x0000_0001_0000_0000,
+            })
+            .collect(),
+    ))
+}
+
+#[test]
+fn section_crypto_types_map_to_ncz_types_and_roundtrip() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+    let nca = nca_with_types(&[1, 2, 5, 6]);
+
+    let plan =
+        nsz_rs::container::nca::build_compression_plan(&nca, &keys, &HashMap::new()).unwrap();
+    let types = plan
+        .sections
+        .iter()
+        .map(|section| section.crypto_type)
+        .collect::<Vec<_>>();
+    assert_eq!(types, vec![1, 2, 3, 3]);
+
+    let ncz =
+        nsz_rs::ncz::compress::compress_nca_to_ncz_vec_with_plan(&nca, 3, false, 1, Some(&plan))
+            .unwrap();
+    assert_eq!(
+        nsz_rs::ncz::decompress::decompress_ncz_to_vec(&ncz).unwrap(),
+        nca
+    );
+    let block = nsz_rs::ncz::compress::compress_nca_to_ncz_block_vec_with_plan(
+        &nca,
+        3,
+        false,
+        14,
+        Some(&plan),
+    )
+    .unwrap();
+    assert_eq!(
+        nsz_rs::ncz::decompress::decompress_ncz_to_vec(&block).unwrap(),
+        nca
+    );
+}
+
+#[test]
+fn each_section_crypto_type_roundtrips_on_its_own() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+
+    for crypto_type in [1u8, 2, 3, 4, 5, 6] {
+        let nca = nca_with_types(&[crypto_type]);
+        let plan = nsz_rs::container::nca::build_compression_plan(&nca, &keys, &HashMap::new())
+            .unwrap_or_else(|err| panic!("crypto type {crypto_type}: {err}"));
+        let ncz = nsz_rs::ncz::compress::compress_nca_to_ncz_vec_with_plan(
+            &nca,
+            3,
+            false,
+            1,
+            Some(&plan),
+        )
+        .unwrap();
+        assert_eq!(
+            nsz_rs::ncz::decompress::decompress_ncz_to_vec(&ncz).unwrap(),
+            nca,
+            "crypto type {crypto_type}"
+        );
+    }
+}
+
+#[test]
+fn unknown_section_crypto_type_is_unsupported() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+
+    for crypto_type in [0u8, 7] {
+        let err = nsz_rs::container::nca::build_compression_plan(
+            &nca_with_types(&[3, crypto_type]),
+            &keys,
+            &HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("NCA section crypto type {crypto_type}")),
+            "{err}"
+        );
+    }
+
+    let mut plan = nsz_rs::container::nca::build_compression_plan(
+        &nca_with_types(&[3]),
+        &keys,
+        &HashMap::new(),
+    )
+    .unwrap();
+    plan.sections[0].crypto_type = 9;
+    let err = nsz_rs::ncz::compress::compress_nca_to_ncz_vec_with_plan(
+        &nca_with_types(&[3]),
+        3,
+        false,
+        1,
+        Some(&plan),
+    )
+    .unwrap_err();
+    assert!(
+        matches!(err, nsz_rs::NszError::UnsupportedFeature { .. }),
+        "{err}"
+    );
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_nca, keys_text, write_keys_file, NcaSpec, SectionSpec, FIRST_SECTION_OFFSET};
+
+const TITLE_KEY: [u8; 16] = [0x3C; 16];
+
+fn spec(magic: [u8; 4], crypto_type: u8) -> NcaSpec {
+    let mut spec = NcaSpec::program(
+        TITLE_KEY,
+        vec![
+            SectionSpec {
+                data: vec![0x11; 0x4000],
+                crypto_type,
+                ctr_upper: 0x0000_0002_0000_0000,
+            },
+            SectionSpec {
+                data: vec![0x22; 0x2000],
+                crypto_type,
+                ctr_upper: 0x0000_0003_0000_0000,
+            },
+        ],
+    );
+    spec.magic = magic;
+    spec
+}
+
+#[test]
+fn nca2_fs_headers_decrypt_as_sector_zero() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+    let nca = build_nca(&spec(*b"NCA2", 3));
+
+    let plan =
+        nsz_rs::container::nca::build_compression_plan(&nca, &keys, &HashMap::new()).unwrap();
+    assert!(plan.meta.is_compressible());
+    assert_eq!(plan.offset_first_section, FIRST_SECTION_OFFSET as u64);
+    assert_eq!(plan.sections.len(), 2);
+    for (section, ctr_upper) in plan.sections.iter().zip([2u8, 3]) {
+        assert_eq!(section.crypto_type, 3);
+        assert_eq!(section.crypto_key, TITLE_KEY);
+        assert_eq!(section.crypto_counter[3], ctr_upper);
+    }
+    assert_eq!(
+        plan.sections[1].offset,
+        FIRST_SECTION_OFFSET as u64 + 0x4000
+    );
+}
+
+#[test]
+fn nca0_is_rejected_as_unsupported() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+    let nca = build_nca(&spec(*b"NCA0", 2));
+
+    let err = nsz_rs::container::nca::analyze_for_compression(&nca, &keys.header_key).unwrap_err();
+    assert!(
+        matches!(err, nsz_rs::NszError::UnsupportedFeature { ref feature } if feature.contains("NCA0")),
+        "{err}"
+    );
+
+    let root = std::env::temp_dir().join(format!("nsz-rs-nca0-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let keys_file = write_keys_file(&root);
+    let input = root.join("fixture.nca");
+    fs::write(&input, &nca).unwrap();
+
+    let err = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.kind(), "unsupported_feature", "{err}");
+    assert!(!root.join("out/fixture.ncz").exists());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn nca2_standalone_compression_roundtrips() {
+    let root = std::env::temp_dir().join(format!("nsz-rs-nca2-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let keys_file = write_keys_file(&root);
+    let nca = build_nca(&spec(*b"NCA2", 3));
+    let input = root.join("fixture.nca");
+    fs::write(&input, &nca).unwrap();
+
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        require_keys: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let ncz = fs::read(&report.processed_files[0]).unwrap();
+    let sections = nsz_rs::ncz::decompress::parse_sections(&ncz).unwrap();
+    assert!(sections
+        .iter()
+        .any(|section| section.crypto_type == 3 && section.crypto_key == TITLE_KEY));
+    assert_eq!(
+        nsz_rs::ncz::decompress::decompress_ncz_to_vec(&ncz).unwrap(),
+        nca
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{build_nca_with, ctr_apply, keys_text, NcaSpec, SectionSpec, FIRST_SECTION_OFFSET};
+
+const TITLE_KEY: [u8; 16] = [0x6B; 16];
+const CTR_UPPER: u64 = 0x0000_0007_0000_0002;
+const SPARSE_GENERATION: u16 = 3;
+const TABLE_OFFSET: usize = 0x2000;
+const TABLE_SIZE: usize = 0x8000;
+const SECTION_SIZE: usize = TABLE_OFFSET + TABLE_SIZE + 0x200;
+
+/// Sparse table: run A at virtual 0, a hole at 0x1000, run B at virtual 0x3000.
+fn sparse_table() -> Vec<u8> {
+    let mut table = vec![0u8; TABLE_SIZE];
+    table[4..8].copy_from_slice(&1u32.to_le_bytes());
+    table[8..16].copy_from_slice(&0x4000u64.to_le_bytes());
+    let set = &mut table[0x4000..];
+    set[4..8].copy_from_slice(&3u32.to_le_bytes());
+    set[8..16].copy_from_slice(&0x4000u64.to_le_bytes());
+    for (index, (virtual_offset, physical_offset, storage)) in
+        [(0u64, 0u64, 0u32), (0x1000, 0, 1), (0x3000, 0x1000, 0)]
+            .into_iter()
+            .enumerate()
+    {
+        let base = 0x10 + index * 0x14;
+        set[base..base + 8].copy_from_slice(&virtual_offset.to_le_bytes());
+        set[base + 8..base + 16].copy_from_slice(&physical_offset.to_le_bytes());
+        set[base + 16..base + 20].copy_from_slice(&storage.to_le_bytes());
+    }
+    table
+}
+
+fn plain_section() -> Vec<u8> {
+    let mut data = vec![0u8; SECTION_SIZE];
+    data[..0x1000].fill(0xAA);
+    data[0x1000..0x2000].fill(0xBB);
+    data[TABLE_OFFSET..TABLE_OFFSET + TABLE_SIZE].copy_from_slice(&sparse_table());
+    data[TABLE_OFFSET + TABLE_SIZE..].fill(0xCC);
+    data
+}
+
+fn sparse_nca(patch: impl FnOnce(&mut [u8])) -> Vec<u8> {
+    let spec = NcaSpec::program(
+        TITLE_KEY,
+        vec![SectionSpec {
+            data: plain_section(),
+            crypto_type: 1,
+            ctr_upper: CTR_UPPER,
+        }],
+    );
+    let mut nca = build_nca_with(&spec, |header| {
+        let fs_header = &mut header[0x400..0x600];
+        fs_header[0x4] = 3;
+        fs_header[0x148..0x150].copy_from_slice(&(TABLE_OFFSET as u64).to_le_bytes());
+        fs_header[0x150..0x158].copy_from_slice(&(TABLE_SIZE as u64).to_le_bytes());
+        fs_header[0x158..0x15C].copy_from_slice(b"BKTR");
+        fs_header[0x15C..0x160].copy_from_slice(&1u32.to_le_bytes());
+        fs_header[0x160..0x164].copy_from_slice(&3u32.to_le_bytes());
+        fs_header[0x168..0x170].copy_from_slice(&(FIRST_SECTION_OFFSET as u64).to_le_bytes());
+        fs_header[0x170..0x172].copy_from_slice(&SPARSE_GENERATION.to_le_bytes());
+        patch(fs_header);
+    });
+
+    // Data runs are encrypted at their virtual offsets, the table with the sparse IV.
+    let base = FIRST_SECTION_OFFSET;
+    ctr_apply(
+        &mut nca[base..base + 0x1000],
+        &TITLE_KEY,
+        CTR_UPPER,
+        base as u64,
+    );
+    ctr_apply(
+        &mut nca[base + 0x1000..base + 0x2000],
+        &TITLE_KEY,
+        CTR_UPPER,
+        (base + 0x3000) as u64,
+    );
+    let sparse_upper = (CTR_UPPER & !0xFFFF_FFFF) | (u64::from(SPARSE_GENERATION) << 16);
+    let table = base + TABLE_OFFSET;
+    ctr_apply(
+        &mut nca[table..table + TABLE_SIZE],
+        &TITLE_KEY,
+        sparse_upper,
+        table as u64,
+    );
+    nca
+}
+
+#[test]
+fn sparse_section_plan_decrypts_every_physical_run() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+    let nca = sparse_nca(|_| {});
+
+    let plan =
+        nsz_rs::container::nca::build_compression_plan(&nca, &keys, &HashMap::new()).unwrap();
+    let layout = plan
+        .sections
+        .iter()
+        .map(|section| (section.offset, section.size, section.crypto_type))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        layout,
+        vec![
+            (0x4000, 0x1000, 3),
+            (0x5000, 0x1000, 3),
+            (0x6000, TABLE_SIZE as u64, 3),
+            (0xE000, 0x200, 1),
+        ]
+    );
+
+    let mut decrypted = nca[FIRST_SECTION_OFFSET..].to_vec();
+    for section in plan
+        .sections
+        .iter()
+        .filter(|section| section.crypto_type == 3)
+    {
+        let start = section.offset as usize - FIRST_SECTION_OFFSET;
+        nsz_rs::crypto::aes::ctr_apply(
+            &mut decrypted[start..start + section.size as usize],
+            &section.crypto_key,
+            &section.crypto_counter,
+            u128::from(section.offset),
+        );
+    }
+    assert_eq!(decrypted, plain_section());
+
+    let ncz =
+        nsz_rs::ncz::compress::compress_nca_to_ncz_vec_with_plan(&nca, 3, false, 1, Some(&plan))
+            .unwrap();
+    assert_eq!(
+        nsz_rs::ncz::decompress::decompress_ncz_to_vec(&ncz).unwrap(),
+        nca
+    );
+}
+
+#[test]
+fn compression_table_outside_section_is_rejected() {
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+    let nca = sparse_nca(|fs_header| {
+        fs_header[0x178..0x180].copy_from_slice(&0x1000u64.to_le_bytes());
+        fs_header[0x180..0x188].copy_from_slice(&(SECTION_SIZE as u64).to_le_bytes());
+        fs_header[0x188..0x18C].copy_from_slice(b"BKTR");
+    });
+
+    let err =
+        nsz_rs::container::nca::build_compression_plan(&nca, &keys, &HashMap::new()).unwrap_err();
+    assert!(err.to_string().contains("compression table"), "{err}");
+}
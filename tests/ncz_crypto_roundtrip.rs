@@ -30,3 +30,66 @@ fn ncz_native_decompress_roundtrip_crypto_type3() {
     let decompressed = nsz_rs::ncz::decompress::decompress_ncz_to_vec(&fixture).unwrap();
     assert_eq!(&decompressed[0x4000..], &payload);
 }
+
+fn single_section_ncz(payload: &[u8], crypto_type: u64) -> Vec<u8> {
+    let compressed = zstd::stream::encode_all(payload, 1).unwrap();
+    let mut fixture = vec![0u8; 0x4000];
+    fixture.extend_from_slice(b"NCZSECTN");
+    fixture.extend_from_slice(&(1u64).to_le_bytes());
+    fixture.extend_from_slice(&(0x4000u64).to_le_bytes());
+    fixture.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    fixture.extend_from_slice(&crypto_type.to_le_bytes());
+    fixture.extend_from_slice(&0u64.to_le_bytes());
+    fixture.extend_from_slice(&[0x11u8; 16]);
+    fixture.extend_from_slice(&[0x22u8; 16]);
+    fixture.extend_from_slice(&compressed);
+    fixture
+}
+
+#[test]
+fn ncz_native_decompress_passes_xts_and_plain_sections_through() {
+    let payload = b"already-encrypted-xts-payload".to_vec();
+    for crypto_type in [1u64, 2] {
+        let decompressed = nsz_rs::ncz::decompress::decompress_ncz_to_vec(&single_section_ncz(
+            &payload,
+            crypto_type,
+        ))
+        .unwrap();
+        assert_eq!(&decompressed[0x4000..], &payload);
+    }
+}
+
+#[test]
+fn ncz_native_decompress_rejects_unknown_crypto_type() {
+    let err = nsz_rs::ncz::decompress::decompress_ncz_to_vec(&single_section_ncz(b"payload", 9))
+        .unwrap_err();
+    assert!(
+        matches!(err, nsz_rs::NszError::UnsupportedFeature { .. }),
+        "{err}"
+    );
+}
+
+#[test]
+fn ncz_native_decompress_handles_every_crypto_type() {
+    type AesCtr = ctr::Ctr128BE<Aes128>;
+
+    let payload = b"per-crypto-type-payload".to_vec();
+    for crypto_type in 0u64..=6 {
+        let mut expected = payload.clone();
+        if crypto_type >= 3 {
+            let mut cipher = AesCtr::new(&[0x11u8; 16].into(), &[0x22u8; 16].into());
+            cipher.seek(0x4000u128);
+            cipher.apply_keystream(&mut expected);
+        }
+        let decompressed = nsz_rs::ncz::decompress::decompress_ncz_to_vec(&single_section_ncz(
+            &payload,
+            crypto_type,
+        ))
+        .unwrap_or_else(|err| panic!("crypto type {crypto_type}: {err}"));
+        assert_eq!(
+            &decompressed[0x4000..],
+            &expected,
+            "crypto type {crypto_type}"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use common::{build_pfs0, hex, raw_nca, scratch_dir};
+use nsz_rs::{FileReport, FileStatus, NszError};
+use sha2::{Digest, Sha256};
+
+fn fixture_nsp() -> Vec<u8> {
+    build_pfs0(&[(
+        "0123456789abcdef0123456789abcdef.nca",
+        &raw_nca(*b"operation-report-payload"),
+    )])
+}
+
+#[test]
+fn reports_carry_per_file_status_and_sizes() {
+    let root = scratch_dir("reports-status");
+    let input = root.join("game.nsp");
+    let nsp = fixture_nsp();
+    fs::write(&input, &nsp).unwrap();
+
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input.clone()],
+        output_dir: Some(root.join("out")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let out_nsz = root.join("out/game.nsz");
+    let nsz_len = fs::metadata(&out_nsz).unwrap().len();
+    assert_eq!(report.files.len(), 1);
+    let file = &report.files[0];
+    assert_eq!(file.input, input);
+    assert_eq!(file.output.as_ref(), Some(&out_nsz));
+    assert_eq!(file.status, FileStatus::Processed);
+    assert_eq!(file.input_size, Some(nsp.len() as u64));
+    assert_eq!(file.output_size, Some(nsz_len));
+    let ratio = file.ratio.unwrap();
+    let expected =
+        f64::from(u32::try_from(nsz_len).unwrap()) / f64::from(u32::try_from(nsp.len()).unwrap());
+    assert!((ratio - expected).abs() < 1e-9);
+    assert!(file.error.is_none());
+
+    let report = nsz_rs::trim(&nsz_rs::TrimRequest {
+        files: vec![input.clone()],
+        output_dir: Some(root.join("trimmed")),
+    })
+    .unwrap();
+    assert_eq!(report.skipped_files, vec![input]);
+    assert_eq!(report.files[0].status, FileStatus::Skipped);
+    assert_eq!(report.files[0].output, None);
+
+    let nca = b"verified-nca-payload".to_vec();
+    let nca_file = root.join(format!("{}.nca", hex(&Sha256::digest(&nca)[..16])));
+    fs::write(&nca_file, &nca).unwrap();
+    let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        files: vec![nca_file],
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(report.files.len(), 1);
+    assert_eq!(report.files[0].status, FileStatus::Verified);
+    assert_eq!(report.files[0].input_size, Some(nca.len() as u64));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn failed_entries_describe_the_error() {
+    let err = NszError::MissingRequiredKey {
+        key: "header_key".to_string(),
+    };
+    let file = FileReport::failed(
+        std::path::Path::new("/missing/game.nsp"),
+        Duration::from_millis(5),
+        &err,
+    );
+    assert_eq!(file.status, FileStatus::Failed);
+    assert_eq!(file.input_size, None);
+    let details = file.error.unwrap();
+    assert_eq!(details.kind, "missing_required_key");
+    assert_eq!(details.message, err.to_string());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn file_reports_serialize_as_json_lines() {
+    let root = scratch_dir("reports-json");
+    let input = root.join("game.nsp");
+    fs::write(&input, fixture_nsp()).unwrap();
+
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input.clone()],
+        output_dir: Some(root.join("out")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let line = report.files[0].to_json_line();
+    assert!(!line.contains('\n'));
+    let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(event["event"], "file");
+    assert_eq!(event["status"], "processed");
+    assert_eq!(event["input"], input.display().to_string());
+    assert!(event["output_size"].as_u64().unwrap() > 0);
+    assert!(event["ratio"].as_f64().unwrap() > 0.0);
+    assert!(event["duration_secs"].as_f64().unwrap() >= 0.0);
+    assert!(event["error"].is_null());
+
+    let details = nsz_rs::ErrorDetails::from(&NszError::ContainerFormat {
+        message: "bad".to_string(),
+    });
+    let event: serde_json::Value = serde_json::from_str(&details.to_json_line()).unwrap();
+    assert_eq!(event["event"], "error");
+    assert_eq!(event["kind"], "container_format");
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["files"][0]["status"], "processed");
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -1,3 +1,5 @@
+mod common;
+
 use std::fs;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -18,12 +20,23 @@ import sys
 args = sys.argv[1:]
 pathlib.Path("args.txt").write_text("\n".join(args), encoding="utf-8")
 pathlib.Path("invoked.txt").write_text("ok", encoding="utf-8")
+home_keys = pathlib.Path.home() / ".switch" / "prod.keys"
+if home_keys.is_file():
+    pathlib.Path("home_keys.txt").write_text(home_keys.read_text(), encoding="utf-8")
 if "-c" in args:
     index = args.index("-c")
     if index + 1 < len(args):
         output = pathlib.Path(args[index + 1])
         output.parent.mkdir(parents=True, exist_ok=True)
         output.write_bytes(b"dummy")
+if "-x" in args:
+    out_dir = pathlib.Path(args[args.index("-o") + 1])
+    skip = False
+    for arg in args:
+        if skip or arg.startswith("-"):
+            skip = arg in ("-o", "--extractregex")
+            continue
+        (out_dir / pathlib.Path(arg).stem).mkdir(parents=True, exist_ok=True)
 "#;
     fs::write(repo_root.join("nsz.py"), script).expect("write fake nsz.py");
 }
@@ -45,7 +58,10 @@ fn extract_invokes_cli_and_reports_output_dirs() {
         files: vec![input.clone()],
         output_dir: Some(output_root.clone()),
         extract_regex: Some("^.*\\.nca$".to_string()),
+        keys_file: None,
         python_repo_root: Some(repo_root.clone()),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .expect("extract should succeed");
 
@@ -74,6 +90,36 @@ fn extract_invokes_cli_and_reports_output_dirs() {
     fs::remove_dir_all(&temp_root).expect("cleanup temp directory");
 }
 
+#[test]
+fn extract_of_split_input_lands_next_to_it() {
+    let temp_root = make_temp_root("extract-split");
+    let repo_root = temp_root.join("fake-nsz");
+    let input_root = temp_root.join("input");
+    write_fake_repo(&repo_root);
+    fs::create_dir_all(&input_root).expect("create input dir");
+    fs::write(input_root.join("game.xc0"), b"part0").expect("write part 0");
+    fs::write(input_root.join("game.xc1"), b"part1").expect("write part 1");
+
+    let report = nsz_rs::extract(&nsz_rs::ExtractRequest {
+        files: vec![input_root.join("game.xc0")],
+        python_repo_root: Some(repo_root.clone()),
+        ..Default::default()
+    })
+    .expect("extract should succeed");
+
+    let out_dir = input_root.join("game");
+    assert_eq!(report.processed_files, vec![out_dir.clone()]);
+    assert!(out_dir.is_dir());
+
+    let args = fs::read_to_string(repo_root.join("args.txt")).expect("args file");
+    assert!(
+        args.contains(input_root.to_string_lossy().as_ref()),
+        "args missing -o target: {args}"
+    );
+
+    fs::remove_dir_all(&temp_root).expect("cleanup temp directory");
+}
+
 #[test]
 fn create_invokes_cli_and_reports_output_file() {
     let temp_root = make_temp_root("create");
@@ -144,6 +190,7 @@ fn titlekeys_invokes_cli_and_reports_inputs() {
 
     let report = nsz_rs::titlekeys(&nsz_rs::TitleKeysRequest {
         files: vec![input.clone()],
+        keys_file: None,
         python_repo_root: Some(repo_root.clone()),
     })
     .expect("titlekeys should succeed");
@@ -160,6 +207,31 @@ fn titlekeys_invokes_cli_and_reports_inputs() {
     fs::remove_dir_all(&temp_root).expect("cleanup temp directory");
 }
 
+#[test]
+fn titlekeys_hands_keys_file_to_python() {
+    let temp_root = make_temp_root("titlekeys-keys");
+    let repo_root = temp_root.join("fake-nsz");
+    write_fake_repo(&repo_root);
+    let input = temp_root.join("sample.nsp");
+    fs::write(&input, b"dummy").expect("write input");
+    let keys_file = temp_root.join("custom.keys");
+    fs::write(&keys_file, common::keys_text()).expect("write keys");
+
+    nsz_rs::titlekeys(&nsz_rs::TitleKeysRequest {
+        files: vec![input],
+        keys_file: Some(keys_file),
+        python_repo_root: Some(repo_root.clone()),
+    })
+    .expect("titlekeys should succeed");
+
+    assert_eq!(
+        fs::read_to_string(repo_root.join("home_keys.txt")).expect("keys seen by python"),
+        common::keys_text()
+    );
+
+    fs::remove_dir_all(&temp_root).expect("cleanup temp directory");
+}
+
 #[test]
 fn undupe_invokes_cli_with_options() {
     let temp_root = make_temp_root("undupe");
@@ -171,7 +171,13 @@ fn run_decompress_benchmark(
             files: vec![decompress_input.to_path_buf()],
             output_dir: Some(decompress_rust_out.to_path_buf()),
             fix_padding: false,
+            split_output: false,
+            continue_on_error: false,
+            journal: None,
+            keys_file: None,
             python_repo_root: Some(PathBuf::from("/does/not/exist")),
+            progress: nsz_rs::Progress::default(),
+            cancel: nsz_rs::CancellationToken::default(),
         })
         .map(|_| ())
     })
@@ -0,0 +1,146 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use common::{build_pfs0, hex, raw_nca, scratch_dir};
+use nsz_rs::{Progress, ProgressEvent};
+use sha2::{Digest, Sha256};
+
+const NCA_NAME: &str = "fedcba9876543210fedcba9876543210.nca";
+const NCZ_NAME: &str = "fedcba9876543210fedcba9876543210.ncz";
+const PAYLOAD_SIZE: u64 = 0x10000;
+
+/// Returns a progress observer and the events it saw, rendered as strings.
+fn recorder() -> (Progress, Arc<Mutex<Vec<String>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let progress = Progress::new(move |event: ProgressEvent<'_>| {
+        sink.lock().unwrap().push(match event {
+            ProgressEvent::ContainerOpened { path, entries } => {
+                format!("opened {} {entries}", path.display())
+            }
+            ProgressEvent::EntryStarted { name, size } => format!("started {name} {size}"),
+            ProgressEvent::BytesProcessed { name, done, total } => {
+                format!("bytes {name} {done}/{total}")
+            }
+            ProgressEvent::EntryFinished { name } => format!("finished {name}"),
+            ProgressEvent::OutputWritten { path, size } => {
+                format!("written {} {size}", path.display())
+            }
+        });
+    });
+    (progress, events)
+}
+
+fn fixture_nsp(path: &Path) -> u64 {
+    let nca = raw_nca((0..PAYLOAD_SIZE).map(|index| (index % 251) as u8));
+    fs::write(
+        path,
+        build_pfs0(&[(NCA_NAME, &nca), ("ticket.tik", b"ticket")]),
+    )
+    .unwrap();
+    nca.len() as u64
+}
+
+#[test]
+fn compress_and_decompress_report_entries_bytes_and_outputs() {
+    let root = scratch_dir("progress-roundtrip");
+    let input = root.join("game.nsp");
+    let nca_size = fixture_nsp(&input);
+    let out_dir = root.join("out");
+
+    let (progress, events) = recorder();
+    nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input.clone()],
+        output_dir: Some(out_dir.clone()),
+        level: 1,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress,
+        ..Default::default()
+    })
+    .unwrap();
+    let compressed = out_dir.join("game.nsz");
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            format!("opened {} 2", input.display()),
+            format!("started {NCA_NAME} {nca_size}"),
+            format!("bytes {NCA_NAME} {PAYLOAD_SIZE}/{PAYLOAD_SIZE}"),
+            format!("finished {NCA_NAME}"),
+            "started ticket.tik 6".to_string(),
+            "finished ticket.tik".to_string(),
+            format!(
+                "written {} {}",
+                compressed.display(),
+                fs::metadata(&compressed).unwrap().len()
+            ),
+        ]
+    );
+
+    let (progress, events) = recorder();
+    nsz_rs::decompress(&nsz_rs::DecompressRequest {
+        files: vec![compressed.clone()],
+        output_dir: Some(root.join("restored")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress,
+        ..Default::default()
+    })
+    .unwrap();
+    let events = std::mem::take(&mut *events.lock().unwrap());
+    assert_eq!(events[0], format!("opened {} 2", compressed.display()));
+    assert!(events[1].starts_with(&format!("started {NCZ_NAME} ")));
+    assert_eq!(
+        events[2..6],
+        [
+            format!("bytes {NCZ_NAME} {PAYLOAD_SIZE}/{PAYLOAD_SIZE}"),
+            format!("finished {NCZ_NAME}"),
+            "started ticket.tik 6".to_string(),
+            "finished ticket.tik".to_string(),
+        ]
+    );
+    assert_eq!(
+        events[6],
+        format!(
+            "written {} {}",
+            root.join("restored/game.nsp").display(),
+            fs::metadata(&input).unwrap().len()
+        )
+    );
+    assert_eq!(events.len(), 7);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn verify_reports_hashed_bytes_of_every_entry() {
+    let root = scratch_dir("progress-verify");
+    let nca = vec![0x5A; 0x6000];
+    let input = root.join(format!("{}.nca", hex(&Sha256::digest(&nca)[..16])));
+    fs::write(&input, &nca).unwrap();
+    let name = input.file_name().unwrap().to_str().unwrap().to_string();
+
+    let (progress, events) = recorder();
+    assert_eq!(format!("{progress:?}"), "Progress(\"observer\")");
+    let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        files: vec![input.clone()],
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress,
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(report.verified_files, vec![input.clone()]);
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            format!("opened {} 1", input.display()),
+            format!("started {name} {}", 0x6000),
+            format!("bytes {name} {}/{}", 0x6000, 0x6000),
+            format!("finished {name}"),
+        ]
+    );
+    assert_eq!(format!("{:?}", Progress::default()), "Progress(\"none\")");
+
+    let _ = fs::remove_dir_all(root);
+}
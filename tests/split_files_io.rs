@@ -0,0 +1,135 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_hfs0, build_pfs0, build_xci, raw_nca, scratch_dir};
+use nsz_rs::fs_ops::split_files::{logical_path, read_input, write_folder_parts, write_xci_parts};
+
+#[test]
+fn split_parts_read_back_as_one_logical_file() {
+    let root = scratch_dir("split-files-parts");
+    let data: Vec<u8> = (0..1000u32).map(|value| value as u8).collect();
+
+    let folder = write_folder_parts(&root.join("game.nsz"), &data, 300).unwrap();
+    assert!(folder.join("03").exists());
+    assert!(!folder.join("04").exists());
+    assert_eq!(fs::metadata(folder.join("03")).unwrap().len(), 100);
+    assert_eq!(read_input(&folder).unwrap(), data);
+    assert_eq!(logical_path(&folder), root.join("game.nsz"));
+
+    let first = write_xci_parts(&root.join("game.xci"), &data, 512).unwrap();
+    assert_eq!(first, root.join("game.xc0"));
+    assert!(root.join("game.xc1").exists());
+    assert!(!root.join("game.xc2").exists());
+    assert_eq!(read_input(&first).unwrap(), data);
+    assert_eq!(logical_path(&first), root.join("game.xci"));
+
+    fs::remove_file(folder.join("01")).unwrap();
+    let err = read_input(&folder).unwrap_err();
+    assert!(err.to_string().contains("split folder"));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn decompress_reads_split_nsz_folder() {
+    let root = scratch_dir("split-files-decompress");
+    let payload = b"split-folder-nsz-payload";
+    let ncz = build_ncz(payload);
+    let nsz_bytes = build_pfs0(&[("0123456789abcdef0123456789abcdef.ncz", &ncz)]);
+
+    let input = write_folder_parts(&root.join("fixture.nsz"), &nsz_bytes, 0x1000).unwrap();
+    assert!(input.join("01").exists());
+    let out_dir = root.join("out");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let report = nsz_rs::decompress(&nsz_rs::DecompressRequest {
+        files: vec![input],
+        output_dir: Some(out_dir.clone()),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let out_nsp = out_dir.join("fixture.nsp");
+    assert_eq!(report.processed_files, vec![out_nsp.clone()]);
+    let out_bytes = fs::read(&out_nsp).unwrap();
+    let archive = nsz_rs::container::nsp::NspArchive::from_bytes(&out_bytes).unwrap();
+    let nca = archive
+        .entries()
+        .iter()
+        .find(|entry| entry.name == "0123456789abcdef0123456789abcdef.nca")
+        .unwrap();
+    assert_eq!(&archive.entry_bytes(&out_bytes, nca)[0x4000..], payload);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn compress_reads_xc_parts_and_writes_split_output() {
+    let root = scratch_dir("split-files-compress");
+    let nca = raw_nca(*b"split-xci-payload");
+    let secure = build_hfs0(&[(
+        "fedcba9876543210fedcba9876543210.nca".to_string(),
+        nca.clone(),
+    )]);
+    let xci_bytes = build_xci(&[("secure", secure)]);
+
+    let input = write_xci_parts(&root.join("fixture.xci"), &xci_bytes, 0x8000).unwrap();
+    assert!(root.join("fixture.xc1").exists());
+    let out_dir = root.join("out");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(out_dir.clone()),
+        split_output: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let out_xcz = out_dir.join("fixture.xcz");
+    assert_eq!(report.processed_files, vec![out_xcz.clone()]);
+    assert!(out_xcz.join("00").is_file());
+
+    let out_bytes = read_input(&out_xcz).unwrap();
+    let xci = nsz_rs::container::xci::XciArchive::from_bytes(&out_bytes).unwrap();
+    let root_bytes = xci.root_hfs0_bytes(&out_bytes).unwrap();
+    let root_hfs0 = xci.root_hfs0_archive(&out_bytes).unwrap();
+    let secure_entry = root_hfs0
+        .entries()
+        .iter()
+        .find(|entry| entry.name == "secure")
+        .unwrap();
+    let secure_bytes = root_hfs0.entry_bytes(root_bytes, secure_entry);
+    let secure_hfs0 = nsz_rs::container::hfs0::Hfs0Archive::from_bytes(secure_bytes).unwrap();
+    let ncz_entry = secure_hfs0
+        .entries()
+        .iter()
+        .find(|entry| entry.name == "fedcba9876543210fedcba9876543210.ncz")
+        .unwrap();
+    let roundtrip = nsz_rs::ncz::decompress::decompress_ncz_to_vec(
+        secure_hfs0.entry_bytes(secure_bytes, ncz_entry),
+    )
+    .unwrap();
+    assert_eq!(roundtrip, nca);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+fn build_ncz(payload: &[u8]) -> Vec<u8> {
+    let compressed = zstd::stream::encode_all(payload, 1).unwrap();
+    let mut ncz = vec![0u8; 0x4000];
+    ncz.extend_from_slice(b"NCZSECTN");
+    ncz.extend_from_slice(&1u64.to_le_bytes());
+    ncz.extend_from_slice(&0x4000u64.to_le_bytes());
+    ncz.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    ncz.extend_from_slice(&0u64.to_le_bytes());
+    ncz.extend_from_slice(&0u64.to_le_bytes());
+    ncz.extend_from_slice(&[0u8; 16]);
+    ncz.extend_from_slice(&[0u8; 16]);
+    ncz.extend_from_slice(&compressed);
+    ncz
+}
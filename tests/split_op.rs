@@ -0,0 +1,167 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{
+    build_hfs0, build_ticket, build_xci, cnmt_entry, content_nca, encrypt_title_key, hex, read_nsp,
+    scratch_dir, write_keys_file, write_nsp,
+};
+use nsz_rs::{compress, split, CompressRequest, NszError, SplitRequest};
+
+const BASE_TITLE: u64 = 0x0100_0000_0000_1000;
+const UPDATE_TITLE: u64 = 0x0100_0000_0000_1800;
+const DLC_TITLE: u64 = 0x0100_0000_0000_2001;
+const BASE_PROGRAM: [u8; 16] = [0xA1; 16];
+const UPDATE_PROGRAM: [u8; 16] = [0xB1; 16];
+const DLC_DATA: [u8; 16] = [0xD1; 16];
+const UPDATE_RIGHTS_ID: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const UPDATE_TITLE_KEY: [u8; 16] = [0x5E; 16];
+
+/// Base, update and DLC of one game in a single bundle, in title order.
+fn bundle_entries() -> Vec<(String, Vec<u8>)> {
+    vec![
+        (
+            format!("{}.nca", hex(&BASE_PROGRAM)),
+            content_nca([0; 16], UPDATE_TITLE_KEY, 0x11),
+        ),
+        cnmt_entry(0xC1, BASE_TITLE, 0x80, 0, BASE_PROGRAM),
+        (
+            format!("{}.nca", hex(&UPDATE_PROGRAM)),
+            content_nca(UPDATE_RIGHTS_ID, UPDATE_TITLE_KEY, 0x22),
+        ),
+        cnmt_entry(0xC2, UPDATE_TITLE, 0x81, 0, UPDATE_PROGRAM),
+        (
+            format!("{}.tik", hex(&UPDATE_RIGHTS_ID)),
+            build_ticket(UPDATE_RIGHTS_ID, encrypt_title_key(&UPDATE_TITLE_KEY)),
+        ),
+        (
+            format!("{}.cert", hex(&UPDATE_RIGHTS_ID)),
+            vec![0xCE; 0x700],
+        ),
+        (
+            format!("{}.nca", hex(&DLC_DATA)),
+            content_nca([0; 16], UPDATE_TITLE_KEY, 0x33),
+        ),
+        cnmt_entry(0xC3, DLC_TITLE, 0x82, 0, DLC_DATA),
+    ]
+}
+
+fn names(entries: &[(String, Vec<u8>)]) -> Vec<&str> {
+    entries.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+#[test]
+fn split_nsz_copies_compressed_entries_per_title() {
+    let root = scratch_dir("split-nsz");
+    let keys_file = write_keys_file(&root);
+    let entries = bundle_entries();
+    let input = root.join("bundle.nsp");
+    write_nsp(&input, &entries);
+    let compressed = compress(&CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("nsz")),
+        keys_file: Some(keys_file.clone()),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let bundle = read_nsp(&compressed.processed_files[0]);
+    let stored = |name: &str| {
+        bundle
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .unwrap_or_else(|| panic!("{name} missing from bundle"))
+            .clone()
+    };
+
+    let report = split(&SplitRequest {
+        files: compressed.processed_files,
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        ..Default::default()
+    })
+    .unwrap();
+    let out = root.join("out");
+    assert_eq!(
+        report.processed_files,
+        vec![
+            out.join("bundle [0100000000001000].nsz"),
+            out.join("bundle [0100000000001800].nsz"),
+            out.join("bundle [0100000000002001].nsz"),
+        ]
+    );
+
+    let base = read_nsp(&report.processed_files[0]);
+    let base_ncz = format!("{}.ncz", hex(&BASE_PROGRAM));
+    assert_eq!(names(&base), [base_ncz.as_str(), entries[1].0.as_str()]);
+    assert_eq!(base[0], stored(&base_ncz));
+
+    let update = read_nsp(&report.processed_files[1]);
+    let update_ncz = format!("{}.ncz", hex(&UPDATE_PROGRAM));
+    assert_eq!(
+        names(&update),
+        [
+            update_ncz.as_str(),
+            entries[3].0.as_str(),
+            entries[4].0.as_str(),
+            entries[5].0.as_str(),
+        ]
+    );
+    assert_eq!(update[0], stored(&update_ncz));
+    assert_eq!(update[2], entries[4]);
+
+    let dlc = read_nsp(&report.processed_files[2]);
+    assert_eq!(dlc[1], entries[7]);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn split_xci_writes_nsp_per_title() {
+    let root = scratch_dir("split-xci");
+    let keys_file = write_keys_file(&root);
+    let entries = bundle_entries();
+    let input = root.join("cart.xci");
+    fs::write(&input, build_xci(&[("secure", build_hfs0(&entries))])).unwrap();
+
+    let report = split(&SplitRequest {
+        files: vec![input, root.join("readme.txt")],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(report.skipped_files, vec![root.join("readme.txt")]);
+    assert_eq!(report.processed_files.len(), 3);
+    assert_eq!(read_nsp(&report.processed_files[1]), entries[2..6].to_vec());
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn split_rejects_entries_no_cnmt_lists() {
+    let root = scratch_dir("split-orphan");
+    let keys_file = write_keys_file(&root);
+    let mut entries = bundle_entries();
+    entries.push((
+        format!("{}.nca", hex(&[0xEE; 16])),
+        content_nca([0; 16], UPDATE_TITLE_KEY, 0x44),
+    ));
+    let input = root.join("bundle.nsp");
+    write_nsp(&input, &entries);
+
+    let err = split(&SplitRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert!(matches!(err, NszError::ContainerFormat { .. }), "{err}");
+    assert!(err.to_string().contains("not listed by any CNMT"), "{err}");
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_nca, encrypt_title_key, hex, keys_text, write_keys_file, NcaSpec, SectionSpec};
+
+const RIGHTS_ID: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const TITLE_KEY: [u8; 16] = [0xA5; 16];
+
+fn rights_id_nca() -> Vec<u8> {
+    let mut spec = NcaSpec::program(
+        TITLE_KEY,
+        vec![SectionSpec {
+            data: vec![0u8; 0x8000],
+            crypto_type: 3,
+            ctr_upper: 0x0000_0001_0000_0000,
+        }],
+    );
+    spec.rights_id = RIGHTS_ID;
+    build_nca(&spec)
+}
+
+#[test]
+fn title_keys_parse_both_supported_layouts() {
+    let content = format!(
+        "# comment\n{} = {}\n\n0100000000002000000000000000000{}|{}|Some Title\n",
+        hex(&RIGHTS_ID),
+        hex(&[0x11; 16]),
+        "1",
+        hex(&[0x22; 16])
+    );
+
+    let parsed = nsz_rs::container::nca::parse_title_keys_str(&content).unwrap();
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[&RIGHTS_ID].encrypted_title_key, [0x11; 16]);
+
+    let err = nsz_rs::container::nca::parse_title_keys_str("0011 = zz").unwrap_err();
+    assert!(
+        err.to_string().contains("line 1"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn title_keys_resolve_rights_id_nca_without_ticket() {
+    let nca = rights_id_nca();
+    let keys = nsz_rs::container::nca::NcaKeySet::from_keys_str(&keys_text()).unwrap();
+
+    let missing =
+        nsz_rs::container::nca::build_compression_plan(&nca, &keys, &HashMap::new()).unwrap_err();
+    assert!(missing.to_string().contains("rights id"), "{missing}");
+
+    let title_keys = nsz_rs::container::nca::parse_title_keys_str(&format!(
+        "{}={}",
+        hex(&RIGHTS_ID),
+        hex(&encrypt_title_key(&TITLE_KEY))
+    ))
+    .unwrap();
+    let mut tickets = HashMap::new();
+    nsz_rs::container::nca::merge_title_keys(&mut tickets, &title_keys);
+
+    let plan = nsz_rs::container::nca::build_compression_plan(&nca, &keys, &tickets).unwrap();
+    assert_eq!(plan.sections.len(), 1);
+    assert_eq!(plan.sections[0].crypto_key, TITLE_KEY);
+}
+
+#[test]
+fn title_keys_file_enables_planned_standalone_nca_compression() {
+    let root = std::env::temp_dir().join(format!("nsz-rs-title-keys-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let keys_file = write_keys_file(&root);
+
+    let title_keys_file = root.join("title.keys");
+    fs::write(
+        &title_keys_file,
+        format!(
+            "{} = {}\n",
+            hex(&RIGHTS_ID),
+            hex(&encrypt_title_key(&TITLE_KEY))
+        ),
+    )
+    .unwrap();
+
+    let nca = rights_id_nca();
+    let input = root.join("fixture.nca");
+    fs::write(&input, &nca).unwrap();
+
+    let report = nsz_rs::compress(&nsz_rs::CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("out")),
+        keys_file: Some(keys_file),
+        title_keys_file: Some(title_keys_file),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let ncz = fs::read(&report.processed_files[0]).unwrap();
+    let sections = nsz_rs::ncz::decompress::parse_sections(&ncz).unwrap();
+    assert!(sections
+        .iter()
+        .any(|section| section.crypto_type == 3 && section.crypto_key == TITLE_KEY));
+    assert_eq!(
+        nsz_rs::ncz::decompress::decompress_ncz_to_vec(&ncz).unwrap(),
+        nca
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_nca, build_pfs0, hex, scratch_dir, write_keys_file, NcaSpec, SectionSpec};
+use nsz_rs::{EntryStatus, FileStatus, NszError, VerifySummary, VerifyVerdict};
+use sha2::{Digest, Sha256};
+
+const WRONG_NAME: &str = "0123456789abcdef0123456789abcdef.nca";
+const OTHER_WRONG_NAME: &str = "fedcba9876543210fedcba9876543210.nca";
+
+fn hashed_name(bytes: &[u8]) -> String {
+    format!("{}.nca", hex(&Sha256::digest(bytes)[..16]))
+}
+
+fn verify_request(files: Vec<PathBuf>) -> nsz_rs::VerifyRequest {
+    nsz_rs::VerifyRequest {
+        files,
+        continue_on_error: true,
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn every_entry_is_checked_and_reported() {
+    let root = scratch_dir("verify-entries-corrupt");
+    let good = b"good-content".to_vec();
+    let good_name = hashed_name(&good);
+    let input = root.join("corrupt.nsp");
+    fs::write(
+        &input,
+        build_pfs0(&[
+            (WRONG_NAME, b"first tampered"),
+            (good_name.as_str(), good.as_slice()),
+            (OTHER_WRONG_NAME, b"second tampered"),
+            ("0123456789abcdef0123456789abcdef.cnmt.nca", b"meta"),
+            ("short.nca", b"unnamed"),
+            ("0123456789abcdef0123456789abcdef.tik", b"ticket"),
+        ]),
+    )
+    .unwrap();
+
+    let err = nsz_rs::verify(&verify_request(vec![input.clone()])).unwrap_err();
+    let NszError::BatchFailed { files, .. } = err else {
+        panic!("expected a batch failure, got {err}");
+    };
+    let file = &files[0];
+    assert_eq!(file.status, FileStatus::Failed);
+    assert_eq!(file.verdict(), Some(VerifyVerdict::Corrupt));
+    assert_eq!(
+        file.result().unwrap_err().entry.as_deref(),
+        Some(WRONG_NAME)
+    );
+    assert_eq!(file.result().unwrap_err().kind, "hash_mismatch");
+
+    let statuses: Vec<_> = file
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (WRONG_NAME, EntryStatus::Mismatched),
+            (good_name.as_str(), EntryStatus::Matched),
+            (OTHER_WRONG_NAME, EntryStatus::Mismatched),
+            (
+                "0123456789abcdef0123456789abcdef.cnmt.nca",
+                EntryStatus::SkippedMeta
+            ),
+            ("short.nca", EntryStatus::SkippedShortName),
+        ]
+    );
+    let mismatched = &file.entries[2];
+    assert_eq!(
+        mismatched.expected_sha256.as_deref(),
+        Some("fedcba9876543210fedcba9876543210")
+    );
+    assert_eq!(
+        mismatched.actual_sha256,
+        Some(format!("{:x}", Sha256::digest(b"second tampered")))
+    );
+    assert!(!mismatched.keys_available);
+    assert_eq!(file.entries[3].expected_sha256, None);
+    assert_eq!(
+        VerifySummary::from_files(&files),
+        VerifySummary {
+            corrupt: 1,
+            ..Default::default()
+        }
+    );
+
+    let err = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        continue_on_error: false,
+        ..verify_request(vec![input])
+    })
+    .unwrap_err();
+    let NszError::HashMismatch {
+        expected_sha256, ..
+    } = err.root()
+    else {
+        panic!("expected a hash mismatch, got {err}");
+    };
+    assert_eq!(expected_sha256, "0123456789abcdef0123456789abcdef");
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn summary_separates_intact_from_unverifiable() {
+    let root = scratch_dir("verify-entries-summary");
+    let good = b"good-content".to_vec();
+    let good_name = hashed_name(&good);
+    let intact = root.join("intact.nsp");
+    let partial = root.join("partial.nsp");
+    let unreadable = root.join("unreadable.nsz");
+    fs::write(
+        &intact,
+        build_pfs0(&[(good_name.as_str(), good.as_slice())]),
+    )
+    .unwrap();
+    fs::write(
+        &partial,
+        build_pfs0(&[(good_name.as_str(), good.as_slice()), ("short.nca", b"x")]),
+    )
+    .unwrap();
+    fs::write(
+        &unreadable,
+        build_pfs0(&[("fedcba9876543210fedcba9876543210.ncz", b"truncated")]),
+    )
+    .unwrap();
+
+    let report = nsz_rs::verify(&verify_request(vec![intact.clone(), partial])).unwrap();
+    assert_eq!(report.files[0].verdict(), Some(VerifyVerdict::Intact));
+    assert_eq!(report.files[1].verdict(), Some(VerifyVerdict::Unverifiable));
+    assert_eq!(
+        report.summary(),
+        VerifySummary {
+            intact: 1,
+            unverifiable: 1,
+            ..Default::default()
+        }
+    );
+
+    let missing = root.join("missing.nsp");
+    let err = nsz_rs::verify(&verify_request(vec![intact, unreadable, missing])).unwrap_err();
+    let NszError::BatchFailed { files, .. } = err else {
+        panic!("expected a batch failure, got {err}");
+    };
+    let entry = &files[1].entries[0];
+    assert_eq!(entry.status, EntryStatus::Unreadable);
+    assert_eq!(entry.actual_sha256, None);
+    assert!(entry.error.is_some());
+    assert_eq!(
+        VerifySummary::from_files(&files),
+        VerifySummary {
+            intact: 1,
+            corrupt: 1,
+            failed: 1,
+            ..Default::default()
+        }
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn entries_record_whether_keys_decrypt_the_header() {
+    let root = scratch_dir("verify-entries-keys");
+    let nca = build_nca(&NcaSpec::program(
+        [0x4E; 16],
+        vec![SectionSpec {
+            data: vec![0x11; 0x1000],
+            crypto_type: 3,
+            ctr_upper: 0x0000_0001_0000_0000,
+        }],
+    ));
+    let input = root.join(hashed_name(&nca));
+    fs::write(&input, &nca).unwrap();
+    let keys_file = write_keys_file(&root);
+
+    let report = nsz_rs::verify(&verify_request(vec![input.clone()])).unwrap();
+    assert!(!report.files[0].entries[0].keys_available);
+
+    let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
+        keys_file: Some(keys_file),
+        ..verify_request(vec![input])
+    })
+    .unwrap();
+    let entry = &report.files[0].entries[0];
+    assert!(entry.keys_available);
+    assert_eq!(entry.status, EntryStatus::Matched);
+    assert_eq!(entry.partition, None);
+    assert_eq!(report.files[0].verdict(), Some(VerifyVerdict::Intact));
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn verify_events_carry_entries_and_verdict() {
+    let root = scratch_dir("verify-entries-json");
+    let good = b"good-content".to_vec();
+    let good_name = hashed_name(&good);
+    let input = root.join("intact.nsp");
+    fs::write(&input, build_pfs0(&[(good_name.as_str(), good.as_slice())])).unwrap();
+
+    let report = nsz_rs::verify(&verify_request(vec![input])).unwrap();
+    let event: serde_json::Value = serde_json::from_str(&report.files[0].to_json_line()).unwrap();
+    assert_eq!(event["verdict"], "intact");
+    assert_eq!(event["entries"][0]["name"], good_name);
+    assert_eq!(event["entries"][0]["status"], "matched");
+    assert_eq!(
+        event["entries"][0]["expected_sha256"],
+        good_name.trim_end_matches(".nca")
+    );
+    assert_eq!(serde_json::to_value(report.summary()).unwrap()["intact"], 1);
+
+    let _ = fs::remove_dir_all(root);
+}
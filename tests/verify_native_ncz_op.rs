@@ -32,7 +32,11 @@ fn verify_uses_native_path_for_ncz_inputs() {
     let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
         files: vec![input.clone()],
         fix_padding: false,
+        continue_on_error: false,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
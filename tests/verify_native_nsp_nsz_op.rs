@@ -23,7 +23,11 @@ fn verify_uses_native_path_for_nsp_inputs() {
     let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
         files: vec![input.clone()],
         fix_padding: false,
+        continue_on_error: false,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
@@ -64,7 +68,11 @@ fn verify_uses_native_path_for_nsz_inputs() {
     let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
         files: vec![input.clone()],
         fix_padding: false,
+        continue_on_error: false,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
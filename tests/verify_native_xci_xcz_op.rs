@@ -26,7 +26,11 @@ fn verify_uses_native_path_for_xci_inputs() {
     let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
         files: vec![input.clone()],
         fix_padding: false,
+        continue_on_error: false,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
@@ -70,7 +74,11 @@ fn verify_uses_native_path_for_xcz_inputs() {
     let report = nsz_rs::verify(&nsz_rs::VerifyRequest {
         files: vec![input.clone()],
         fix_padding: false,
+        continue_on_error: false,
+        keys_file: None,
         python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        progress: nsz_rs::Progress::default(),
+        cancel: nsz_rs::CancellationToken::default(),
     })
     .unwrap();
 
@@ -118,8 +126,17 @@ fn build_xci_like(root_hfs0: &[u8]) -> Vec<u8> {
     let mut out = vec![0u8; 0x200];
     out[0x100..0x104].copy_from_slice(b"HEAD");
     out[0x130..0x138].copy_from_slice(&hfs0_offset.to_le_bytes());
-    out[0x138..0x140].copy_from_slice(&(root_hfs0.len() as u64).to_le_bytes());
+    let header_size = hfs0_header_size(root_hfs0);
+    out[0x138..0x140].copy_from_slice(&header_size.to_le_bytes());
+    out[0x140..0x160].copy_from_slice(&Sha256::digest(&root_hfs0[..header_size as usize]));
     out.resize(usize::try_from(hfs0_offset).unwrap(), 0);
     out.extend_from_slice(root_hfs0);
     out
 }
+
+/// Size of an HFS0 header: the fixed header, entry table and string table.
+fn hfs0_header_size(hfs0: &[u8]) -> u64 {
+    let count = u32::from_le_bytes(hfs0[4..8].try_into().unwrap());
+    let string_table_size = u32::from_le_bytes(hfs0[8..12].try_into().unwrap());
+    0x10 + u64::from(count) * 0x40 + u64::from(string_table_size)
+}
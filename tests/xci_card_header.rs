@@ -0,0 +1,110 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_hfs0, build_xci, raw_nca, scratch_dir};
+use nsz_rs::container::xci::{XciArchive, XciCardHeader};
+use nsz_rs::{compress, decompress, CompressRequest, DecompressRequest};
+
+/// Builds an XCI with every header field set and distinct bytes throughout the pre-HFS0 area.
+fn populated_xci() -> Vec<u8> {
+    let nca = raw_nca([0x3C; 0x2000]);
+    let secure = build_hfs0(&[("0123456789abcdef0123456789abcdef.nca".to_string(), nca)]);
+    let mut xci = build_xci(&[("secure", secure)]);
+    for (offset, byte) in xci[..0x100].iter_mut().enumerate() {
+        *byte = offset as u8;
+    }
+    xci[0x104..0x108].copy_from_slice(&0x0000_0048u32.to_le_bytes());
+    xci[0x108..0x10C].copy_from_slice(&u32::MAX.to_le_bytes());
+    xci[0x10C] = 0x12;
+    xci[0x10D] = 0xF8;
+    xci[0x10E] = 0x01;
+    xci[0x10F] = 0x02;
+    xci[0x110..0x118].copy_from_slice(&0x1122_3344_5566_7788u64.to_le_bytes());
+    xci[0x118..0x11C].copy_from_slice(&0x0000_1234u32.to_le_bytes());
+    xci[0x120..0x130].copy_from_slice(&[0xA5; 0x10]);
+    xci[0x140..0x160].copy_from_slice(&[0xB6; 0x20]);
+    xci[0x160..0x180].copy_from_slice(&[0xC7; 0x20]);
+    xci[0x180..0x184].copy_from_slice(&1u32.to_le_bytes());
+    xci[0x184..0x188].copy_from_slice(&2u32.to_le_bytes());
+    xci[0x188..0x18C].copy_from_slice(&3u32.to_le_bytes());
+    xci[0x18C..0x190].copy_from_slice(&0x0000_0040u32.to_le_bytes());
+    xci[0x190..0x200].copy_from_slice(&[0xD8; 0x70]);
+    xci[0x1000..0x1200].fill(0x1D);
+    xci[0x7000..0x7200].fill(0xCE);
+    xci
+}
+
+#[test]
+fn card_header_fields_parse() {
+    let xci = populated_xci();
+    let header = XciCardHeader::from_bytes(&xci).unwrap();
+    assert_eq!(header.signature[0xFF], 0xFF);
+    assert_eq!(header.secure_area_start, 0x48);
+    assert_eq!(header.secure_area_offset(), 0x9000);
+    assert_eq!(header.backup_area_start, u32::MAX);
+    assert_eq!(header.key_index, 0x12);
+    assert_eq!(header.rom_size, 0xF8);
+    assert_eq!(header.version, 0x01);
+    assert_eq!(header.flags, 0x02);
+    assert_eq!(header.package_id, 0x1122_3344_5566_7788);
+    assert_eq!(header.valid_data_end, 0x1234);
+    assert_eq!(header.iv, [0xA5; 0x10]);
+    assert_eq!(header.hfs0_offset, 0xF000);
+    assert_eq!(header.hfs0_header_hash, [0xB6; 0x20]);
+    assert_eq!(header.initial_data_hash, [0xC7; 0x20]);
+    assert_eq!(
+        (header.sel_sec, header.sel_t1_key, header.sel_key),
+        (1, 2, 3)
+    );
+    assert_eq!(header.normal_area_end_offset(), 0x8000);
+    assert_eq!(header.encrypted_gamecard_info, [0xD8; 0x70]);
+
+    let mut with_key_area = vec![0x4Bu8; 0x1000];
+    with_key_area.extend_from_slice(&xci);
+    let archive = XciArchive::from_bytes(&with_key_area).unwrap();
+    assert_eq!(archive.header_offset, 0x1000);
+    assert_eq!(archive.card_header, header);
+}
+
+#[test]
+fn xcz_roundtrip_preserves_bytes_outside_hfs0_tree() {
+    let root = scratch_dir("xci-card-header");
+    let mut input_bytes = vec![0x4Bu8; 0x1000];
+    input_bytes.extend_from_slice(&populated_xci());
+    let input = root.join("game.xci");
+    fs::write(&input, &input_bytes).unwrap();
+    let prefix_len = 0x1000 + 0xF000;
+
+    let compressed = compress(&CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("xcz")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let xcz_bytes = fs::read(&compressed.processed_files[0]).unwrap();
+    assert_eq!(xcz_bytes[..prefix_len], input_bytes[..prefix_len]);
+
+    let decompressed = decompress(&DecompressRequest {
+        files: compressed.processed_files,
+        output_dir: Some(root.join("xci")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let xci_bytes = fs::read(&decompressed.processed_files[0]).unwrap();
+    // Decompression only refreshes the root HFS0 header hash at 0x140.
+    let hash_range = 0x1140..0x1160;
+    assert_eq!(
+        xci_bytes[..hash_range.start],
+        input_bytes[..hash_range.start]
+    );
+    assert_eq!(
+        xci_bytes[hash_range.end..prefix_len],
+        input_bytes[hash_range.end..prefix_len]
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
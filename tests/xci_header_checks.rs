@@ -0,0 +1,222 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{build_hfs0, build_xci, hex, keys_text, raw_nca, scratch_dir, write_keys_file};
+use nsz_rs::container::xci::{XciArchive, XciSignatureStatus};
+use nsz_rs::{
+    compress, decompress, verify, CompressRequest, DecompressRequest, EntryStatus, NszError,
+    VerifyRequest, VerifySummary, VerifyVerdict,
+};
+use rsa::{BigUint, Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+
+/// Test-only RSA-2048 key standing in for the gamecard header signing key.
+const TEST_MODULUS: &[&str] = &[
+    "ca20cdefe70f408324f7bd3b943088429021e3df7263aa849843b8fc367be4d2",
+    "ded3b8b45597386bf7be6e8aaaa999eefffcfc116a60313b5b1a88fe7a5005d6",
+    "fa3ab342ae14eeff5cf58a25a58e1a9eb230ebc4647ae1488f4ecf5fbbdc4317",
+    "0498cf3b7e4cbefb7b3b96f9cdc23dbbc69de1a425f91733aa0aaa59868850fb",
+    "4141c38cf6e2504a38b3aec82cb372637df37efb34465e69b051a4c8dbc8825f",
+    "e796d420f79080cfcac8633c08a9b4e5610df9974565b2392946ff18af013128",
+    "8979296036c0cf6924fe3a69cf81b221c3d358f5f66278193cafe7cd5d837b0a",
+    "9536fa85d410fa204bbf9a16a42e8533393e8c52ca24f1d1ed25870aa5e6aa25",
+];
+const TEST_PRIVATE_EXPONENT: &[&str] = &[
+    "c46a8e54531f421c6e9f03575310ba489c912243af7fe462c7280029aeb4444f",
+    "19d37a085ba8005f02b71dea481f5983a3646e7a69f9a1bdc1310ef0e360c1db",
+    "fefb6dd91d1b20882c083abf8c9a8389b534ced6e0a17c2c2b7e750bdd9d13e0",
+    "d949b90f621327ff6ee820350d5a74ecf4fbd519d300b4283353fbe7d550c007",
+    "3d4ff6831b9125d819bff612e6552fe66da592b72f978dac39d9d2190deb8ea0",
+    "7e4016d0ae1e78aba027e20fdad5b27524965a5a8f905899dc854f348206213e",
+    "054ed86171eaeb60bb930f72b32cefc06cb199dfbc27921154c0bfcc095ca925",
+    "09764b4218b8233d5acaf7e820b55528f5fbe3212586c5f18f89f5ed254fb2c1",
+];
+const TEST_PRIMES: [&[&str]; 2] = [
+    &[
+        "d3d00caaeb5794c77436af930b394b8c4155ebaae2af47d28d5f4fd9658b8424",
+        "d18ba940c7b0c6a124bf6ae1a41b2af5532447841ac082245106b80c640815b9",
+        "814750bb5bc738db964bbc34270ae1b4a661f1fb4aed4757aedee5f3618718e0",
+        "79d9cf252f49d970c6df298f02eec8e124bd4c3a73c231c6e9f818034b19ebb5",
+    ],
+    &[
+        "f44b8c179384a61ccc9db3dc7b1701be646e764cc6aadf6020aeeb91bd922668",
+        "89ce9bf1f6777ebecb7403195a647ea5935a06536d5cac9c089bc0bb4e2a8eb7",
+        "6e1deceb29037ac713a2e987e1587500b83fb201d22289ec5f99c3e014e9ed34",
+        "beaca6564f1338ed4d923b5a07eeb9b5ea369d546f42afbcc80c6193fe732ab1",
+    ],
+];
+
+fn big(hex_lines: &[&str]) -> BigUint {
+    BigUint::parse_bytes(hex_lines.concat().as_bytes(), 16).unwrap()
+}
+
+fn sample_xci() -> Vec<u8> {
+    let nca = raw_nca([0x6E; 0x1000]);
+    let name = format!("{}.nca", &hex(&Sha256::digest(&nca))[..32]);
+    build_xci(&[("secure", build_hfs0(&[(name, nca)]))])
+}
+
+fn sign_header(xci: &mut [u8]) {
+    let key = RsaPrivateKey::from_components(
+        big(TEST_MODULUS),
+        BigUint::from(0x10001u32),
+        big(TEST_PRIVATE_EXPONENT),
+        TEST_PRIMES.iter().map(|prime| big(prime)).collect(),
+    )
+    .unwrap();
+    let digest = Sha256::digest(&xci[0x100..0x200]);
+    let signature = key.sign(Pkcs1v15Sign::new::<Sha256>(), &digest).unwrap();
+    xci[..0x100].copy_from_slice(&signature);
+}
+
+fn keys_file_with_modulus(root: &std::path::Path) -> PathBuf {
+    let path = root.join("prod.keys");
+    fs::write(
+        &path,
+        format!(
+            "{}xci_header_sign_modulus = {}\n",
+            keys_text(),
+            TEST_MODULUS.concat()
+        ),
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn verify_reports_header_signature_status() {
+    let root = scratch_dir("xci-header-signature");
+    let keys_file = keys_file_with_modulus(&root);
+    let mut signed = sample_xci();
+    sign_header(&mut signed);
+    let mut edited = signed.clone();
+    edited[0x10F] ^= 0x01;
+    fs::write(root.join("signed.xci"), &signed).unwrap();
+    fs::write(root.join("edited.xci"), &edited).unwrap();
+    let files = vec![root.join("signed.xci"), root.join("edited.xci")];
+
+    let report = verify(&VerifyRequest {
+        files: files.clone(),
+        keys_file: Some(keys_file),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(report.verified_files, files);
+    assert_eq!(
+        report.header_signatures,
+        vec![
+            (files[0].clone(), XciSignatureStatus::Valid),
+            (files[1].clone(), XciSignatureStatus::Invalid),
+        ]
+    );
+
+    let keys_without_modulus = root.join("keys.txt");
+    fs::write(&keys_without_modulus, keys_text()).unwrap();
+    let report = verify(&VerifyRequest {
+        files: vec![files[0].clone()],
+        keys_file: Some(keys_without_modulus),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+        report.header_signatures,
+        vec![(files[0].clone(), XciSignatureStatus::Unchecked)]
+    );
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn verify_rejects_root_hfs0_header_hash_mismatch() {
+    let root = scratch_dir("xci-header-hash");
+    let mut xci = sample_xci();
+    xci[0x140] ^= 0xFF;
+    let input = root.join("game.xci");
+    fs::write(&input, &xci).unwrap();
+    let keys_file = write_keys_file(&root);
+
+    let request = VerifyRequest {
+        files: vec![input],
+        keys_file: Some(keys_file),
+        ..Default::default()
+    };
+    let err = verify(&request).unwrap_err();
+    assert_eq!(err.entry(), Some("root HFS0 header"));
+    let NszError::HashMismatch {
+        expected_sha256,
+        actual_sha256,
+    } = err.root()
+    else {
+        panic!("unexpected error: {err}");
+    };
+    assert_ne!(expected_sha256, actual_sha256);
+    assert_eq!(expected_sha256.len(), 64);
+
+    // The partitions are still checked, so the input is judged corrupt with every entry.
+    let err = verify(&VerifyRequest {
+        continue_on_error: true,
+        ..request
+    })
+    .unwrap_err();
+    let NszError::BatchFailed { files, .. } = err else {
+        panic!("expected a batch failure, got {err}");
+    };
+    let statuses = files[0]
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.status))
+        .collect::<Vec<_>>();
+    assert_eq!(statuses[0], ("root HFS0 header", EntryStatus::Mismatched));
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[1].1, EntryStatus::Matched);
+    assert_eq!(files[0].verdict(), Some(VerifyVerdict::Corrupt));
+    assert_eq!(VerifySummary::from_files(&files).corrupt, 1);
+
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn decompressed_xci_carries_recomputed_root_hfs0_hash() {
+    let root = scratch_dir("xci-header-rehash");
+    let input = root.join("game.xci");
+    fs::write(&input, sample_xci()).unwrap();
+    let keys_file = write_keys_file(&root);
+
+    let compressed = compress(&CompressRequest {
+        files: vec![input],
+        output_dir: Some(root.join("xcz")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let xcz = fs::read(&compressed.processed_files[0]).unwrap();
+    let xcz_archive = XciArchive::from_bytes(&xcz).unwrap();
+    assert_ne!(
+        xcz_archive.compute_root_hfs0_header_hash(&xcz).unwrap(),
+        xcz_archive.card_header.hfs0_header_hash
+    );
+
+    let decompressed = decompress(&DecompressRequest {
+        files: compressed.processed_files,
+        output_dir: Some(root.join("xci")),
+        python_repo_root: Some(PathBuf::from("/does/not/exist")),
+        ..Default::default()
+    })
+    .unwrap();
+    let xci = fs::read(&decompressed.processed_files[0]).unwrap();
+    let archive = XciArchive::from_bytes(&xci).unwrap();
+    assert_eq!(
+        archive.compute_root_hfs0_header_hash(&xci).unwrap(),
+        archive.card_header.hfs0_header_hash
+    );
+    verify(&VerifyRequest {
+        files: decompressed.processed_files,
+        keys_file: Some(keys_file),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let _ = fs::remove_dir_all(root);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+
+use common::{build_hfs0, build_xci, scratch_dir};
+use nsz_rs::{trim, untrim, NszError, TrimRequest};
+
+/// Usable bytes of a 1 GiB card once ECC is reserved.
+const ONE_GIB_CARD_SIZE: u64 = 0x3B80_0000;
+
+/// Builds an XCI whose card header declares exactly its own bytes as used data on a 1 GiB card.
+fn used_xci() -> Vec<u8> {
+    let secure = build_hfs0(&[("00.nca".to_string(), vec![0x5A; 0x300])]);
+    let mut xci = build_xci(&[("update", build_hfs0(&[])), ("secure", secure)]);
+    xci.resize(xci.len().next_multiple_of(0x200), 0);
+    let valid_data_end = u32::try_from(xci.len() / 0x200 - 1).unwrap();
+    xci[0x10D] = 0xFA;
+    xci[0x118..0x11C].copy_from_slice(&valid_data_end.to_le_bytes());
+    xci
+}
+
+#[test]
+fn trim_cuts_padding_and_untrim_restores_full_card() {
+    let root = scratch_dir("xci-trim");
+    let used = used_xci();
+    let mut padded = used.clone();
+    padded.resize(used.len() + 0x8000, 0xFF);
+    let input = root.join("game.xci");
+    fs::write(&input, &padded).unwrap();
+
+    let trimmed_dir = root.join("trimmed");
+    let report = trim(&TrimRequest {
+        files: vec![input, root.join("notes.txt")],
+        output_dir: Some(trimmed_dir.clone()),
+    })
+    .unwrap();
+    assert_eq!(report.processed_files, vec![trimmed_dir.join("game.xci")]);
+    assert_eq!(report.skipped_files, vec![root.join("notes.txt")]);
+    assert_eq!(fs::read(trimmed_dir.join("game.xci")).unwrap(), used);
+
+    let untrimmed_dir = root.join("untrimmed");
+    untrim(&TrimRequest {
+        files: vec![trimmed_dir.join("game.xci")],
+        output_dir: Some(untrimmed_dir.clone()),
+    })
+    .unwrap();
+    let mut untrimmed = File::open(untrimmed_dir.join("game.xci")).unwrap();
+    assert_eq!(untrimmed.metadata().unwrap().len(), ONE_GIB_CARD_SIZE);
+    let mut head = vec![0u8; padded.len()];
+    untrimmed.read_exact(&mut head).unwrap();
+    assert_eq!(head, padded);
+    untrimmed.seek(SeekFrom::End(-0x1000)).unwrap();
+    let mut tail = Vec::new();
+    untrimmed.read_to_end(&mut tail).unwrap();
+    assert!(tail.iter().all(|byte| *byte == 0xFF));
+
+    drop(untrimmed);
+    let _ = fs::remove_dir_all(root);
+}
+
+#[test]
+fn trim_and_untrim_refuse_to_drop_non_padding_bytes() {
+    let root = scratch_dir("xci-trim-reject");
+    let mut xci = used_xci();
+    xci.extend_from_slice(&[0xFF; 0x100]);
+    xci.push(0x00);
+    let input = root.join("game.xci");
+    fs::write(&input, &xci).unwrap();
+    let request = TrimRequest {
+        files: vec![input.clone()],
+        output_dir: Some(root.join("out")),
+    };
+
+    let err = trim(&request).unwrap_err();
+    assert!(matches!(err, NszError::ContainerFormat { .. }), "{err}");
+    assert!(err.to_string().contains("non-padding"), "{err}");
+    let err = untrim(&request).unwrap_err();
+    assert!(err.to_string().contains("non-padding"), "{err}");
+
+    let mut unknown_rom_size = used_xci();
+    unknown_rom_size[0x10D] = 0x42;
+    fs::write(&input, &unknown_rom_size).unwrap();
+    let err = untrim(&request).unwrap_err();
+    assert!(matches!(err, NszError::UnsupportedFeature { .. }), "{err}");
+
+    let _ = fs::remove_dir_all(root);
+}